@@ -35,7 +35,7 @@ fn main() -> io::Result<()> {
                 .short('a')
                 .long("address")
                 .value_name("ADDRESS")
-                .default_value("0x1234")
+                .default_value("0x0000000000000000000000000000000000001234")
                 .help("Address to assign the contract to"),
         )
         .get_matches();
@@ -52,8 +52,15 @@ fn main() -> io::Result<()> {
     };
 
     let address = matches.get_one::<String>("address").unwrap();
+    let address = match jet_runtime::exec::parse_address(address) {
+        Some(address) => jet_runtime::exec::format_address(&address),
+        None => {
+            eprintln!("Error: invalid address: {}", address);
+            std::process::exit(1);
+        }
+    };
 
-    let output = match compile_evm_to_llvm_ir(address, bytecode.as_slice()) {
+    let output = match compile_evm_to_llvm_ir(&address, bytecode.as_slice()) {
         Ok(output) => output,
         Err(e) => {
             eprintln!("Error: {}", e);
@@ -91,8 +98,13 @@ fn parse_hex(s: &str) -> Vec<u8> {
 fn compile_evm_to_llvm_ir(address: &str, bytecode: &[u8]) -> Result<String, Error> {
     let context = Context::create();
     let module = jet_runtime::module::load(&context).unwrap();
-    let build_opts =
-        jet::builder::env::Options::new(jet::builder::env::Mode::Debug, false, false, true);
+    let build_opts = jet::builder::env::Options::new(
+        jet::builder::env::Mode::Debug,
+        false,
+        false,
+        true,
+        jet_runtime::spec::SpecId::default(),
+    );
     let env = jet::builder::env::Env::new(&context, module, build_opts);
     let manager = jet::builder::manager::Manager::new(env);
 
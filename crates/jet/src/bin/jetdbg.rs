@@ -5,7 +5,10 @@ use simple_logger::SimpleLogger;
 use thiserror::Error;
 
 use jet::instructions::Instruction;
-use jet_runtime::{self, exec};
+use jet_runtime::{self, exec, spec::SpecId, state::InMemoryStorage};
+
+// Gas given to every run
+const GAS_LIMIT: u64 = 30_000_000;
 
 #[derive(Parser, Debug)]
 #[command(author, version, about, long_about = None)]
@@ -58,6 +61,7 @@ fn build_cmd(args: BuildArgs) -> Result<(), Error> {
         args.use_vstack.unwrap_or(false),
         args.emit_llvm.unwrap_or(true),
         args.assert.unwrap_or(true),
+        SpecId::default(),
     );
 
     // let alice_rom = [
@@ -137,8 +141,7 @@ fn build_cmd(args: BuildArgs) -> Result<(), Error> {
         Instruction::PUSH2.opcode(), // Address
         0x00,
         0x01,
-        Instruction::PUSH1.opcode(), // Gas
-        0x00,
+        Instruction::GAS.opcode(),  // Gas
         Instruction::CALL.opcode(), // Mem: 0x00FF
         Instruction::RETURNDATASIZE.opcode(),
         Instruction::PUSH1.opcode(), // Len
@@ -170,15 +173,23 @@ fn build_cmd(args: BuildArgs) -> Result<(), Error> {
 
     // Create the LLVM JIT engine
     let context = Context::create();
-    let mut engine = jet::engine::Engine::new(&context, build_opts)?;
+    let engine = jet::engine::Engine::new(&context, build_opts)?;
 
     // Build the contract
-    engine.build_contract("0x1234", alice_rom.as_slice())?;
-    engine.build_contract("0x0001", bob_rom.as_slice())?;
-
-    // Run the contract with a test block
+    engine.build_contract(
+        "0x0000000000000000000000000000000000001234",
+        alice_rom.as_slice(),
+    )?;
+    engine.build_contract(
+        "0x0000000000000000000000000000000000000001",
+        bob_rom.as_slice(),
+    )?;
+
+    // Run the contract with a test block and transaction
     let block_info = new_test_block_info();
-    let run = engine.run_contract("0x1234", &block_info)?;
+    let tx_info = new_test_tx_info("0x0000000000000000000000000000000000001234");
+    let storage = Box::new(InMemoryStorage::new());
+    let run = engine.run_contract(&tx_info, &block_info, &[], GAS_LIMIT, &[], storage)?;
     info!("{}", run);
 
     Ok(())
@@ -214,7 +225,8 @@ fn new_test_block_info() -> exec::BlockInfo {
         25, 26, 27, 28, 29, 30, 31,
     ];
     let hash_history = new_test_block_info_hash_history();
-    let coinbase = [1, 0];
+    let mut coinbase = [0; jet_runtime::ADDRESS_SIZE_BYTES];
+    coinbase[0] = 1;
 
     exec::BlockInfo::new(
         42,
@@ -230,6 +242,13 @@ fn new_test_block_info() -> exec::BlockInfo {
     )
 }
 
+fn new_test_tx_info(address: &str) -> exec::TxInfo {
+    let address = exec::parse_address(address).unwrap();
+    let sender = [0; jet_runtime::ADDRESS_SIZE_BYTES];
+
+    exec::TxInfo::new(sender, [0; 32], sender, address, [0; 32])
+}
+
 fn new_test_block_info_hash_history() -> exec::HashHistory {
     let mut hash_history = [[0; 32]; jet_runtime::BLOCK_HASH_HISTORY_SIZE];
 
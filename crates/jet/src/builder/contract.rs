@@ -2,14 +2,15 @@ use std::cell::{Ref, RefCell, RefMut};
 
 use inkwell::{
     basic_block::BasicBlock,
-    values::{FunctionValue, IntValue},
+    module::Linkage,
+    values::{FunctionValue, GlobalValue, IntValue},
 };
 use log::{info, trace};
 
 use jet_runtime::exec::ReturnCode;
 
 use crate::{
-    builder::{env::Env, Error, ops},
+    builder::{env::Env, Error, gas, ops},
     instructions,
     instructions::{Instruction, IteratorItem},
 };
@@ -20,12 +21,14 @@ pub(crate) struct Registers<'ctx> {
     // Function parameters
     pub(crate) exec_ctx: inkwell::values::PointerValue<'ctx>,
     pub(crate) block_info: inkwell::values::PointerValue<'ctx>,
+    pub(crate) tx_info: inkwell::values::PointerValue<'ctx>,
 
     // Pointers into the exec context
     pub(crate) jump_ptr: inkwell::values::PointerValue<'ctx>,
     pub(crate) return_offset: inkwell::values::PointerValue<'ctx>,
     pub(crate) return_length: inkwell::values::PointerValue<'ctx>,
-    pub(crate) sub_call: inkwell::values::PointerValue<'ctx>,
+    pub(crate) memory_len: inkwell::values::PointerValue<'ctx>,
+    pub(crate) gas_remaining: inkwell::values::PointerValue<'ctx>,
 }
 
 impl<'ctx> Registers<'ctx> {
@@ -37,6 +40,7 @@ impl<'ctx> Registers<'ctx> {
         let t = env.types();
         let exec_ctx = func.get_nth_param(0).unwrap().into_pointer_value();
         let block_info = func.get_nth_param(1).unwrap().into_pointer_value();
+        let tx_info = func.get_nth_param(2).unwrap().into_pointer_value();
 
         let jump_ptr = builder
             .build_struct_gep(t.exec_ctx, exec_ctx, 1, "jump_ptr")
@@ -47,18 +51,23 @@ impl<'ctx> Registers<'ctx> {
         let return_length = builder
             .build_struct_gep(t.exec_ctx, exec_ctx, 3, "return_length")
             .unwrap();
-        let sub_call = builder
-            .build_struct_gep(t.exec_ctx, exec_ctx, 4, "sub_call")
+        let memory_len = builder
+            .build_struct_gep(t.exec_ctx, exec_ctx, 5, "memory_len")
+            .unwrap();
+        let gas_remaining = builder
+            .build_struct_gep(t.exec_ctx, exec_ctx, 7, "gas_remaining")
             .unwrap();
 
         Self {
             exec_ctx,
             block_info,
+            tx_info,
 
             jump_ptr,
             return_offset,
             return_length,
-            sub_call,
+            memory_len,
+            gas_remaining,
         }
     }
 }
@@ -68,7 +77,11 @@ pub(crate) struct BuildCtx<'ctx, 'b> {
     pub(crate) builder: &'b inkwell::builder::Builder<'ctx>,
     pub(crate) registers: Registers<'ctx>,
     _vstack: RefCell<Vec<IntValue<'ctx>>>,
-    func: FunctionValue<'ctx>,
+    pub(crate) func: FunctionValue<'ctx>,
+
+    // The code the function was built from, embedded in the module
+    pub(crate) code: GlobalValue<'ctx>,
+    pub(crate) code_len: u32,
 }
 
 impl<'ctx, 'b> BuildCtx<'ctx, 'b> {
@@ -76,6 +89,8 @@ impl<'ctx, 'b> BuildCtx<'ctx, 'b> {
         env: &'b Env<'ctx>,
         builder: &'b inkwell::builder::Builder<'ctx>,
         func: FunctionValue<'ctx>,
+        code: GlobalValue<'ctx>,
+        code_len: u32,
     ) -> Self {
         let vstack = RefCell::new(Vec::with_capacity(VSTACK_INIT_SIZE));
         Self {
@@ -83,6 +98,8 @@ impl<'ctx, 'b> BuildCtx<'ctx, 'b> {
             builder,
             _vstack: vstack,
             func,
+            code,
+            code_len,
             registers: Registers::new(env, builder, func),
         }
     }
@@ -182,7 +199,8 @@ pub fn build(env: &'_ Env<'_>, name: &str, rom: &[u8]) -> Result<(), Error> {
     builder.position_at_end(preamble_block);
 
     // Build ROM into IR
-    let bctx = BuildCtx::new(env, &builder, func);
+    let code = embed_code(env, name, rom);
+    let bctx = BuildCtx::new(env, &builder, func, code, rom.len() as u32);
     let code_blocks = find_code_blocks(env, func, rom);
     build_contract_body(&bctx, &code_blocks)?;
 
@@ -194,6 +212,18 @@ pub fn build(env: &'_ Env<'_>, name: &str, rom: &[u8]) -> Result<(), Error> {
     Ok(())
 }
 
+/// Adds a constant holding the ROM to the module, so the contract can read its own code with
+/// CODESIZE and CODECOPY.
+fn embed_code<'ctx>(env: &Env<'ctx>, name: &str, rom: &[u8]) -> GlobalValue<'ctx> {
+    let code = env.context().const_string(rom, false);
+    let global_name = format!("{}{}", name, jet_runtime::symbols::CODE_SUFFIX);
+    let global = env.module().add_global(code.get_type(), None, &global_name);
+    global.set_initializer(&code);
+    global.set_constant(true);
+    global.set_linkage(Linkage::Private);
+    global
+}
+
 fn find_code_blocks<'ctx, 'b>(
     env: &Env<'ctx>,
     func: FunctionValue<'ctx>,
@@ -203,12 +233,13 @@ fn find_code_blocks<'ctx, 'b>(
     trace!("find_code_blocks: ROM: {:?}", bytecode);
 
     let create_bb = || env.context().append_basic_block(func, "block");
+    let spec_id = env.opts().spec_id();
 
     let mut blocks = CodeBlocks::new();
     let mut current_block: &mut CodeBlock = blocks.add(0, create_bb());
     let mut current_block_starting_pc = 0usize;
 
-    for item in instructions::Iterator::new(bytecode) {
+    for item in instructions::Iterator::new(bytecode, spec_id) {
         match item {
             IteratorItem::PushData(pc, data) => {
                 trace!("find_code_blocks: Found push data {:?} at PC {}", data, pc);
@@ -226,6 +257,7 @@ fn find_code_blocks<'ctx, 'b>(
                     Instruction::STOP
                     | Instruction::RETURN
                     | Instruction::REVERT
+                    | Instruction::INVALID
                     | Instruction::JUMP => {
                         trace!("find_code_blocks: Found terminator {}", instr);
                         current_block.rom = &bytecode[current_block_starting_pc..pc + 1];
@@ -255,11 +287,6 @@ fn find_code_blocks<'ctx, 'b>(
                     }
                 }
             }
-            IteratorItem::Invalid(pc) => {
-                trace!("find_code_blocks: Found invalid instruction at PC {}", pc);
-                // TODO: return error
-                panic!("Invalid instruction at PC {}", pc)
-            }
         }
     }
 
@@ -363,7 +390,30 @@ fn build_code_block(
     // and start a relative PC at 0.
     bctx.builder.position_at_end(code_block.basic_block);
 
-    for item in instructions::Iterator::new(code_block.rom) {
+    // Static gas costs are charged a segment at a time, up front. Each instruction that observes
+    // gas ends a segment, so that it sees the exact amount of gas left.
+    let spec_id = bctx.env.opts().spec_id();
+    let mut segment_costs =
+        gas::segment_costs(code_block.rom, code_block.is_jumpdest(), spec_id).into_iter();
+    ops::__use_gas(bctx, segment_costs.next().unwrap_or_default())?;
+
+    let mut items = instructions::Iterator::new(code_block.rom, spec_id)
+        .collect::<Vec<_>>()
+        .into_iter();
+    while let Some(item) = items.next() {
+        // Raising a pushed base to a pushed exponent is folded into pushing the result
+        if let Some((exponent, base)) = constant_exp_operands(&item, items.as_slice()) {
+            trace!("loop: Folding EXP of {:?} and {:?}", base, exponent);
+            ops::exp_constant(bctx, &base, &exponent)?;
+
+            // Skip the push of the base and the EXP
+            items.nth(1);
+            continue;
+        }
+
+        let ends_segment =
+            matches!(&item, IteratorItem::Instr(_, instr) if gas::observes_gas(instr));
+
         match item {
             IteratorItem::PushData(_, data) => {
                 trace!("loop: Data: {:?}", data);
@@ -407,12 +457,37 @@ fn build_code_block(
                     Instruction::KECCAK256 => ops::keccak256(bctx),
 
                     // Call data
+                    Instruction::ADDRESS => ops::address(bctx),
+                    Instruction::BALANCE => ops::balance(bctx),
+                    Instruction::ORIGIN => ops::origin(bctx),
+                    Instruction::CALLER => ops::caller(bctx),
+                    Instruction::CALLVALUE => ops::callvalue(bctx),
+                    Instruction::GASPRICE => ops::gasprice(bctx),
+
+                    Instruction::CALLDATALOAD => ops::calldataload(bctx),
+                    Instruction::CALLDATASIZE => ops::calldatasize(bctx),
+                    Instruction::CALLDATACOPY => ops::calldatacopy(bctx),
                     Instruction::RETURNDATASIZE => ops::returndatasize(bctx),
                     Instruction::RETURNDATACOPY => ops::returndatacopy(bctx),
 
+                    // Code
+                    Instruction::CODESIZE => ops::codesize(bctx),
+                    Instruction::CODECOPY => ops::codecopy(bctx),
+                    Instruction::EXTCODESIZE => ops::extcodesize(bctx),
+                    Instruction::EXTCODECOPY => ops::extcodecopy(bctx),
+                    Instruction::EXTCODEHASH => ops::extcodehash(bctx),
+
                     // Block information
                     Instruction::BLOCKHASH => ops::blockhash(bctx),
-                    // Instruction::NUMBER => ops::blockhash(bctx),
+                    Instruction::COINBASE => ops::coinbase(bctx),
+                    Instruction::TIMESTAMP => ops::timestamp(bctx),
+                    Instruction::NUMBER => ops::number(bctx),
+                    Instruction::DIFFICULTY => ops::prevrandao(bctx),
+                    Instruction::GASLIMIT => ops::gaslimit(bctx),
+                    Instruction::CHAINID => ops::chainid(bctx),
+                    Instruction::BASEFEE => ops::basefee(bctx),
+                    Instruction::BLOBBASEFEE => ops::blobbasefee(bctx),
+                    Instruction::SELFBALANCE => ops::selfbalance(bctx),
 
                     // Runtime
                     Instruction::POP => ops::pop(bctx),
@@ -420,6 +495,19 @@ fn build_code_block(
                     Instruction::MLOAD => ops::mload(bctx),
                     Instruction::MSTORE => ops::mstore(bctx),
                     Instruction::MSTORE8 => ops::mstore8(bctx),
+                    Instruction::MSIZE => ops::msize(bctx),
+                    Instruction::MCOPY => ops::mcopy(bctx),
+
+                    Instruction::LOG0 => ops::log(bctx, 0),
+                    Instruction::LOG1 => ops::log(bctx, 1),
+                    Instruction::LOG2 => ops::log(bctx, 2),
+                    Instruction::LOG3 => ops::log(bctx, 3),
+                    Instruction::LOG4 => ops::log(bctx, 4),
+
+                    Instruction::SLOAD => ops::sload(bctx),
+                    Instruction::SSTORE => ops::sstore(bctx),
+                    Instruction::TLOAD => ops::tload(bctx),
+                    Instruction::TSTORE => ops::tstore(bctx),
 
                     Instruction::JUMP => match jump_block {
                         Some(jump_block) => ops::jump(bctx, jump_block),
@@ -443,8 +531,15 @@ fn build_code_block(
                     },
 
                     Instruction::PC => ops::pc(bctx, code_block.offset + pc),
+                    Instruction::GAS => ops::gas(bctx),
 
                     Instruction::CALL => ops::call(bctx),
+                    Instruction::CALLCODE => ops::callcode(bctx),
+                    Instruction::DELEGATECALL => ops::delegatecall(bctx),
+                    Instruction::STATICCALL => ops::staticcall(bctx),
+
+                    Instruction::CREATE => ops::create(bctx),
+                    Instruction::CREATE2 => ops::create2(bctx),
 
                     Instruction::RETURN => ops::_return(bctx),
                     Instruction::REVERT => ops::revert(bctx),
@@ -487,117 +582,9 @@ fn build_code_block(
                     Instruction::SWAP16 => ops::swap(bctx, 16),
 
                     // Not yet implemented
-                    Instruction::ADDRESS => {
-                        Err(Error::UnimplementedInstruction(Instruction::ADDRESS))
-                    }
-                    Instruction::BALANCE => {
-                        Err(Error::UnimplementedInstruction(Instruction::BALANCE))
-                    }
-                    Instruction::ORIGIN => {
-                        Err(Error::UnimplementedInstruction(Instruction::ORIGIN))
-                    }
-                    Instruction::CALLER => {
-                        Err(Error::UnimplementedInstruction(Instruction::CALLER))
-                    }
-                    Instruction::CALLVALUE => {
-                        Err(Error::UnimplementedInstruction(Instruction::CALLVALUE))
-                    }
-                    Instruction::CALLDATALOAD => {
-                        Err(Error::UnimplementedInstruction(Instruction::CALLDATALOAD))
-                    }
-                    Instruction::CALLDATASIZE => {
-                        Err(Error::UnimplementedInstruction(Instruction::CALLDATASIZE))
-                    }
-                    Instruction::CALLDATACOPY => {
-                        Err(Error::UnimplementedInstruction(Instruction::CALLDATACOPY))
-                    }
-                    Instruction::CODESIZE => {
-                        Err(Error::UnimplementedInstruction(Instruction::CODESIZE))
-                    }
-                    Instruction::CODECOPY => {
-                        Err(Error::UnimplementedInstruction(Instruction::CODECOPY))
-                    }
-                    Instruction::GASPRICE => {
-                        Err(Error::UnimplementedInstruction(Instruction::GASPRICE))
-                    }
-                    Instruction::EXTCODESIZE => {
-                        Err(Error::UnimplementedInstruction(Instruction::EXTCODESIZE))
-                    }
-                    Instruction::EXTCODECOPY => {
-                        Err(Error::UnimplementedInstruction(Instruction::EXTCODECOPY))
-                    }
-                    Instruction::EXTCODEHASH => {
-                        Err(Error::UnimplementedInstruction(Instruction::EXTCODEHASH))
-                    }
-
-                    Instruction::COINBASE => {
-                        Err(Error::UnimplementedInstruction(Instruction::COINBASE))
-                    }
-                    Instruction::TIMESTAMP => {
-                        Err(Error::UnimplementedInstruction(Instruction::TIMESTAMP))
-                    }
-                    Instruction::NUMBER => {
-                        Err(Error::UnimplementedInstruction(Instruction::NUMBER))
-                    }
-                    Instruction::DIFFICULTY => {
-                        Err(Error::UnimplementedInstruction(Instruction::DIFFICULTY))
-                    }
-                    Instruction::GASLIMIT => {
-                        Err(Error::UnimplementedInstruction(Instruction::GASLIMIT))
-                    }
-                    Instruction::CHAINID => {
-                        Err(Error::UnimplementedInstruction(Instruction::CHAINID))
-                    }
-                    Instruction::SELFBALANCE => {
-                        Err(Error::UnimplementedInstruction(Instruction::SELFBALANCE))
-                    }
-                    Instruction::BASEFEE => {
-                        Err(Error::UnimplementedInstruction(Instruction::BASEFEE))
-                    }
                     Instruction::BLOBHASH => {
                         Err(Error::UnimplementedInstruction(Instruction::BLOBHASH))
                     }
-                    Instruction::BLOBBASEFEE => {
-                        Err(Error::UnimplementedInstruction(Instruction::BLOBBASEFEE))
-                    }
-
-                    Instruction::SLOAD => Err(Error::UnimplementedInstruction(Instruction::SLOAD)),
-                    Instruction::SSTORE => {
-                        Err(Error::UnimplementedInstruction(Instruction::SSTORE))
-                    }
-
-                    Instruction::MSIZE => Err(Error::UnimplementedInstruction(Instruction::MSIZE)),
-                    Instruction::GAS => Err(Error::UnimplementedInstruction(Instruction::GAS)),
-
-                    Instruction::TLOAD => Err(Error::UnimplementedInstruction(Instruction::TLOAD)),
-                    Instruction::TSTORE => {
-                        Err(Error::UnimplementedInstruction(Instruction::TSTORE))
-                    }
-
-                    Instruction::MCOPY => Err(Error::UnimplementedInstruction(Instruction::MCOPY)),
-
-                    Instruction::LOG0 => Err(Error::UnimplementedInstruction(Instruction::LOG0)),
-                    Instruction::LOG1 => Err(Error::UnimplementedInstruction(Instruction::LOG1)),
-                    Instruction::LOG2 => Err(Error::UnimplementedInstruction(Instruction::LOG2)),
-                    Instruction::LOG3 => Err(Error::UnimplementedInstruction(Instruction::LOG3)),
-                    Instruction::LOG4 => Err(Error::UnimplementedInstruction(Instruction::LOG4)),
-
-                    Instruction::CREATE => {
-                        Err(Error::UnimplementedInstruction(Instruction::CREATE))
-                    }
-                    Instruction::CREATE2 => {
-                        Err(Error::UnimplementedInstruction(Instruction::CREATE2))
-                    }
-
-                    Instruction::CALLCODE => {
-                        Err(Error::UnimplementedInstruction(Instruction::CALLCODE))
-                    }
-                    Instruction::DELEGATECALL => {
-                        Err(Error::UnimplementedInstruction(Instruction::DELEGATECALL))
-                    }
-                    Instruction::STATICCALL => {
-                        Err(Error::UnimplementedInstruction(Instruction::STATICCALL))
-                    }
 
                     // We should handle all of these before here
                     Instruction::JUMPDEST => {
@@ -638,15 +625,30 @@ fn build_code_block(
                     Instruction::PUSH32 => Err(Error::UnexpectedInstruction(Instruction::PUSH32)),
                 }
             }
-            IteratorItem::Invalid(_) => {
-                trace!("loop: Invalid");
-                return Err(Error::UnknownInstruction(0));
-            }
-        }?
+        }?;
+
+        if ends_segment {
+            ops::__use_gas(bctx, segment_costs.next().unwrap_or_default())?;
+        }
     }
     Ok(())
 }
 
+/// Returns the exponent and base of an EXP that only follows the pushes of both, given the push
+/// of the exponent and the items after it.
+fn constant_exp_operands(
+    item: &IteratorItem,
+    rest: &[IteratorItem],
+) -> Option<([u8; 32], [u8; 32])> {
+    match (item, rest) {
+        (
+            IteratorItem::PushData(_, exponent),
+            [IteratorItem::PushData(_, base), IteratorItem::Instr(_, Instruction::EXP), ..],
+        ) => Some((*exponent, *base)),
+        _ => None,
+    }
+}
+
 fn build_jump_table(
     bctx: &BuildCtx,
     jump_block: BasicBlock,
@@ -7,7 +7,7 @@ use inkwell::{
     values::{FunctionValue, GlobalValue},
 };
 
-use jet_runtime;
+use jet_runtime::{self, spec::SpecId};
 
 const PACK_STRUCTS: bool = true;
 
@@ -17,15 +17,17 @@ pub struct Options {
     vstack: bool,
     emit_llvm: bool,
     assert: bool,
+    spec_id: SpecId,
 }
 
 impl Options {
-    pub fn new(mode: Mode, vstack: bool, emit_llvm: bool, assert: bool) -> Self {
+    pub fn new(mode: Mode, vstack: bool, emit_llvm: bool, assert: bool, spec_id: SpecId) -> Self {
         Self {
             mode,
             vstack,
             emit_llvm,
             assert,
+            spec_id,
         }
     }
 
@@ -44,6 +46,11 @@ impl Options {
     pub fn assert(&self) -> bool {
         self.assert
     }
+
+    /// Returns the fork whose instructions and gas costs contracts are built for.
+    pub fn spec_id(&self) -> SpecId {
+        self.spec_id
+    }
 }
 
 #[derive(clap::ValueEnum, serde::Serialize, Clone, Debug, Default, PartialEq, Eq)]
@@ -72,15 +79,19 @@ pub struct Types<'ctx> {
     pub i64: inkwell::types::IntType<'ctx>,
     pub i160: inkwell::types::IntType<'ctx>,
     pub i256: inkwell::types::IntType<'ctx>,
+    pub i512: inkwell::types::IntType<'ctx>,
     pub ptr: inkwell::types::PointerType<'ctx>,
     pub word_bytes: inkwell::types::ArrayType<'ctx>,
+    pub address: inkwell::types::ArrayType<'ctx>,
 
     // Architecture
     pub stack: inkwell::types::ArrayType<'ctx>,
+    pub hash_history: inkwell::types::ArrayType<'ctx>,
 
     pub mem_len: inkwell::types::IntType<'ctx>,
     pub mem_cap: inkwell::types::IntType<'ctx>,
-    pub mem: inkwell::types::StructType<'ctx>,
+
+    pub gas: inkwell::types::IntType<'ctx>,
 
     // Runtime
     pub stack_ptr: inkwell::types::IntType<'ctx>,
@@ -90,6 +101,7 @@ pub struct Types<'ctx> {
 
     pub exec_ctx: inkwell::types::StructType<'ctx>,
     pub block_info: inkwell::types::StructType<'ctx>,
+    pub tx_info: inkwell::types::StructType<'ctx>,
     pub contract_fn: inkwell::types::FunctionType<'ctx>,
 }
 
@@ -101,15 +113,20 @@ impl<'ctx> Types<'ctx> {
         let i64 = context.i64_type();
         let i160 = context.custom_width_int_type(160);
         let i256 = context.custom_width_int_type(256);
+        let i512 = context.custom_width_int_type(512);
         let ptr = context.ptr_type(AddressSpace::default());
         let word_bytes = i8.array_type(32);
+        // Addresses are byte arrays rather than i160s, which LLVM pads to 24 bytes
+        let address = i8.array_type(jet_runtime::ADDRESS_SIZE_BYTES as u32);
 
         // Architecture
         let stack = i256.array_type(jet_runtime::STACK_SIZE_WORDS);
+        let hash_history = i256.array_type(jet_runtime::BLOCK_HASH_HISTORY_SIZE as u32);
 
         let mem_len = context.i32_type();
         let mem_cap = context.i32_type();
-        let mem = context.struct_type(&[ptr.into(), mem_len.into(), mem_cap.into()], PACK_STRUCTS);
+
+        let gas = context.i64_type();
 
         // Registers
         let stack_ptr = context.i32_type();
@@ -123,30 +140,47 @@ impl<'ctx> Types<'ctx> {
                 jump_ptr.into(),
                 return_offset.into(),
                 return_length.into(),
-                ptr.into(),
                 stack.into(),
-                mem.into(),
+                mem_len.into(),
+                mem_cap.into(),
+                gas.into(),
             ],
             PACK_STRUCTS,
         );
 
+        // Must match the layout of exec::BlockInfo
         let block_info = context.struct_type(
             &[
-                i64.into(),
-                i64.into(),
-                i64.into(),
-                i64.into(),
-                i64.into(),
-                i64.into(),
-                i64.into(),
-                i256.into(),
-                i160.into(),
+                i64.into(),          // number
+                i64.into(),          // difficulty
+                i64.into(),          // gas_limit
+                i64.into(),          // timestamp
+                i64.into(),          // base_fee
+                i64.into(),          // blob_base_fee
+                i64.into(),          // chain_id
+                i256.into(),         // hash
+                hash_history.into(), // hash_history
+                address.into(),      // coinbase
+            ],
+            PACK_STRUCTS,
+        );
+
+        // Must match the layout of exec::TxInfo
+        let tx_info = context.struct_type(
+            &[
+                address.into(), // origin
+                i256.into(),    // gas_price
+                address.into(), // caller
+                address.into(), // address
+                i256.into(),    // value
             ],
             PACK_STRUCTS,
         );
 
-        // contract func sig: func(ctx: &exec_ctx, block_info: &BlockInfo) i8
-        let contract_fn = context.i8_type().fn_type(&[ptr.into(), ptr.into()], false);
+        // contract func sig: func(ctx: &exec_ctx, block_info: &BlockInfo, tx_info: &TxInfo) i8
+        let contract_fn = context
+            .i8_type()
+            .fn_type(&[ptr.into(), ptr.into(), ptr.into()], false);
 
         Self {
             i8,
@@ -154,14 +188,18 @@ impl<'ctx> Types<'ctx> {
             i64,
             i160,
             i256,
+            i512,
             ptr,
             word_bytes,
+            address,
 
             stack,
+            hash_history,
 
             mem_len,
             mem_cap,
-            mem,
+
+            gas,
 
             stack_ptr,
             jump_ptr,
@@ -170,6 +208,7 @@ impl<'ctx> Types<'ctx> {
 
             exec_ctx,
             block_info,
+            tx_info,
             contract_fn,
         }
     }
@@ -188,10 +227,34 @@ pub(crate) struct Symbols<'ctx> {
     mem_store: FunctionValue<'ctx>,
     mem_store_byte: FunctionValue<'ctx>,
     mem_load: FunctionValue<'ctx>,
+    mem_expand: FunctionValue<'ctx>,
+    mem_copy: FunctionValue<'ctx>,
+
+    calldata_load: FunctionValue<'ctx>,
+    calldata_size: FunctionValue<'ctx>,
+    calldata_copy: FunctionValue<'ctx>,
+
+    code_copy: FunctionValue<'ctx>,
+    ext_code_size: FunctionValue<'ctx>,
+    ext_code_copy: FunctionValue<'ctx>,
+    ext_code_hash: FunctionValue<'ctx>,
+
+    storage_load: FunctionValue<'ctx>,
+    storage_store: FunctionValue<'ctx>,
+    transient_storage_load: FunctionValue<'ctx>,
+    transient_storage_store: FunctionValue<'ctx>,
+
+    balance: FunctionValue<'ctx>,
+    self_balance: FunctionValue<'ctx>,
+
+    log: FunctionValue<'ctx>,
 
     contract_call: FunctionValue<'ctx>,
-    contract_call_return_data_copy: FunctionValue<'ctx>,
+    contract_create: FunctionValue<'ctx>,
+    return_data_size: FunctionValue<'ctx>,
+    return_data_copy: FunctionValue<'ctx>,
 
+    exp: FunctionValue<'ctx>,
     keccak256: FunctionValue<'ctx>,
 }
 
@@ -209,11 +272,36 @@ impl<'ctx> Symbols<'ctx> {
         let mem_store = module.get_function(jet_runtime::symbols::FN_MEM_STORE_WORD)?;
         let mem_store_byte = module.get_function(jet_runtime::symbols::FN_MEM_STORE_BYTE)?;
         let mem_load = module.get_function(jet_runtime::symbols::FN_MEM_LOAD)?;
+        let mem_expand = module.get_function(jet_runtime::symbols::FN_MEM_EXPAND)?;
+        let mem_copy = module.get_function(jet_runtime::symbols::FN_MEM_COPY)?;
+
+        let calldata_load = module.get_function(jet_runtime::symbols::FN_CALLDATA_LOAD)?;
+        let calldata_size = module.get_function(jet_runtime::symbols::FN_CALLDATA_SIZE)?;
+        let calldata_copy = module.get_function(jet_runtime::symbols::FN_CALLDATA_COPY)?;
+
+        let code_copy = module.get_function(jet_runtime::symbols::FN_CODE_COPY)?;
+        let ext_code_size = module.get_function(jet_runtime::symbols::FN_EXT_CODE_SIZE)?;
+        let ext_code_copy = module.get_function(jet_runtime::symbols::FN_EXT_CODE_COPY)?;
+        let ext_code_hash = module.get_function(jet_runtime::symbols::FN_EXT_CODE_HASH)?;
+
+        let storage_load = module.get_function(jet_runtime::symbols::FN_STORAGE_LOAD)?;
+        let storage_store = module.get_function(jet_runtime::symbols::FN_STORAGE_STORE)?;
+        let transient_storage_load =
+            module.get_function(jet_runtime::symbols::FN_TRANSIENT_STORAGE_LOAD)?;
+        let transient_storage_store =
+            module.get_function(jet_runtime::symbols::FN_TRANSIENT_STORAGE_STORE)?;
+
+        let balance = module.get_function(jet_runtime::symbols::FN_BALANCE)?;
+        let self_balance = module.get_function(jet_runtime::symbols::FN_SELF_BALANCE)?;
+
+        let log = module.get_function(jet_runtime::symbols::FN_LOG)?;
 
         let contract_call = module.get_function(jet_runtime::symbols::FN_CONTRACT_CALL)?;
-        let contract_call_return_data_copy =
-            module.get_function(jet_runtime::symbols::FN_CONTRACT_CALL_RETURN_DATA_COPY)?;
+        let contract_create = module.get_function(jet_runtime::symbols::FN_CONTRACT_CREATE)?;
+        let return_data_size = module.get_function(jet_runtime::symbols::FN_RETURN_DATA_SIZE)?;
+        let return_data_copy = module.get_function(jet_runtime::symbols::FN_RETURN_DATA_COPY)?;
 
+        let exp = module.get_function(jet_runtime::symbols::FN_EXP)?;
         let keccak256 = module.get_function(jet_runtime::symbols::FN_KECCAK256)?;
 
         Some(Self {
@@ -229,10 +317,34 @@ impl<'ctx> Symbols<'ctx> {
             mem_store,
             mem_store_byte,
             mem_load,
+            mem_expand,
+            mem_copy,
+
+            calldata_load,
+            calldata_size,
+            calldata_copy,
+
+            code_copy,
+            ext_code_size,
+            ext_code_copy,
+            ext_code_hash,
+
+            storage_load,
+            storage_store,
+            transient_storage_load,
+            transient_storage_store,
+
+            balance,
+            self_balance,
+
+            log,
 
             contract_call,
-            contract_call_return_data_copy,
+            contract_create,
+            return_data_size,
+            return_data_copy,
 
+            exp,
             keccak256,
         })
     }
@@ -273,12 +385,88 @@ impl<'ctx> Symbols<'ctx> {
         self.mem_load
     }
 
+    pub(crate) fn mem_expand(&self) -> FunctionValue<'ctx> {
+        self.mem_expand
+    }
+
+    pub(crate) fn mem_copy(&self) -> FunctionValue<'ctx> {
+        self.mem_copy
+    }
+
+    pub(crate) fn calldata_load(&self) -> FunctionValue<'ctx> {
+        self.calldata_load
+    }
+
+    pub(crate) fn calldata_size(&self) -> FunctionValue<'ctx> {
+        self.calldata_size
+    }
+
+    pub(crate) fn calldata_copy(&self) -> FunctionValue<'ctx> {
+        self.calldata_copy
+    }
+
+    pub(crate) fn code_copy(&self) -> FunctionValue<'ctx> {
+        self.code_copy
+    }
+
+    pub(crate) fn ext_code_size(&self) -> FunctionValue<'ctx> {
+        self.ext_code_size
+    }
+
+    pub(crate) fn ext_code_copy(&self) -> FunctionValue<'ctx> {
+        self.ext_code_copy
+    }
+
+    pub(crate) fn ext_code_hash(&self) -> FunctionValue<'ctx> {
+        self.ext_code_hash
+    }
+
+    pub(crate) fn storage_load(&self) -> FunctionValue<'ctx> {
+        self.storage_load
+    }
+
+    pub(crate) fn storage_store(&self) -> FunctionValue<'ctx> {
+        self.storage_store
+    }
+
+    pub(crate) fn transient_storage_load(&self) -> FunctionValue<'ctx> {
+        self.transient_storage_load
+    }
+
+    pub(crate) fn transient_storage_store(&self) -> FunctionValue<'ctx> {
+        self.transient_storage_store
+    }
+
+    pub(crate) fn balance(&self) -> FunctionValue<'ctx> {
+        self.balance
+    }
+
+    pub(crate) fn self_balance(&self) -> FunctionValue<'ctx> {
+        self.self_balance
+    }
+
+    pub(crate) fn log(&self) -> FunctionValue<'ctx> {
+        self.log
+    }
+
     pub(crate) fn contract_call(&self) -> FunctionValue<'ctx> {
         self.contract_call
     }
 
-    pub(crate) fn contract_call_return_data_copy(&self) -> FunctionValue<'ctx> {
-        self.contract_call_return_data_copy
+    pub(crate) fn contract_create(&self) -> FunctionValue<'ctx> {
+        self.contract_create
+    }
+
+    pub(crate) fn return_data_size(&self) -> FunctionValue<'ctx> {
+        self.return_data_size
+    }
+
+    pub(crate) fn return_data_copy(&self) -> FunctionValue<'ctx> {
+        self.return_data_copy
+    }
+
+    pub(crate) fn exp(&self) -> FunctionValue<'ctx> {
+        self.exp
     }
 
     pub(crate) fn keccak256(&self) -> FunctionValue<'ctx> {
@@ -328,7 +516,7 @@ impl<'ctx> Env<'ctx> {
         &self.opts
     }
 
-    pub(crate) fn types(&self) -> &Types<'ctx> {
+    pub fn types(&self) -> &Types<'ctx> {
         &self.types
     }
 
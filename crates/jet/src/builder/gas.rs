@@ -0,0 +1,223 @@
+use jet_runtime::spec::SpecId;
+
+use crate::{
+    instructions,
+    instructions::{Instruction, IteratorItem},
+};
+
+// Cost tiers shared by most instructions
+const ZERO: u64 = 0;
+const JUMPDEST: u64 = 1;
+const BASE: u64 = 2;
+const VERY_LOW: u64 = 3;
+const LOW: u64 = 5;
+const MID: u64 = 8;
+const HIGH: u64 = 10;
+
+// Instructions with costs of their own
+const EXP: u64 = 10;
+const KECCAK256: u64 = 30;
+const BLOCKHASH: u64 = 20;
+const WARM_ACCESS: u64 = 100;
+const TRANSIENT_STORAGE: u64 = 100;
+const LOG: u64 = 375;
+const LOG_TOPIC: u64 = 375;
+const CREATE: u64 = 32000;
+const SELFDESTRUCT: u64 = 5000;
+
+/// Returns the part of the cost of an instruction that doesn't depend on its operands in the given
+/// fork. The rest is charged by the runtime as the instruction runs.
+pub(crate) fn static_cost(instr: &Instruction, spec_id: SpecId) -> u64 {
+    // Accessing state got more expensive over time. Since Berlin, accesses cost the same as
+    // reading a warm slot, and the first access to an account or slot in a transaction costs
+    // extra, which is charged by the runtime.
+    if let Some(cost) = state_access_cost(instr, spec_id) {
+        return cost;
+    }
+
+    match instr {
+        Instruction::STOP | Instruction::RETURN | Instruction::REVERT | Instruction::INVALID => {
+            ZERO
+        }
+
+        // The cost of SSTORE depends entirely on the values of the slot
+        Instruction::SSTORE => ZERO,
+
+        Instruction::JUMPDEST => JUMPDEST,
+
+        Instruction::ADDRESS
+        | Instruction::ORIGIN
+        | Instruction::CALLER
+        | Instruction::CALLVALUE
+        | Instruction::CALLDATASIZE
+        | Instruction::CODESIZE
+        | Instruction::GASPRICE
+        | Instruction::RETURNDATASIZE
+        | Instruction::COINBASE
+        | Instruction::TIMESTAMP
+        | Instruction::NUMBER
+        | Instruction::DIFFICULTY
+        | Instruction::GASLIMIT
+        | Instruction::CHAINID
+        | Instruction::BASEFEE
+        | Instruction::BLOBBASEFEE
+        | Instruction::POP
+        | Instruction::PC
+        | Instruction::MSIZE
+        | Instruction::GAS
+        | Instruction::PUSH0 => BASE,
+
+        Instruction::ADD
+        | Instruction::SUB
+        | Instruction::LT
+        | Instruction::GT
+        | Instruction::SLT
+        | Instruction::SGT
+        | Instruction::EQ
+        | Instruction::ISZERO
+        | Instruction::AND
+        | Instruction::OR
+        | Instruction::XOR
+        | Instruction::NOT
+        | Instruction::BYTE
+        | Instruction::SHL
+        | Instruction::SHR
+        | Instruction::SAR
+        | Instruction::CALLDATALOAD
+        | Instruction::CALLDATACOPY
+        | Instruction::CODECOPY
+        | Instruction::RETURNDATACOPY
+        | Instruction::BLOBHASH
+        | Instruction::MLOAD
+        | Instruction::MSTORE
+        | Instruction::MSTORE8
+        | Instruction::MCOPY => VERY_LOW,
+
+        Instruction::MUL
+        | Instruction::DIV
+        | Instruction::SDIV
+        | Instruction::MOD
+        | Instruction::SMOD
+        | Instruction::SIGNEXTEND
+        | Instruction::SELFBALANCE => LOW,
+
+        Instruction::ADDMOD | Instruction::MULMOD | Instruction::JUMP => MID,
+
+        Instruction::JUMPI => HIGH,
+
+        Instruction::EXP => EXP,
+        Instruction::KECCAK256 => KECCAK256,
+        Instruction::BLOCKHASH => BLOCKHASH,
+
+        Instruction::TLOAD | Instruction::TSTORE => TRANSIENT_STORAGE,
+
+        Instruction::LOG0 => LOG,
+        Instruction::LOG1 => LOG + LOG_TOPIC,
+        Instruction::LOG2 => LOG + 2 * LOG_TOPIC,
+        Instruction::LOG3 => LOG + 3 * LOG_TOPIC,
+        Instruction::LOG4 => LOG + 4 * LOG_TOPIC,
+
+        Instruction::CREATE | Instruction::CREATE2 => CREATE,
+
+        // Every other push, dup and swap
+        _ => VERY_LOW,
+    }
+}
+
+/// Returns the static cost of instructions that access accounts or storage in the given fork, or
+/// None for other instructions.
+fn state_access_cost(instr: &Instruction, spec_id: SpecId) -> Option<u64> {
+    let berlin = spec_id.is_enabled_in(SpecId::Berlin);
+    let istanbul = spec_id.is_enabled_in(SpecId::Istanbul);
+    let tangerine_whistle = spec_id.is_enabled_in(SpecId::TangerineWhistle);
+
+    let cost = match instr {
+        Instruction::BALANCE
+        | Instruction::EXTCODESIZE
+        | Instruction::EXTCODECOPY
+        | Instruction::EXTCODEHASH
+        | Instruction::SLOAD
+        | Instruction::CALL
+        | Instruction::CALLCODE
+        | Instruction::DELEGATECALL
+        | Instruction::STATICCALL
+            if berlin =>
+        {
+            WARM_ACCESS
+        }
+
+        Instruction::BALANCE | Instruction::EXTCODEHASH if istanbul => 700,
+        Instruction::BALANCE if tangerine_whistle => 400,
+        Instruction::BALANCE => 20,
+
+        Instruction::EXTCODESIZE | Instruction::EXTCODECOPY if tangerine_whistle => 700,
+        Instruction::EXTCODESIZE | Instruction::EXTCODECOPY => 20,
+
+        // EXTCODEHASH came after Tangerine Whistle
+        Instruction::EXTCODEHASH => 400,
+
+        Instruction::SLOAD if istanbul => 800,
+        Instruction::SLOAD if tangerine_whistle => 200,
+        Instruction::SLOAD => 50,
+
+        Instruction::CALL
+        | Instruction::CALLCODE
+        | Instruction::DELEGATECALL
+        | Instruction::STATICCALL
+            if tangerine_whistle =>
+        {
+            700
+        }
+        Instruction::CALL
+        | Instruction::CALLCODE
+        | Instruction::DELEGATECALL
+        | Instruction::STATICCALL => 40,
+
+        Instruction::SELFDESTRUCT if tangerine_whistle => SELFDESTRUCT,
+        Instruction::SELFDESTRUCT => ZERO,
+
+        _ => return None,
+    };
+    Some(cost)
+}
+
+/// Returns true if the instruction depends on the exact amount of gas left when it runs. Static
+/// costs can't be charged ahead of these instructions, so they end a segment of a code block.
+/// SSTORE is one of them because it fails when no more than the call stipend is left.
+pub(crate) fn observes_gas(instr: &Instruction) -> bool {
+    matches!(
+        instr,
+        Instruction::GAS
+            | Instruction::SSTORE
+            | Instruction::CALL
+            | Instruction::CALLCODE
+            | Instruction::DELEGATECALL
+            | Instruction::STATICCALL
+            | Instruction::CREATE
+            | Instruction::CREATE2
+    )
+}
+
+/// Splits a code block into segments that each end with an instruction that observes gas, and
+/// returns the static cost of each segment. The cost of a segment is charged before the segment
+/// runs. Code blocks that are jump destinations also pay for the JUMPDEST they start after.
+pub(crate) fn segment_costs(rom: &[u8], is_jumpdest: bool, spec_id: SpecId) -> Vec<u64> {
+    let mut costs = vec![if is_jumpdest { JUMPDEST } else { ZERO }];
+    for item in instructions::Iterator::new(rom, spec_id) {
+        let cost = costs.last_mut().unwrap();
+        match item {
+            IteratorItem::PushData(pc, _) => {
+                if let Ok(instr) = Instruction::try_from(rom[pc]) {
+                    *cost += static_cost(&instr, spec_id);
+                }
+            }
+            IteratorItem::Instr(_, instr) => {
+                *cost += static_cost(&instr, spec_id);
+                if observes_gas(&instr) {
+                    costs.push(ZERO);
+                }
+            }
+        }
+    }
+    costs
+}
@@ -6,7 +6,7 @@ use syntect::{
     util::{as_24_bit_terminal_escaped, LinesWithEndings},
 };
 
-use jet_runtime::exec;
+use jet_runtime::{exec, exec::Word};
 
 use crate::builder::{contract, env::Env, Error};
 
@@ -24,17 +24,37 @@ impl<'ctx> Manager<'ctx> {
     }
 
     pub fn add_contract_function(&self, addr: &str, rom: &[u8]) -> Result<(), Error> {
-        let fn_name = exec::mangle_contract_fn(addr);
+        self.add_function(&exec::mangle_contract_fn(addr), rom)
+    }
+
+    /// Builds code deployed at the given address during an execution. The code hash tells apart
+    /// code deployed at the same address by creations that were undone.
+    pub fn add_deployed_contract_function(
+        &self,
+        addr: &str,
+        code_hash: &Word,
+        rom: &[u8],
+    ) -> Result<(), Error> {
+        self.add_function(&exec::mangle_deployed_contract_fn(addr, code_hash), rom)
+    }
+
+    /// Builds init code for a contract being created at the given address. The id tells apart
+    /// init code that runs for the same address more than once.
+    pub fn add_init_code_function(&self, addr: &str, id: usize, rom: &[u8]) -> Result<(), Error> {
+        self.add_function(&exec::mangle_init_code_fn(addr, id), rom)
+    }
+
+    fn add_function(&self, fn_name: &str, rom: &[u8]) -> Result<(), Error> {
         info!("Building ROM into function {}", fn_name);
 
-        contract::build(&self.build_env, &fn_name, rom)?;
+        contract::build(&self.build_env, fn_name, rom)?;
 
         if self.build_env.opts().emit_llvm() {
             self.print_ir();
         }
 
         if self.build_env.opts().assert() {
-            if !self.verify_contract(addr) {
+            if !self.verify_function(fn_name) {
                 return Err(Error::Verify);
             }
             self.build_env.module().verify()?;
@@ -42,9 +62,8 @@ impl<'ctx> Manager<'ctx> {
         Ok(())
     }
 
-    fn verify_contract(&self, addr: &str) -> bool {
-        let func_name = exec::mangle_contract_fn(addr);
-        let func = self.build_env.module().get_function(&func_name).unwrap();
+    fn verify_function(&self, fn_name: &str) -> bool {
+        let func = self.build_env.module().get_function(fn_name).unwrap();
         func.verify(true)
     }
 
@@ -5,6 +5,7 @@ use crate::instructions::Instruction;
 
 pub mod contract;
 pub mod env;
+pub(crate) mod gas;
 pub mod manager;
 pub(crate) mod ops;
 
@@ -1,11 +1,12 @@
 use inkwell::{
     basic_block::BasicBlock,
     builder::BuilderError,
+    intrinsics::Intrinsic,
     types::IntType,
     values::{AsValueRef, CallSiteValue, IntValue, PointerValue},
 };
 
-use jet_runtime::exec::ReturnCode;
+use jet_runtime::exec::{CallKind, ReturnCode, word_exp};
 
 use crate::{
     builder::{contract::BuildCtx, Error},
@@ -15,6 +16,20 @@ use crate::{
 type StackPop1<'ctx> = PointerValue<'ctx>;
 type StackPop2<'ctx> = (PointerValue<'ctx>, PointerValue<'ctx>);
 type StackPop3<'ctx> = (PointerValue<'ctx>, PointerValue<'ctx>, PointerValue<'ctx>);
+type StackPop4<'ctx> = (
+    PointerValue<'ctx>,
+    PointerValue<'ctx>,
+    PointerValue<'ctx>,
+    PointerValue<'ctx>,
+);
+type StackPop6<'ctx> = (
+    PointerValue<'ctx>,
+    PointerValue<'ctx>,
+    PointerValue<'ctx>,
+    PointerValue<'ctx>,
+    PointerValue<'ctx>,
+    PointerValue<'ctx>,
+);
 type StackPop7<'ctx> = (
     PointerValue<'ctx>,
     PointerValue<'ctx>,
@@ -24,6 +39,12 @@ type StackPop7<'ctx> = (
     PointerValue<'ctx>,
     PointerValue<'ctx>,
 );
+type ModularOperands<'ctx> = (
+    IntValue<'ctx>,
+    IntValue<'ctx>,
+    IntValue<'ctx>,
+    IntValue<'ctx>,
+);
 
 // Stdlib callers
 //
@@ -105,7 +126,7 @@ fn __stack_push_int<'ctx>(bctx: &BuildCtx<'ctx, '_>, value: IntValue<'ctx>) -> R
 
     let bit_width = value.get_type().get_bit_width();
     let value_i256 = match bit_width {
-        1 | 8 | 32 => {
+        1 | 8 | 32 | 64 => {
             bctx.builder
                 .build_int_z_extend(value, bctx.env.types().i256, "int_to_word")?
         }
@@ -188,6 +209,26 @@ fn __stack_pop_3<'ctx>(bctx: &BuildCtx<'ctx, '_>) -> Result<StackPop3<'ctx>, Err
     Ok((a, b, c))
 }
 
+fn __stack_pop_4<'ctx>(bctx: &BuildCtx<'ctx, '_>) -> Result<StackPop4<'ctx>, Error> {
+    let a = __call_stack_pop(bctx)?;
+    let b = __call_stack_pop(bctx)?;
+    let c = __call_stack_pop(bctx)?;
+    let d = __call_stack_pop(bctx)?;
+
+    Ok((a, b, c, d))
+}
+
+fn __stack_pop_6<'ctx>(bctx: &BuildCtx<'ctx, '_>) -> Result<StackPop6<'ctx>, Error> {
+    let a = __call_stack_pop(bctx)?;
+    let b = __call_stack_pop(bctx)?;
+    let c = __call_stack_pop(bctx)?;
+    let d = __call_stack_pop(bctx)?;
+    let e = __call_stack_pop(bctx)?;
+    let f = __call_stack_pop(bctx)?;
+
+    Ok((a, b, c, d, e, f))
+}
+
 fn __stack_pop_7<'ctx>(bctx: &BuildCtx<'ctx, '_>) -> Result<StackPop7<'ctx>, Error> {
     // TODO: Re-enable
     // if bctx.env.opts().vstack() {
@@ -234,6 +275,68 @@ fn __stack_pop_7<'ctx>(bctx: &BuildCtx<'ctx, '_>) -> Result<StackPop7<'ctx>, Err
     Ok((a, b, c, d, e, f, g))
 }
 
+/// Returns from the contract function with the return code of the given builtin call, unless it
+/// is zero. Builtins that can fail report a non-zero ReturnCode instead of panicking.
+fn __check_return_code<'ctx>(
+    bctx: &BuildCtx<'ctx, '_>,
+    ret: CallSiteValue<'ctx>,
+) -> Result<(), Error> {
+    let code = unsafe { IntValue::new(ret.as_value_ref()) };
+    let zero = bctx.env.types().i8.const_zero();
+    let is_ok = bctx
+        .builder
+        .build_int_compare(inkwell::IntPredicate::EQ, code, zero, "is_ok")?;
+
+    let ok_block = bctx.env.context().append_basic_block(bctx.func, "ok");
+    let failure_block = bctx.env.context().append_basic_block(bctx.func, "failure");
+    bctx.builder
+        .build_conditional_branch(is_ok, ok_block, failure_block)?;
+
+    bctx.builder.position_at_end(failure_block);
+    bctx.builder.build_return(Some(&code))?;
+
+    bctx.builder.position_at_end(ok_block);
+    Ok(())
+}
+
+/// Takes the given amount of gas from the gas remaining, or returns OutOfGas if there isn't enough
+/// left. Nothing is built when the amount is zero.
+pub(crate) fn __use_gas(bctx: &BuildCtx<'_, '_>, amount: u64) -> Result<(), Error> {
+    if amount == 0 {
+        return Ok(());
+    }
+
+    let t = bctx.env.types();
+    let gas_remaining = load_i64(bctx, bctx.registers.gas_remaining)?;
+    let amount = t.i64.const_int(amount, false);
+    let has_gas = bctx.builder.build_int_compare(
+        inkwell::IntPredicate::UGE,
+        gas_remaining,
+        amount,
+        "has_gas",
+    )?;
+
+    let ok_block = bctx.env.context().append_basic_block(bctx.func, "has_gas");
+    let out_of_gas_block = bctx
+        .env
+        .context()
+        .append_basic_block(bctx.func, "out_of_gas");
+    bctx.builder
+        .build_conditional_branch(has_gas, ok_block, out_of_gas_block)?;
+
+    bctx.builder.position_at_end(out_of_gas_block);
+    let code = t.i8.const_int(ReturnCode::OutOfGas as u64, false);
+    bctx.builder.build_return(Some(&code))?;
+
+    bctx.builder.position_at_end(ok_block);
+    let gas_remaining = bctx
+        .builder
+        .build_int_sub(gas_remaining, amount, "gas_remaining")?;
+    bctx.builder
+        .build_store(bctx.registers.gas_remaining, gas_remaining)?;
+    Ok(())
+}
+
 pub(crate) fn __invalid_jump_return(bctx: &BuildCtx<'_, '_>) -> Result<(), Error> {
     __build_return(bctx, ReturnCode::InvalidJumpBlock)
 }
@@ -249,6 +352,97 @@ pub(crate) fn __build_return(
     Ok(())
 }
 
+/// Pops the operands of a call of the given kind from the stack and makes the call. Only CALL
+/// and CALLCODE take a value operand.
+fn __build_contract_call(bctx: &BuildCtx<'_, '_>, kind: CallKind) -> Result<(), Error> {
+    let (gas, to, value, in_off, in_len, out_off, out_len) = match kind {
+        CallKind::Call | CallKind::CallCode => __stack_pop_7(bctx)?,
+        CallKind::DelegateCall | CallKind::StaticCall => {
+            let (gas, to, in_off, in_len, out_off, out_len) = __stack_pop_6(bctx)?;
+            let value = bctx.env.types().ptr.const_null();
+            (gas, to, value, in_off, in_len, out_off, out_len)
+        }
+    };
+
+    // Call the contract with the call context; the success flag is pushed by the runtime
+    let contract_call_fn = bctx.env.symbols().contract_call();
+    let jit_engine = bctx.env.symbols().jit_engine();
+    let jit_engine_ptr = jit_engine.as_pointer_value();
+    let kind = bctx.env.types().i8.const_int(kind as u64, false);
+    let ret = bctx.builder.build_call(
+        contract_call_fn,
+        &[
+            bctx.registers.exec_ctx.into(),
+            jit_engine_ptr.into(),
+            bctx.registers.block_info.into(),
+            kind.into(),
+            gas.into(),
+            to.into(),
+            value.into(),
+            in_off.into(),
+            in_len.into(),
+            out_off.into(),
+            out_len.into(),
+        ],
+        "contract_call",
+    )?;
+    __check_return_code(bctx, ret)
+}
+
+/// Creates a contract from init code in memory. The address of the new contract, or zero if the
+/// creation failed, is pushed by the runtime. Only CREATE2 passes a salt.
+fn __build_contract_create<'ctx>(
+    bctx: &BuildCtx<'ctx, '_>,
+    value: PointerValue<'ctx>,
+    offset: PointerValue<'ctx>,
+    size: PointerValue<'ctx>,
+    salt: PointerValue<'ctx>,
+) -> Result<(), Error> {
+    let jit_engine_ptr = bctx.env.symbols().jit_engine().as_pointer_value();
+    let ret = bctx.builder.build_call(
+        bctx.env.symbols().contract_create(),
+        &[
+            bctx.registers.exec_ctx.into(),
+            jit_engine_ptr.into(),
+            bctx.registers.block_info.into(),
+            value.into(),
+            offset.into(),
+            size.into(),
+            salt.into(),
+        ],
+        "contract_create",
+    )?;
+    __check_return_code(bctx, ret)
+}
+
+/// Pops the offset and size of the return data from the stack, records them in the context and
+/// returns with the given code.
+fn __build_return_with_data(
+    bctx: &BuildCtx<'_, '_>,
+    return_value: ReturnCode,
+) -> Result<(), Error> {
+    let (offset, size) = __stack_pop_2(bctx)?;
+
+    // Returned data is read from memory, so it must be expanded to cover the range
+    let ret = bctx.builder.build_call(
+        bctx.env.symbols().mem_expand(),
+        &[bctx.registers.exec_ctx.into(), offset.into(), size.into()],
+        "return_mem_expand",
+    )?;
+    __check_return_code(bctx, ret)?;
+
+    // TODO: Copy instead of load and re-store
+    let offset = load_i32(bctx, offset)?;
+    let size = load_i32(bctx, size)?;
+
+    bctx.builder
+        .build_store(bctx.registers.return_offset, offset)?;
+    bctx.builder
+        .build_store(bctx.registers.return_length, size)?;
+
+    __build_return(bctx, return_value)
+}
+
 fn load_i8<'a>(bctx: &BuildCtx<'a, '_>, ptr: PointerValue<'a>) -> Result<IntValue<'a>, Error> {
     let int = load_int(bctx, ptr, bctx.env.types().i8)?;
     Ok(int)
@@ -264,6 +458,27 @@ fn load_i64<'a>(bctx: &BuildCtx<'a, '_>, ptr: PointerValue<'a>) -> Result<IntVal
     Ok(int)
 }
 
+/// Loads an address, which is kept as a byte array, through an i160 pointer and widens it into a
+/// word.
+fn load_address<'a>(bctx: &BuildCtx<'a, '_>, ptr: PointerValue<'a>) -> Result<IntValue<'a>, Error> {
+    let t = bctx.env.types();
+    let address = load_int(bctx, ptr, t.i160)?;
+
+    // Addresses are stored big-endian, so the loaded value has its bytes reversed
+    let bswap = Intrinsic::find("llvm.bswap")
+        .and_then(|bswap| bswap.get_declaration(bctx.env.module(), &[t.i160.into()]))
+        .ok_or_else(|| Error::invariant_violation("llvm.bswap is not available"))?;
+    let ret = bctx
+        .builder
+        .build_call(bswap, &[address.into()], "address_bswap")?;
+    let address = unsafe { IntValue::new(ret.as_value_ref()) };
+
+    let word = bctx
+        .builder
+        .build_int_z_extend(address, t.i256, "address_to_word")?;
+    Ok(word)
+}
+
 fn load_i256<'a>(bctx: &BuildCtx<'a, '_>, ptr: PointerValue<'a>) -> Result<IntValue<'a>, Error> {
     let int = load_int(bctx, ptr, bctx.env.types().i256)?;
     Ok(int)
@@ -289,17 +504,187 @@ fn call_return_to_ptr(ret: CallSiteValue) -> PointerValue {
 // Block info getter helpers
 //
 
-fn __block_info_hash(bctx: &BuildCtx<'_, '_>) -> Result<(), Error> {
-    let hash_ptr = bctx.builder.build_struct_gep(
+// Field indexes of the block info struct
+const BLOCK_INFO_NUMBER: u32 = 0;
+const BLOCK_INFO_DIFFICULTY: u32 = 1;
+const BLOCK_INFO_GAS_LIMIT: u32 = 2;
+const BLOCK_INFO_TIMESTAMP: u32 = 3;
+const BLOCK_INFO_BASE_FEE: u32 = 4;
+const BLOCK_INFO_BLOB_BASE_FEE: u32 = 5;
+const BLOCK_INFO_CHAIN_ID: u32 = 6;
+const BLOCK_INFO_HASH_HISTORY: u32 = 8;
+const BLOCK_INFO_COINBASE: u32 = 9;
+
+fn __block_info_field_ptr<'ctx>(
+    bctx: &BuildCtx<'ctx, '_>,
+    index: u32,
+    name: &str,
+) -> Result<PointerValue<'ctx>, Error> {
+    let ptr = bctx.builder.build_struct_gep(
         bctx.env.types().block_info,
         bctx.registers.block_info,
-        7,
-        "block_info_hash_ptr",
+        index,
+        name,
     )?;
+    Ok(ptr)
+}
 
-    let hash = load_i256(bctx, hash_ptr)?;
-    __stack_push_int(bctx, hash)?;
-    Ok(())
+/// Pushes one of the u64 fields of the block info onto the stack.
+fn __block_info_push_u64(bctx: &BuildCtx<'_, '_>, index: u32, name: &str) -> Result<(), Error> {
+    let ptr = __block_info_field_ptr(bctx, index, name)?;
+    let value = load_i64(bctx, ptr)?;
+    __stack_push_int(bctx, value)
+}
+
+// Tx info getter helpers
+//
+
+// Field indexes of the tx info struct
+const TX_INFO_ORIGIN: u32 = 0;
+const TX_INFO_GAS_PRICE: u32 = 1;
+const TX_INFO_CALLER: u32 = 2;
+const TX_INFO_ADDRESS: u32 = 3;
+const TX_INFO_VALUE: u32 = 4;
+
+fn __tx_info_field_ptr<'ctx>(
+    bctx: &BuildCtx<'ctx, '_>,
+    index: u32,
+    name: &str,
+) -> Result<PointerValue<'ctx>, Error> {
+    let ptr = bctx.builder.build_struct_gep(
+        bctx.env.types().tx_info,
+        bctx.registers.tx_info,
+        index,
+        name,
+    )?;
+    Ok(ptr)
+}
+
+/// Pushes one of the address fields of the tx info onto the stack.
+fn __tx_info_push_address(bctx: &BuildCtx<'_, '_>, index: u32, name: &str) -> Result<(), Error> {
+    let ptr = __tx_info_field_ptr(bctx, index, name)?;
+    let address = load_address(bctx, ptr)?;
+    __stack_push_int(bctx, address)
+}
+
+/// Pushes one of the word fields of the tx info onto the stack.
+fn __tx_info_push_word(bctx: &BuildCtx<'_, '_>, index: u32, name: &str) -> Result<(), Error> {
+    let ptr = __tx_info_field_ptr(bctx, index, name)?;
+    let word = load_i256(bctx, ptr)?;
+    __stack_push_int(bctx, word)
+}
+
+/// Replaces a zero divisor with one, since LLVM leaves division by zero undefined while the EVM
+/// defines its result as zero. Returns whether the divisor was zero, so that the caller can zero
+/// the result, along with the divisor to use.
+fn __guard_zero_divisor<'ctx>(
+    bctx: &BuildCtx<'ctx, '_>,
+    divisor: IntValue<'ctx>,
+) -> Result<(IntValue<'ctx>, IntValue<'ctx>), Error> {
+    let t = bctx.env.types();
+    let is_zero = bctx.builder.build_int_compare(
+        inkwell::IntPredicate::EQ,
+        divisor,
+        t.i256.const_zero(),
+        "divisor_is_zero",
+    )?;
+    let divisor = bctx
+        .builder
+        .build_select(is_zero, t.i256.const_int(1, false), divisor, "divisor")?
+        .into_int_value();
+    Ok((is_zero, divisor))
+}
+
+/// Like `__guard_zero_divisor`, but also replaces a divisor of minus one, since signed division of
+/// INT_MIN by it overflows, which LLVM leaves undefined as well. Returns whether the divisor was
+/// zero, whether it was minus one, and the divisor to use.
+fn __guard_signed_divisor<'ctx>(
+    bctx: &BuildCtx<'ctx, '_>,
+    divisor: IntValue<'ctx>,
+) -> Result<(IntValue<'ctx>, IntValue<'ctx>, IntValue<'ctx>), Error> {
+    let t = bctx.env.types();
+    let is_minus_one = bctx.builder.build_int_compare(
+        inkwell::IntPredicate::EQ,
+        divisor,
+        t.i256.const_all_ones(),
+        "divisor_is_minus_one",
+    )?;
+    let (is_zero, divisor) = __guard_zero_divisor(bctx, divisor)?;
+    let divisor = bctx
+        .builder
+        .build_select(is_minus_one, t.i256.const_int(1, false), divisor, "divisor")?
+        .into_int_value();
+    Ok((is_zero, is_minus_one, divisor))
+}
+
+/// Pops the operands of ADDMOD and MULMOD, widened to 512 bits so that adding or multiplying them
+/// can't wrap before the modulo is taken. A zero modulus is replaced with one, and whether it was
+/// zero is returned along with the operands.
+fn __pop_modular_operands<'ctx>(bctx: &BuildCtx<'ctx, '_>) -> Result<ModularOperands<'ctx>, Error> {
+    let t = bctx.env.types();
+    let (a, b, n) = __stack_pop_3(bctx)?;
+    let a = load_i256(bctx, a)?;
+    let b = load_i256(bctx, b)?;
+    let n = load_i256(bctx, n)?;
+    let (is_zero, n) = __guard_zero_divisor(bctx, n)?;
+
+    let a = bctx.builder.build_int_z_extend(a, t.i512, "modular_a")?;
+    let b = bctx.builder.build_int_z_extend(b, t.i512, "modular_b")?;
+    let n = bctx.builder.build_int_z_extend(n, t.i512, "modular_n")?;
+    Ok((a, b, n, is_zero))
+}
+
+/// Takes the 512 bit intermediate result of ADDMOD or MULMOD modulo n and pushes it, or zero if
+/// the modulus was zero.
+fn __push_modular_result<'ctx>(
+    bctx: &BuildCtx<'ctx, '_>,
+    result: IntValue<'ctx>,
+    n: IntValue<'ctx>,
+    is_zero: IntValue<'ctx>,
+) -> Result<(), Error> {
+    let result = bctx
+        .builder
+        .build_int_unsigned_rem(result, n, "modular_result")?;
+    let result =
+        bctx.builder
+            .build_int_truncate(result, bctx.env.types().i256, "modular_result")?;
+    let result = __zero_if(bctx, is_zero, result)?;
+    __stack_push_int(bctx, result)
+}
+
+/// Replaces a shift of 256 bits or more with zero, since LLVM leaves shifting a word by its width
+/// or more undefined. Returns whether the shift was that large, so that the caller can replace the
+/// result, along with the shift to use.
+fn __guard_shift<'ctx>(
+    bctx: &BuildCtx<'ctx, '_>,
+    shift: IntValue<'ctx>,
+) -> Result<(IntValue<'ctx>, IntValue<'ctx>), Error> {
+    let t = bctx.env.types();
+    let is_too_large = bctx.builder.build_int_compare(
+        inkwell::IntPredicate::UGE,
+        shift,
+        t.i256.const_int(256, false),
+        "shift_is_too_large",
+    )?;
+    let shift = bctx
+        .builder
+        .build_select(is_too_large, t.i256.const_zero(), shift, "shift")?
+        .into_int_value();
+    Ok((is_too_large, shift))
+}
+
+/// Returns zero if the condition holds, or the value otherwise.
+fn __zero_if<'ctx>(
+    bctx: &BuildCtx<'ctx, '_>,
+    condition: IntValue<'ctx>,
+    value: IntValue<'ctx>,
+) -> Result<IntValue<'ctx>, Error> {
+    let zero = bctx.env.types().i256.const_zero();
+    let value = bctx
+        .builder
+        .build_select(condition, zero, value, "zero_if")?
+        .into_int_value();
+    Ok(value)
 }
 
 // OPCode implementations
@@ -383,7 +768,9 @@ pub(crate) fn div(bctx: &BuildCtx<'_, '_>) -> Result<(), Error> {
     let (a, b) = __stack_pop_2(bctx)?;
     let a = load_i256(bctx, a)?;
     let b = load_i256(bctx, b)?;
+    let (is_zero, b) = __guard_zero_divisor(bctx, b)?;
     let result = bctx.builder.build_int_unsigned_div(a, b, "div_result")?;
+    let result = __zero_if(bctx, is_zero, result)?;
     __stack_push_int(bctx, result)?;
     Ok(())
 }
@@ -392,7 +779,17 @@ pub(crate) fn sdiv(bctx: &BuildCtx<'_, '_>) -> Result<(), Error> {
     let (a, b) = __stack_pop_2(bctx)?;
     let a = load_i256(bctx, a)?;
     let b = load_i256(bctx, b)?;
+    let (is_zero, is_minus_one, b) = __guard_signed_divisor(bctx, b)?;
     let result = bctx.builder.build_int_signed_div(a, b, "sdiv_result")?;
+
+    // Dividing by minus one negates, which wraps INT_MIN back to itself
+    let zero = bctx.env.types().i256.const_zero();
+    let negated = bctx.builder.build_int_sub(zero, a, "sdiv_negated")?;
+    let result = bctx
+        .builder
+        .build_select(is_minus_one, negated, result, "sdiv_result")?
+        .into_int_value();
+    let result = __zero_if(bctx, is_zero, result)?;
     __stack_push_int(bctx, result)?;
     Ok(())
 }
@@ -401,7 +798,9 @@ pub(crate) fn _mod(bctx: &BuildCtx<'_, '_>) -> Result<(), Error> {
     let (a, b) = __stack_pop_2(bctx)?;
     let a = load_i256(bctx, a)?;
     let b = load_i256(bctx, b)?;
+    let (is_zero, b) = __guard_zero_divisor(bctx, b)?;
     let result = bctx.builder.build_int_unsigned_rem(a, b, "mod_result")?;
+    let result = __zero_if(bctx, is_zero, result)?;
     __stack_push_int(bctx, result)?;
     Ok(())
 }
@@ -410,50 +809,85 @@ pub(crate) fn smod(bctx: &BuildCtx<'_, '_>) -> Result<(), Error> {
     let (a, b) = __stack_pop_2(bctx)?;
     let a = load_i256(bctx, a)?;
     let b = load_i256(bctx, b)?;
+    let (is_zero, is_minus_one, b) = __guard_signed_divisor(bctx, b)?;
     let result = bctx.builder.build_int_signed_rem(a, b, "smod_result")?;
+
+    // Every number is a multiple of minus one
+    let no_remainder = bctx
+        .builder
+        .build_or(is_zero, is_minus_one, "smod_no_remainder")?;
+    let result = __zero_if(bctx, no_remainder, result)?;
     __stack_push_int(bctx, result)?;
     Ok(())
 }
 
 pub(crate) fn addmod(bctx: &BuildCtx<'_, '_>) -> Result<(), Error> {
-    let (a, b, c) = __stack_pop_3(bctx)?;
-    let a = load_i256(bctx, a)?;
-    let b = load_i256(bctx, b)?;
-    let c = load_i256(bctx, c)?;
+    let (a, b, n, is_zero) = __pop_modular_operands(bctx)?;
     let result = bctx.builder.build_int_add(a, b, "addmod_add_result")?;
-    let result = bctx
-        .builder
-        .build_int_unsigned_rem(result, c, "addmod_mod_result")?;
-    __stack_push_int(bctx, result)?;
-    Ok(())
+    __push_modular_result(bctx, result, n, is_zero)
 }
 
 pub(crate) fn mulmod(bctx: &BuildCtx<'_, '_>) -> Result<(), Error> {
-    let (a, b, c) = __stack_pop_3(bctx)?;
-    let a = load_i256(bctx, a)?;
-    let b = load_i256(bctx, b)?;
-    let c = load_i256(bctx, c)?;
+    let (a, b, n, is_zero) = __pop_modular_operands(bctx)?;
     let result = bctx.builder.build_int_mul(a, b, "mulmod_mul_result")?;
-    let result = bctx
-        .builder
-        .build_int_unsigned_rem(result, c, "mulmod_mod_result")?;
-    __stack_push_int(bctx, result)?;
-    Ok(())
+    __push_modular_result(bctx, result, n, is_zero)
 }
 
-pub(crate) fn exp(_: &BuildCtx) -> Result<(), Error> {
-    Ok(())
-    // TODO: Create a pow function in the runtime and use it here
-    // let (base, exponent) = stack_pop_2(bctx)?;
-    // let result = bctx.builder.build_int_pow(base, exponent, "exp_result")?;
-    // stack_push_word(bctx, result)?;
+pub(crate) fn exp(bctx: &BuildCtx<'_, '_>) -> Result<(), Error> {
+    let (base, exponent) = __stack_pop_2(bctx)?;
+    let ret = bctx.builder.build_call(
+        bctx.env.symbols().exp(),
+        &[bctx.registers.exec_ctx.into(), base.into(), exponent.into()],
+        "exp",
+    )?;
+    __check_return_code(bctx, ret)
+}
+
+/// Pushes a base pushed by the contract raised to an exponent pushed by it, computed while
+/// building. The exponent bytes are charged for as EXP would charge for them at run time.
+pub(crate) fn exp_constant(
+    bctx: &BuildCtx<'_, '_>,
+    base: &[u8; 32],
+    exponent: &[u8; 32],
+) -> Result<(), Error> {
+    let spec_id = bctx.env.opts().spec_id();
+    __use_gas(bctx, jet_runtime::gas::exp_cost(spec_id, exponent))?;
+    push(bctx, word_exp(base, exponent))
 }
 
-pub(crate) fn signextend(_: &BuildCtx) -> Result<(), Error> {
-    // let (a, b) = stack_pop_2(bctx)?;
-    // let result = bctx.builder.build_int_s_extend(a, b, "signextend_result")?;
-    // stack_push_word(bctx, result)?;
-    // TODO: Implement
+pub(crate) fn signextend(bctx: &BuildCtx<'_, '_>) -> Result<(), Error> {
+    let t = bctx.env.types();
+    let (b, x) = __stack_pop_2(bctx)?;
+    let b = load_i256(bctx, b)?;
+    let x = load_i256(bctx, x)?;
+
+    // Extending from a byte past the last one is treated as extending from the last one, which
+    // leaves the value unchanged
+    let last_byte = t.i256.const_int(31, false);
+    let is_too_large = bctx.builder.build_int_compare(
+        inkwell::IntPredicate::UGT,
+        b,
+        last_byte,
+        "signextend_b_is_too_large",
+    )?;
+    let b = bctx
+        .builder
+        .build_select(is_too_large, last_byte, b, "signextend_b")?
+        .into_int_value();
+
+    // Shift the sign bit of byte b up to the top, then shift back down arithmetically to copy it
+    // into the bits above
+    let unused_bytes = bctx
+        .builder
+        .build_int_sub(last_byte, b, "signextend_bytes")?;
+    let shift =
+        bctx.builder
+            .build_int_mul(unused_bytes, t.i256.const_int(8, false), "signextend_shift")?;
+    let result = bctx.builder.build_left_shift(x, shift, "signextend_shl")?;
+    let result = bctx
+        .builder
+        .build_right_shift(result, shift, true, "signextend_result")?;
+    __stack_push_int(bctx, result)?;
     Ok(())
 }
 
@@ -561,127 +995,353 @@ pub(crate) fn not(bctx: &BuildCtx<'_, '_>) -> Result<(), Error> {
 }
 
 pub(crate) fn byte(bctx: &BuildCtx<'_, '_>) -> Result<(), Error> {
+    let t = bctx.env.types();
     let (idx, word) = __stack_pop_2(bctx)?;
 
-    // Load the index and sub from 31 to reverse endianess
-    let idx = load_i32(bctx, idx)?;
-    let const_31 = bctx.env.types().i32.const_int(31, false);
+    // Indexes past the end of the word read the first byte instead, so the GEP stays in bounds, and
+    // the byte is then replaced with zero
+    let idx = load_i256(bctx, idx)?;
+    let word_size = t.i256.const_int(32, false);
+    let is_out_of_range = bctx.builder.build_int_compare(
+        inkwell::IntPredicate::UGE,
+        idx,
+        word_size,
+        "byte_idx_out_of_range",
+    )?;
+    let idx = bctx
+        .builder
+        .build_select(is_out_of_range, t.i256.const_zero(), idx, "byte_idx")?
+        .into_int_value();
+    let idx = bctx.builder.build_int_truncate(idx, t.i32, "byte_idx")?;
+
+    // Sub the index from 31 to reverse endianess
+    let const_31 = t.i32.const_int(31, false);
     let idx_i32 = bctx.builder.build_int_sub(const_31, idx, "byte_idx")?;
 
     // GEP into the word array and load the byte
-    let typ = bctx.env.types().word_bytes;
+    let typ = t.word_bytes;
     let path = [idx_i32];
     let byte_ptr = unsafe { bctx.builder.build_in_bounds_gep(typ, word, &path, "byte") }?;
 
     // Load byte and then push as an int instead of pushing as pointer directly, otherwise we'll
     // write 31 bytes of garbage instead of padding.
     let byte = load_i8(bctx, byte_ptr)?;
+    let byte = bctx
+        .builder
+        .build_int_z_extend(byte, t.i256, "byte_to_word")?;
+    let byte = __zero_if(bctx, is_out_of_range, byte)?;
     __stack_push_int(bctx, byte)?;
 
     Ok(())
 }
 
 pub(crate) fn shl(bctx: &BuildCtx<'_, '_>) -> Result<(), Error> {
-    let (a, b) = __stack_pop_2(bctx)?;
-    let a = load_i256(bctx, a)?;
-    let b = load_i256(bctx, b)?;
-    let result = bctx.builder.build_left_shift(a, b, "shl_result")?;
+    let (shift, value) = __stack_pop_2(bctx)?;
+    let shift = load_i256(bctx, shift)?;
+    let value = load_i256(bctx, value)?;
+    let (is_too_large, shift) = __guard_shift(bctx, shift)?;
+    let result = bctx.builder.build_left_shift(value, shift, "shl_result")?;
+    let result = __zero_if(bctx, is_too_large, result)?;
     __stack_push_int(bctx, result)?;
     Ok(())
 }
 
 pub(crate) fn shr(bctx: &BuildCtx<'_, '_>) -> Result<(), Error> {
-    let (a, b) = __stack_pop_2(bctx)?;
-    let a = load_i256(bctx, a)?;
-    let b = load_i256(bctx, b)?;
-    let result = bctx.builder.build_right_shift(a, b, false, "shr_result")?;
+    let (shift, value) = __stack_pop_2(bctx)?;
+    let shift = load_i256(bctx, shift)?;
+    let value = load_i256(bctx, value)?;
+    let (is_too_large, shift) = __guard_shift(bctx, shift)?;
+    let result = bctx
+        .builder
+        .build_right_shift(value, shift, false, "shr_result")?;
+    let result = __zero_if(bctx, is_too_large, result)?;
     __stack_push_int(bctx, result)?;
     Ok(())
 }
 
 pub(crate) fn sar(bctx: &BuildCtx<'_, '_>) -> Result<(), Error> {
-    let (a, b) = __stack_pop_2(bctx)?;
-    let a = load_i256(bctx, a)?;
-    let b = load_i256(bctx, b)?;
-    let result = bctx.builder.build_right_shift(a, b, true, "sar_result")?;
+    let (shift, value) = __stack_pop_2(bctx)?;
+    let shift = load_i256(bctx, shift)?;
+    let value = load_i256(bctx, value)?;
+
+    // Shifting by 255 already leaves nothing but the sign, which is all larger shifts leave too
+    let (is_too_large, shift) = __guard_shift(bctx, shift)?;
+    let max_shift = bctx.env.types().i256.const_int(255, false);
+    let shift = bctx
+        .builder
+        .build_select(is_too_large, max_shift, shift, "sar_shift")?
+        .into_int_value();
+    let result = bctx
+        .builder
+        .build_right_shift(value, shift, true, "sar_result")?;
     __stack_push_int(bctx, result)?;
     Ok(())
 }
 
-pub(crate) fn keccak256(ctx: &BuildCtx<'_, '_>) -> Result<(), Error> {
-    let data_ptr = __stack_pop_1(ctx)?;
-
-    // TODO: Check return code
-    ctx.builder.build_call(
-        ctx.env.symbols().keccak256(),
-        &[data_ptr.into()],
+pub(crate) fn keccak256(bctx: &BuildCtx<'_, '_>) -> Result<(), Error> {
+    let (offset, size) = __stack_pop_2(bctx)?;
+    let ret = bctx.builder.build_call(
+        bctx.env.symbols().keccak256(),
+        &[bctx.registers.exec_ctx.into(), offset.into(), size.into()],
         "keccak256",
     )?;
+    __check_return_code(bctx, ret)
+}
 
-    // TODO: We could instead simply increase the stack ptr
-    __call_stack_push_ptr(ctx, data_ptr)?;
-    Ok(())
+pub(crate) fn address(bctx: &BuildCtx<'_, '_>) -> Result<(), Error> {
+    __tx_info_push_address(bctx, TX_INFO_ADDRESS, "address_ptr")
 }
 
-pub(crate) fn returndatasize(bctx: &BuildCtx<'_, '_>) -> Result<(), Error> {
-    // Load sub call ctx
-    let sub_call_ctx_ptr = bctx.builder.build_load(
-        bctx.env.types().ptr,
-        bctx.registers.sub_call,
-        "sub_call_ctx_ptr",
+pub(crate) fn origin(bctx: &BuildCtx<'_, '_>) -> Result<(), Error> {
+    __tx_info_push_address(bctx, TX_INFO_ORIGIN, "origin_ptr")
+}
+
+pub(crate) fn caller(bctx: &BuildCtx<'_, '_>) -> Result<(), Error> {
+    __tx_info_push_address(bctx, TX_INFO_CALLER, "caller_ptr")
+}
+
+pub(crate) fn callvalue(bctx: &BuildCtx<'_, '_>) -> Result<(), Error> {
+    __tx_info_push_word(bctx, TX_INFO_VALUE, "value_ptr")
+}
+
+pub(crate) fn gasprice(bctx: &BuildCtx<'_, '_>) -> Result<(), Error> {
+    __tx_info_push_word(bctx, TX_INFO_GAS_PRICE, "gas_price_ptr")
+}
+
+pub(crate) fn balance(bctx: &BuildCtx<'_, '_>) -> Result<(), Error> {
+    let address = __stack_pop_1(bctx)?;
+    let ret = bctx.builder.build_call(
+        bctx.env.symbols().balance(),
+        &[bctx.registers.exec_ctx.into(), address.into()],
+        "balance",
     )?;
+    __check_return_code(bctx, ret)
+}
 
-    let sub_call_ctx_ptr = unsafe { PointerValue::new(sub_call_ctx_ptr.as_value_ref()) };
+pub(crate) fn calldataload(bctx: &BuildCtx<'_, '_>) -> Result<(), Error> {
+    let offset = __stack_pop_1(bctx)?;
+    let ret = bctx.builder.build_call(
+        bctx.env.symbols().calldata_load(),
+        &[bctx.registers.exec_ctx.into(), offset.into()],
+        "calldataload",
+    )?;
+    __check_return_code(bctx, ret)
+}
 
-    // GetElementPointer to the return length
-    let return_length_ptr = bctx.builder.build_struct_gep(
-        bctx.env.types().exec_ctx,
-        sub_call_ctx_ptr,
-        3,
-        "return_length_ptr",
+pub(crate) fn calldatasize(bctx: &BuildCtx<'_, '_>) -> Result<(), Error> {
+    let ret = bctx.builder.build_call(
+        bctx.env.symbols().calldata_size(),
+        &[bctx.registers.exec_ctx.into()],
+        "calldatasize",
     )?;
+    __check_return_code(bctx, ret)
+}
 
-    let return_length = load_i32(bctx, return_length_ptr)?;
+pub(crate) fn calldatacopy(bctx: &BuildCtx<'_, '_>) -> Result<(), Error> {
+    let (dest_offset, offset, size) = __stack_pop_3(bctx)?;
+    let ret = bctx.builder.build_call(
+        bctx.env.symbols().calldata_copy(),
+        &[
+            bctx.registers.exec_ctx.into(),
+            dest_offset.into(),
+            offset.into(),
+            size.into(),
+        ],
+        "calldatacopy",
+    )?;
+    __check_return_code(bctx, ret)
+}
 
-    __stack_push_int(bctx, return_length)?;
-    Ok(())
+pub(crate) fn returndatasize(bctx: &BuildCtx<'_, '_>) -> Result<(), Error> {
+    let ret = bctx.builder.build_call(
+        bctx.env.symbols().return_data_size(),
+        &[bctx.registers.exec_ctx.into()],
+        "returndatasize",
+    )?;
+    __check_return_code(bctx, ret)
 }
 
 pub(crate) fn returndatacopy(bctx: &BuildCtx<'_, '_>) -> Result<(), Error> {
-    let (dest_off, src_off, len) = __stack_pop_3(bctx)?;
-
-    // Load sub call ctx
-    let sub_call_ctx_ptr = bctx.builder.build_load(
-        bctx.env.types().ptr,
-        bctx.registers.sub_call,
-        "sub_call_ctx_ptr",
+    let (dest_offset, offset, size) = __stack_pop_3(bctx)?;
+    let ret = bctx.builder.build_call(
+        bctx.env.symbols().return_data_copy(),
+        &[
+            bctx.registers.exec_ctx.into(),
+            dest_offset.into(),
+            offset.into(),
+            size.into(),
+        ],
+        "returndatacopy",
     )?;
+    __check_return_code(bctx, ret)
+}
 
-    let sub_call_ctx_ptr = unsafe { PointerValue::new(sub_call_ctx_ptr.as_value_ref()) };
+pub(crate) fn codesize(bctx: &BuildCtx<'_, '_>) -> Result<(), Error> {
+    // The code is known when the contract is built, so its size is a constant
+    let size = bctx.env.types().i64.const_int(bctx.code_len as u64, false);
+    __stack_push_int(bctx, size)?;
+    Ok(())
+}
 
-    let dest_off = load_i32(bctx, dest_off)?;
-    let src_off = load_i32(bctx, src_off)?;
-    let len = load_i32(bctx, len)?;
+pub(crate) fn codecopy(bctx: &BuildCtx<'_, '_>) -> Result<(), Error> {
+    let (dest_offset, offset, size) = __stack_pop_3(bctx)?;
+    let code_len = bctx.env.types().i32.const_int(bctx.code_len as u64, false);
+    let ret = bctx.builder.build_call(
+        bctx.env.symbols().code_copy(),
+        &[
+            bctx.registers.exec_ctx.into(),
+            bctx.code.as_pointer_value().into(),
+            code_len.into(),
+            dest_offset.into(),
+            offset.into(),
+            size.into(),
+        ],
+        "codecopy",
+    )?;
+    __check_return_code(bctx, ret)
+}
 
-    // Call the runtime function to copy the return data
-    bctx.builder.build_call(
-        bctx.env.symbols().contract_call_return_data_copy(),
+pub(crate) fn extcodesize(bctx: &BuildCtx<'_, '_>) -> Result<(), Error> {
+    let address = __stack_pop_1(bctx)?;
+    let ret = bctx.builder.build_call(
+        bctx.env.symbols().ext_code_size(),
+        &[bctx.registers.exec_ctx.into(), address.into()],
+        "extcodesize",
+    )?;
+    __check_return_code(bctx, ret)
+}
+
+pub(crate) fn extcodecopy(bctx: &BuildCtx<'_, '_>) -> Result<(), Error> {
+    let (address, dest_offset, offset, size) = __stack_pop_4(bctx)?;
+    let ret = bctx.builder.build_call(
+        bctx.env.symbols().ext_code_copy(),
         &[
             bctx.registers.exec_ctx.into(),
-            sub_call_ctx_ptr.into(),
-            dest_off.into(),
-            src_off.into(),
-            len.into(),
+            address.into(),
+            dest_offset.into(),
+            offset.into(),
+            size.into(),
         ],
-        "return_data_copy",
+        "extcodecopy",
     )?;
+    __check_return_code(bctx, ret)
+}
 
-    Ok(())
+pub(crate) fn extcodehash(bctx: &BuildCtx<'_, '_>) -> Result<(), Error> {
+    let address = __stack_pop_1(bctx)?;
+    let ret = bctx.builder.build_call(
+        bctx.env.symbols().ext_code_hash(),
+        &[bctx.registers.exec_ctx.into(), address.into()],
+        "extcodehash",
+    )?;
+    __check_return_code(bctx, ret)
 }
 
+/// Pushes the hash of one of the 256 blocks before the current one. Any other block, including
+/// the current one, hashes to zero.
 pub(crate) fn blockhash(bctx: &BuildCtx<'_, '_>) -> Result<(), Error> {
-    __block_info_hash(bctx)?;
-    Ok(())
+    let t = bctx.env.types();
+
+    let block_number = __stack_pop_1(bctx)?;
+    let block_number = load_i256(bctx, block_number)?;
+
+    let number_ptr = __block_info_field_ptr(bctx, BLOCK_INFO_NUMBER, "number_ptr")?;
+    let number = load_i64(bctx, number_ptr)?;
+    let number = bctx
+        .builder
+        .build_int_z_extend(number, t.i256, "number_to_word")?;
+
+    // The requested block must be in the past, but no more than the size of the history back
+    let is_past = bctx.builder.build_int_compare(
+        inkwell::IntPredicate::ULT,
+        block_number,
+        number,
+        "blockhash_is_past",
+    )?;
+    let age = bctx
+        .builder
+        .build_int_sub(number, block_number, "blockhash_age")?;
+    let history_size = t
+        .i256
+        .const_int(jet_runtime::BLOCK_HASH_HISTORY_SIZE as u64, false);
+    let is_recent = bctx.builder.build_int_compare(
+        inkwell::IntPredicate::ULE,
+        age,
+        history_size,
+        "blockhash_is_recent",
+    )?;
+    let in_window = bctx
+        .builder
+        .build_and(is_past, is_recent, "blockhash_in_window")?;
+
+    // The history starts with the parent block. Requests outside the window read the first entry
+    // instead, so the index is always in bounds, and the hash is then replaced with zero.
+    let one = t.i256.const_int(1, false);
+    let index = bctx.builder.build_int_sub(age, one, "blockhash_index")?;
+    let index = bctx
+        .builder
+        .build_select(in_window, index, t.i256.const_zero(), "blockhash_index")?
+        .into_int_value();
+    let index = bctx
+        .builder
+        .build_int_truncate(index, t.i32, "blockhash_index")?;
+
+    let history_ptr = __block_info_field_ptr(bctx, BLOCK_INFO_HASH_HISTORY, "hash_history_ptr")?;
+    let path = [t.i32.const_zero(), index];
+    let hash_ptr = unsafe {
+        bctx.builder
+            .build_in_bounds_gep(t.hash_history, history_ptr, &path, "hash_ptr")
+    }?;
+    let hash = load_i256(bctx, hash_ptr)?;
+    let hash = bctx
+        .builder
+        .build_select(in_window, hash, t.i256.const_zero(), "blockhash")?
+        .into_int_value();
+    __stack_push_int(bctx, hash)
+}
+
+pub(crate) fn coinbase(bctx: &BuildCtx<'_, '_>) -> Result<(), Error> {
+    let coinbase_ptr = __block_info_field_ptr(bctx, BLOCK_INFO_COINBASE, "coinbase_ptr")?;
+    let coinbase = load_address(bctx, coinbase_ptr)?;
+    __stack_push_int(bctx, coinbase)
+}
+
+pub(crate) fn timestamp(bctx: &BuildCtx<'_, '_>) -> Result<(), Error> {
+    __block_info_push_u64(bctx, BLOCK_INFO_TIMESTAMP, "timestamp_ptr")
+}
+
+pub(crate) fn number(bctx: &BuildCtx<'_, '_>) -> Result<(), Error> {
+    __block_info_push_u64(bctx, BLOCK_INFO_NUMBER, "number_ptr")
+}
+
+/// DIFFICULTY was repurposed as PREVRANDAO by the merge; both read the same field.
+pub(crate) fn prevrandao(bctx: &BuildCtx<'_, '_>) -> Result<(), Error> {
+    __block_info_push_u64(bctx, BLOCK_INFO_DIFFICULTY, "prevrandao_ptr")
+}
+
+pub(crate) fn gaslimit(bctx: &BuildCtx<'_, '_>) -> Result<(), Error> {
+    __block_info_push_u64(bctx, BLOCK_INFO_GAS_LIMIT, "gas_limit_ptr")
+}
+
+pub(crate) fn chainid(bctx: &BuildCtx<'_, '_>) -> Result<(), Error> {
+    __block_info_push_u64(bctx, BLOCK_INFO_CHAIN_ID, "chain_id_ptr")
+}
+
+pub(crate) fn basefee(bctx: &BuildCtx<'_, '_>) -> Result<(), Error> {
+    __block_info_push_u64(bctx, BLOCK_INFO_BASE_FEE, "base_fee_ptr")
+}
+
+pub(crate) fn blobbasefee(bctx: &BuildCtx<'_, '_>) -> Result<(), Error> {
+    __block_info_push_u64(bctx, BLOCK_INFO_BLOB_BASE_FEE, "blob_base_fee_ptr")
+}
+
+pub(crate) fn selfbalance(bctx: &BuildCtx<'_, '_>) -> Result<(), Error> {
+    let ret = bctx.builder.build_call(
+        bctx.env.symbols().self_balance(),
+        &[bctx.registers.exec_ctx.into()],
+        "selfbalance",
+    )?;
+    __check_return_code(bctx, ret)
 }
 
 pub(crate) fn pop(bctx: &BuildCtx<'_, '_>) -> Result<(), Error> {
@@ -692,38 +1352,122 @@ pub(crate) fn pop(bctx: &BuildCtx<'_, '_>) -> Result<(), Error> {
 
 pub(crate) fn mload(bctx: &BuildCtx<'_, '_>) -> Result<(), Error> {
     let loc = __stack_pop_1(bctx)?;
-    let mem_ptr = bctx.builder.build_call(
+    let ret = bctx.builder.build_call(
         bctx.env.symbols().mem_load(),
         &[bctx.registers.exec_ctx.into(), loc.into()],
         "mload",
     )?;
-
-    let mem_ptr = unsafe { PointerValue::new(mem_ptr.as_value_ref()) };
-    __stack_push_ptr(bctx, mem_ptr)?;
-
-    Ok(())
+    __check_return_code(bctx, ret)
 }
 
 pub(crate) fn mstore(bctx: &BuildCtx<'_, '_>) -> Result<(), Error> {
     let (loc, val) = __stack_pop_2(bctx)?;
-    bctx.builder.build_call(
+    let ret = bctx.builder.build_call(
         bctx.env.symbols().mem_store(),
         &[bctx.registers.exec_ctx.into(), loc.into(), val.into()],
         "mstore",
     )?;
-    Ok(())
+    __check_return_code(bctx, ret)
 }
 
 pub(crate) fn mstore8(bctx: &BuildCtx<'_, '_>) -> Result<(), Error> {
     let (loc, val) = __stack_pop_2(bctx)?;
-    bctx.builder.build_call(
+    let ret = bctx.builder.build_call(
         bctx.env.symbols().mem_store_byte(),
         &[bctx.registers.exec_ctx.into(), loc.into(), val.into()],
         "mstore8",
     )?;
+    __check_return_code(bctx, ret)
+}
+
+pub(crate) fn msize(bctx: &BuildCtx<'_, '_>) -> Result<(), Error> {
+    let memory_len = load_i32(bctx, bctx.registers.memory_len)?;
+    __stack_push_int(bctx, memory_len)?;
     Ok(())
 }
 
+pub(crate) fn mcopy(bctx: &BuildCtx<'_, '_>) -> Result<(), Error> {
+    let (dest_offset, offset, size) = __stack_pop_3(bctx)?;
+    let ret = bctx.builder.build_call(
+        bctx.env.symbols().mem_copy(),
+        &[
+            bctx.registers.exec_ctx.into(),
+            dest_offset.into(),
+            offset.into(),
+            size.into(),
+        ],
+        "mcopy",
+    )?;
+    __check_return_code(bctx, ret)
+}
+
+pub(crate) fn sload(bctx: &BuildCtx<'_, '_>) -> Result<(), Error> {
+    let key = __stack_pop_1(bctx)?;
+    let ret = bctx.builder.build_call(
+        bctx.env.symbols().storage_load(),
+        &[bctx.registers.exec_ctx.into(), key.into()],
+        "sload",
+    )?;
+    __check_return_code(bctx, ret)
+}
+
+pub(crate) fn sstore(bctx: &BuildCtx<'_, '_>) -> Result<(), Error> {
+    let (key, val) = __stack_pop_2(bctx)?;
+    let ret = bctx.builder.build_call(
+        bctx.env.symbols().storage_store(),
+        &[bctx.registers.exec_ctx.into(), key.into(), val.into()],
+        "sstore",
+    )?;
+    __check_return_code(bctx, ret)
+}
+
+pub(crate) fn tload(bctx: &BuildCtx<'_, '_>) -> Result<(), Error> {
+    let key = __stack_pop_1(bctx)?;
+    let ret = bctx.builder.build_call(
+        bctx.env.symbols().transient_storage_load(),
+        &[bctx.registers.exec_ctx.into(), key.into()],
+        "tload",
+    )?;
+    __check_return_code(bctx, ret)
+}
+
+pub(crate) fn tstore(bctx: &BuildCtx<'_, '_>) -> Result<(), Error> {
+    let (key, val) = __stack_pop_2(bctx)?;
+    let ret = bctx.builder.build_call(
+        bctx.env.symbols().transient_storage_store(),
+        &[bctx.registers.exec_ctx.into(), key.into(), val.into()],
+        "tstore",
+    )?;
+    __check_return_code(bctx, ret)
+}
+
+pub(crate) fn log(bctx: &BuildCtx<'_, '_>, topic_count: u8) -> Result<(), Error> {
+    let (offset, size) = __stack_pop_2(bctx)?;
+
+    // Unused topics are passed as null pointers
+    let mut topics = [bctx.env.types().ptr.const_null(); 4];
+    for topic in topics.iter_mut().take(topic_count as usize) {
+        *topic = __stack_pop_1(bctx)?;
+    }
+
+    let topic_count = bctx.env.types().i8.const_int(topic_count as u64, false);
+    let ret = bctx.builder.build_call(
+        bctx.env.symbols().log(),
+        &[
+            bctx.registers.exec_ctx.into(),
+            offset.into(),
+            size.into(),
+            topic_count.into(),
+            topics[0].into(),
+            topics[1].into(),
+            topics[2].into(),
+            topics[3].into(),
+        ],
+        "log",
+    )?;
+    __check_return_code(bctx, ret)
+}
+
 pub(crate) fn jump(bctx: &BuildCtx<'_, '_>, jump_block: BasicBlock) -> Result<(), Error> {
     let pc = __stack_pop_1(bctx)?;
 
@@ -743,7 +1487,7 @@ pub(crate) fn jumpi(
     let (pc, cond) = __stack_pop_2(bctx)?;
 
     let pc = load_i32(bctx, pc)?;
-    let cond = load_i64(bctx, cond)?;
+    let cond = load_i256(bctx, cond)?;
 
     bctx.builder.build_store(bctx.registers.jump_ptr, pc)?;
     let zero = bctx.env.types().i256.const_zero();
@@ -761,49 +1505,46 @@ pub(crate) fn pc(bctx: &BuildCtx<'_, '_>, pc: usize) -> Result<(), Error> {
     Ok(())
 }
 
-pub(crate) fn call(bctx: &BuildCtx<'_, '_>) -> Result<(), Error> {
-    let (_gas, to, _value, _in_off, _in_len, out_off, out_len) = __stack_pop_7(bctx)?;
-
-    // Call the contract with the call context
-    let contract_call_fn = bctx.env.symbols().contract_call();
-    let jit_engine = bctx.env.symbols().jit_engine();
-    let jit_engine_ptr = jit_engine.as_pointer_value();
-    let make_contract_call = bctx.builder.build_call(
-        contract_call_fn,
-        &[
-            bctx.registers.exec_ctx.into(),
-            jit_engine_ptr.into(),
-            to.into(),
-            out_off.into(),
-            out_len.into(),
-        ],
-        "contract_call",
-    )?;
+pub(crate) fn gas(bctx: &BuildCtx<'_, '_>) -> Result<(), Error> {
+    let gas_remaining = load_i64(bctx, bctx.registers.gas_remaining)?;
+    __stack_push_int(bctx, gas_remaining)?;
+    Ok(())
+}
 
-    let ret = unsafe { IntValue::new(make_contract_call.as_value_ref()) };
+pub(crate) fn call(bctx: &BuildCtx<'_, '_>) -> Result<(), Error> {
+    __build_contract_call(bctx, CallKind::Call)
+}
 
-    __stack_push_int(bctx, ret)?;
+pub(crate) fn callcode(bctx: &BuildCtx<'_, '_>) -> Result<(), Error> {
+    __build_contract_call(bctx, CallKind::CallCode)
+}
 
-    Ok(())
+pub(crate) fn delegatecall(bctx: &BuildCtx<'_, '_>) -> Result<(), Error> {
+    __build_contract_call(bctx, CallKind::DelegateCall)
 }
 
-pub(crate) fn _return(bctx: &BuildCtx<'_, '_>) -> Result<(), Error> {
-    let (offset, size) = __stack_pop_2(bctx)?;
+pub(crate) fn staticcall(bctx: &BuildCtx<'_, '_>) -> Result<(), Error> {
+    __build_contract_call(bctx, CallKind::StaticCall)
+}
 
-    // TODO: Copy instead of load and re-store
-    let offset = load_i32(bctx, offset)?;
-    let size = load_i32(bctx, size)?;
+pub(crate) fn create(bctx: &BuildCtx<'_, '_>) -> Result<(), Error> {
+    let (value, offset, size) = __stack_pop_3(bctx)?;
+    let salt = bctx.env.types().ptr.const_null();
+    __build_contract_create(bctx, value, offset, size, salt)
+}
 
-    bctx.builder
-        .build_store(bctx.registers.return_offset, offset)?;
-    bctx.builder
-        .build_store(bctx.registers.return_length, size)?;
+pub(crate) fn create2(bctx: &BuildCtx<'_, '_>) -> Result<(), Error> {
+    let (value, offset, size) = __stack_pop_3(bctx)?;
+    let salt = __stack_pop_1(bctx)?;
+    __build_contract_create(bctx, value, offset, size, salt)
+}
 
-    __build_return(bctx, ReturnCode::ExplicitReturn)
+pub(crate) fn _return(bctx: &BuildCtx<'_, '_>) -> Result<(), Error> {
+    __build_return_with_data(bctx, ReturnCode::ExplicitReturn)
 }
 
 pub(crate) fn revert(bctx: &BuildCtx<'_, '_>) -> Result<(), Error> {
-    __build_return(bctx, ReturnCode::Revert)
+    __build_return_with_data(bctx, ReturnCode::Revert)
 }
 
 pub(crate) fn invalid(bctx: &BuildCtx<'_, '_>) -> Result<(), Error> {
@@ -1,3 +1,10 @@
+use std::{
+    cell::{Cell, OnceCell, Ref, RefCell},
+    ffi::c_void,
+    panic::{self, AssertUnwindSafe},
+    rc::Rc,
+};
+
 use inkwell::{
     context::Context,
     execution_engine::{ExecutionEngine, FunctionLookupError, JitFunction},
@@ -9,24 +16,54 @@ use thiserror::Error;
 
 use jet_runtime::{
     self, builtins, exec,
-    exec::{BlockInfo, ContractFunc, ContractRun},
+    exec::{
+        AccessListItem, Address, BlockInfo, ContractFunc, ContractRun, Deployer, Execution, TxInfo,
+    },
+    gas,
+    state::{CodeRegistry, hash_code, Storage},
 };
 
 use crate::{
     builder,
-    builder::{env, env::Env, manager::Manager},
+    builder::{
+        env,
+        env::{Env, Symbols},
+        manager::Manager,
+    },
 };
 
 #[derive(Error, Debug)]
-#[error(transparent)]
 pub enum Error {
+    #[error(transparent)]
     Build(#[from] builder::Error),
+    #[error(transparent)]
     FunctionLookup(#[from] FunctionLookupError),
+    #[error(transparent)]
     LLVM(#[from] LLVMString),
+
+    #[error("failed to add module to the JIT")]
+    AddModule,
+
+    #[error("invalid address: {}", .0)]
+    InvalidAddress(String),
+
+    #[error("init code of {} bytes is over the size limit", .0)]
+    InitCodeTooLarge(usize),
 }
 
 pub struct Engine<'ctx> {
     build_manager: Manager<'ctx>,
+    memory_limit: u32,
+
+    // Created on the first run. Contracts built after that go into modules of their own, since
+    // the JIT can't take functions added to a module it has already compiled.
+    jit: OnceCell<Box<ExecutionEngine<'ctx>>>,
+
+    // Number of init code functions built so far, used to give each of them a unique name
+    init_code_count: Cell<usize>,
+
+    // Code of every contract built through build_contract, shared with each execution
+    code_registry: Rc<RefCell<CodeRegistry>>,
 }
 
 impl<'ctx> Engine<'ctx> {
@@ -35,41 +72,276 @@ impl<'ctx> Engine<'ctx> {
         let build_env = Env::new(context, runtime_module, build_opts);
         let build_manager = Manager::new(build_env);
 
-        Ok(Engine { build_manager })
+        Ok(Engine {
+            build_manager,
+            memory_limit: jet_runtime::WORD_SIZE_BYTES * jet_runtime::MEMORY_MAX_SIZE_WORDS,
+            jit: OnceCell::new(),
+            init_code_count: Cell::new(0),
+            code_registry: Rc::new(RefCell::new(CodeRegistry::new())),
+        })
+    }
+
+    /// Sets the maximum size, in bytes, that the memory of a single call can grow to.
+    pub fn set_memory_limit(&mut self, memory_limit: u32) {
+        self.memory_limit = memory_limit;
     }
 
-    pub fn build_contract(&mut self, addr: &str, rom: &[u8]) -> Result<(), Error> {
-        self.build_manager.add_contract_function(addr, rom)?;
+    /// Builds the contract at the given address and records its code, so that other contracts
+    /// can inspect it.
+    pub fn build_contract(&self, addr: &str, rom: &[u8]) -> Result<(), Error> {
+        let address =
+            exec::parse_address(addr).ok_or_else(|| Error::InvalidAddress(addr.to_string()))?;
+        self.build_contract_fn(&address, rom)?;
+        self.code_registry.borrow_mut().insert(address, rom);
         Ok(())
     }
 
-    pub fn run_contract(&self, addr: &str, _block_info: &BlockInfo) -> Result<ContractRun, Error> {
-        // Create a JIT execution engine
-        let jit = self
-            .build_manager
-            .env()
-            .module()
-            .create_jit_execution_engine(OptimizationLevel::None)?;
-        self.link_in_runtime(&jit);
+    /// Returns the code of every contract built so far.
+    pub fn code_registry(&self) -> Ref<'_, CodeRegistry> {
+        self.code_registry.borrow()
+    }
+
+    /// Runs the contract at the address of the given transaction with the given amount of gas.
+    /// Accounts and slots in the access list start out warm.
+    pub fn run_contract(
+        &self,
+        tx_info: &TxInfo,
+        block_info: &BlockInfo,
+        input: &[u8],
+        gas_limit: u64,
+        access_list: &[AccessListItem],
+        storage: Box<dyn Storage>,
+    ) -> Result<ContractRun, Error> {
+        let addr = exec::format_address(tx_info.address());
 
         // Load and run the contract function
-        let contract_exec_fn = match self.get_contract_exec_fn(&jit, addr) {
+        let contract_exec_fn = match self.get_contract_exec_fn(self.jit()?, &addr) {
             Ok(f) => f,
             Err(e) => {
                 return Err(Error::FunctionLookup(e));
             }
         };
+        let contract_exec_fn = unsafe { contract_exec_fn.as_raw() };
+
+        Ok(self.execute(
+            *tx_info,
+            contract_exec_fn,
+            block_info,
+            input,
+            gas_limit,
+            access_list,
+            storage,
+            false,
+        ))
+    }
+
+    /// Deploys a contract the way a contract creation transaction would. The init code runs at the
+    /// address derived from the caller and its nonce, in place of the address of the transaction,
+    /// and the code it returns is built into the contract function for that address. Returns the
+    /// address along with the run of the init code; the contract is only deployed if that run
+    /// succeeded and had enough gas left to pay for the code. Init code over the size limit since
+    /// Shanghai is rejected without running.
+    #[allow(clippy::too_many_arguments)]
+    pub fn deploy(
+        &self,
+        tx_info: &TxInfo,
+        nonce: u64,
+        initcode: &[u8],
+        block_info: &BlockInfo,
+        gas_limit: u64,
+        access_list: &[AccessListItem],
+        storage: Box<dyn Storage>,
+    ) -> Result<(Address, ContractRun), Error> {
+        let spec_id = self.build_manager.env().opts().spec_id();
+        if gas::max_initcode_size(spec_id).is_some_and(|max| initcode.len() > max) {
+            return Err(Error::InitCodeTooLarge(initcode.len()));
+        }
+
+        let address = exec::create_address(tx_info.caller(), nonce);
+        let init_code_fn = self.build_init_code(&address, initcode)?;
+        let tx_info = tx_info.with_address(address);
+        let run = self.execute(
+            tx_info,
+            init_code_fn,
+            block_info,
+            &[],
+            gas_limit,
+            access_list,
+            storage,
+            true,
+        );
+
+        let code = run.ctx().return_data();
+        if run.result().is_success() && !code.is_empty() {
+            self.build_contract(&exec::format_address(&address), code)?;
+        }
+        Ok((address, run))
+    }
 
+    /// Runs a contract function as the top-level frame of a new execution. When the function runs
+    /// init code, the code it returns is paid for before the run is finished.
+    #[allow(clippy::too_many_arguments)]
+    fn execute(
+        &self,
+        tx_info: TxInfo,
+        contract_fn: ContractFunc,
+        block_info: &BlockInfo,
+        input: &[u8],
+        gas_limit: u64,
+        access_list: &[AccessListItem],
+        storage: Box<dyn Storage>,
+        deploys_code: bool,
+    ) -> ContractRun {
         trace!("Running function...");
-        let ctx = exec::Context::new();
-        let result = unsafe { contract_exec_fn.call(&ctx as *const exec::Context) };
+        let spec_id = self.build_manager.env().opts().spec_id();
+        // Every frame of the run points at the execution, so it is boxed to keep it in place as
+        // it is moved into the returned run
+        let mut execution = Box::new(Execution::new(storage, spec_id));
+        execution.set_deployer(self.deployer());
+        execution.set_code_registry(self.code_registry.clone());
+        execution.warm_up(&tx_info, block_info.coinbase(), access_list);
+        let mut ctx = exec::Context::new(tx_info, input.to_vec(), gas_limit, &mut *execution);
+        ctx.set_memory_cap(self.memory_limit);
+        execution.enter_frame(*tx_info.address());
+        let mut result = unsafe { contract_fn(&ctx, block_info, ctx.tx_info()) };
         trace!("Function returned");
 
-        Ok(ContractRun::new(result, ctx))
+        // Code that can't be deployed or paid for fails the run
+        if deploys_code
+            && result.is_success()
+            && !(ctx.check_code_deposit() && ctx.charge_code_deposit())
+        {
+            result = ctx.result();
+        }
+
+        // A failed run leaves no trace in storage, and transient storage never outlives the run
+        execution.exit_frame(result.is_success());
+        execution.clear_transient_storage();
+
+        ContractRun::new(result, ctx, execution)
+    }
+
+    /// Builds the contract function for the given address.
+    fn build_contract_fn(&self, address: &Address, rom: &[u8]) -> Result<(), Error> {
+        // Contract functions are looked up by the canonical form of the address
+        let addr = &exec::format_address(address);
+
+        if self.jit.get().is_none() {
+            self.build_manager.add_contract_function(addr, rom)?;
+            return Ok(());
+        }
+        self.build_in_new_module(|manager| manager.add_contract_function(addr, rom))
+    }
+
+    /// Builds code deployed at the given address during an execution.
+    fn build_deployed_contract_fn(&self, address: &Address, rom: &[u8]) -> Result<(), Error> {
+        let addr = exec::format_address(address);
+        let code_hash = hash_code(rom);
+        self.build_in_new_module(|manager| {
+            manager.add_deployed_contract_function(&addr, &code_hash, rom)
+        })
+    }
+
+    /// Builds init code for a contract being created at the given address and returns the
+    /// function that runs it.
+    fn build_init_code(&self, address: &Address, rom: &[u8]) -> Result<ContractFunc, Error> {
+        let id = self.init_code_count.get();
+        self.init_code_count.set(id + 1);
+
+        let addr = exec::format_address(address);
+        self.build_in_new_module(|manager| manager.add_init_code_function(&addr, id, rom))?;
+
+        let fn_name = exec::mangle_init_code_fn(&addr, id);
+        let init_code_fn = unsafe { self.jit()?.get_function::<ContractFunc>(&fn_name) }?;
+        Ok(unsafe { init_code_fn.as_raw() })
+    }
+
+    /// Returns the JIT, creating it from the main module the first time it is needed.
+    fn jit(&self) -> Result<&ExecutionEngine<'ctx>, Error> {
+        if let Some(jit) = self.jit.get() {
+            return Ok(jit);
+        }
+
+        // The runtime holds on to the JIT's address, so it is boxed before being linked in
+        let jit = Box::new(
+            self.build_manager
+                .env()
+                .module()
+                .create_jit_execution_engine(OptimizationLevel::None)?,
+        );
+        Self::link_in_runtime(&jit, self.build_manager.env().symbols());
+        Ok(self.jit.get_or_init(|| jit))
+    }
+
+    /// Builds code into a fresh copy of the runtime module and adds it to the JIT.
+    fn build_in_new_module(
+        &self,
+        build: impl FnOnce(&Manager<'ctx>) -> Result<(), builder::Error>,
+    ) -> Result<(), Error> {
+        let jit = self.jit()?;
+        let env = self.build_manager.env();
+        let module = jet_runtime::module::load(env.context())?;
+        let manager = Manager::new(Env::new(env.context(), module, env.opts().clone()));
+        build(&manager)?;
+
+        jit.add_module(manager.env().module())
+            .map_err(|_| Error::AddModule)?;
+        Self::link_in_runtime(jit, manager.env().symbols());
+        Ok(())
+    }
+
+    /// Returns the hooks the runtime uses to build contracts created during a run.
+    fn deployer(&self) -> Deployer {
+        unsafe {
+            Deployer::new(
+                self as *const Self as *const c_void,
+                Self::build_init_code_hook,
+                Self::build_contract_hook,
+            )
+        }
+    }
+
+    unsafe fn build_init_code_hook(
+        host: *const c_void,
+        address: &Address,
+        code: &[u8],
+    ) -> Option<ContractFunc> {
+        // The hooks are called from contract code, which a panic can't unwind through, so a
+        // panic fails the creation like any other build error
+        let engine = unsafe { &*(host as *const Self) };
+        match panic::catch_unwind(AssertUnwindSafe(|| engine.build_init_code(address, code))) {
+            Ok(Ok(f)) => Some(f),
+            Ok(Err(e)) => {
+                error!("Error building init code: {}", e);
+                None
+            }
+            Err(_) => {
+                error!("Panicked building init code");
+                None
+            }
+        }
+    }
+
+    unsafe fn build_contract_hook(host: *const c_void, address: &Address, code: &[u8]) -> bool {
+        // The code is kept in the new contract's account rather than the registry, so that it goes
+        // away if the creation is undone. The function is named after the code's hash, so a later
+        // creation at the same address runs its own code.
+        let engine = unsafe { &*(host as *const Self) };
+        let build = || engine.build_deployed_contract_fn(address, code);
+        match panic::catch_unwind(AssertUnwindSafe(build)) {
+            Ok(Ok(())) => true,
+            Ok(Err(e)) => {
+                error!("Error building contract: {}", e);
+                false
+            }
+            Err(_) => {
+                error!("Panicked building contract");
+                false
+            }
+        }
     }
 
-    fn link_in_runtime(&self, ee: &ExecutionEngine) {
-        let sym = self.build_manager.env().symbols();
+    fn link_in_runtime(ee: &ExecutionEngine, sym: &Symbols) {
         let map_fn = |name, ptr| {
             ee.add_global_mapping(&name, ptr);
         };
@@ -85,11 +357,36 @@ impl<'ctx> Engine<'ctx> {
         map_fn(sym.mem_store(), builtins::mem_store as usize);
         map_fn(sym.mem_store_byte(), builtins::mem_store_byte as usize);
         map_fn(sym.mem_load(), builtins::mem_load as usize);
+        map_fn(sym.mem_expand(), builtins::mem_expand as usize);
+        map_fn(sym.mem_copy(), builtins::mem_copy as usize);
+        map_fn(sym.calldata_load(), builtins::calldata_load as usize);
+        map_fn(sym.calldata_size(), builtins::calldata_size as usize);
+        map_fn(sym.calldata_copy(), builtins::calldata_copy as usize);
+        map_fn(sym.code_copy(), builtins::code_copy as usize);
+        map_fn(sym.ext_code_size(), builtins::ext_code_size as usize);
+        map_fn(sym.ext_code_copy(), builtins::ext_code_copy as usize);
+        map_fn(sym.ext_code_hash(), builtins::ext_code_hash as usize);
+        map_fn(sym.storage_load(), builtins::storage_load as usize);
+        map_fn(sym.storage_store(), builtins::storage_store as usize);
+        map_fn(
+            sym.transient_storage_load(),
+            builtins::transient_storage_load as usize,
+        );
+        map_fn(
+            sym.transient_storage_store(),
+            builtins::transient_storage_store as usize,
+        );
+        map_fn(sym.balance(), builtins::balance as usize);
+        map_fn(sym.self_balance(), builtins::self_balance as usize);
+        map_fn(sym.log(), builtins::log as usize);
         map_fn(sym.contract_call(), builtins::jet_contract_call as usize);
         map_fn(
-            sym.contract_call_return_data_copy(),
-            builtins::jet_contract_call_return_data_copy as usize,
+            sym.contract_create(),
+            builtins::jet_contract_create as usize,
         );
+        map_fn(sym.return_data_size(), builtins::return_data_size as usize);
+        map_fn(sym.return_data_copy(), builtins::return_data_copy as usize);
+        map_fn(sym.exp(), builtins::jet_ops_exp as usize);
         map_fn(sym.keccak256(), builtins::jet_ops_keccak256 as usize);
     }
 
@@ -1,3 +1,5 @@
+use jet_runtime::spec::SpecId;
+
 macro_rules! instructions {
     // Match identifier and value pairs
     ($($name:ident = $value:expr),* $(,)?) => {
@@ -196,23 +198,56 @@ impl Instruction {
     pub fn is_push(&self) -> bool {
         (Self::PUSH0..=Self::PUSH32).contains(self)
     }
+
+    /// Returns the fork that introduced the instruction.
+    pub fn introduced_in(&self) -> SpecId {
+        match self {
+            Self::DELEGATECALL => SpecId::Homestead,
+            Self::RETURNDATASIZE | Self::RETURNDATACOPY | Self::STATICCALL | Self::REVERT => {
+                SpecId::Byzantium
+            }
+            Self::SHL | Self::SHR | Self::SAR | Self::EXTCODEHASH | Self::CREATE2 => {
+                SpecId::Constantinople
+            }
+            Self::CHAINID | Self::SELFBALANCE => SpecId::Istanbul,
+            Self::BASEFEE => SpecId::London,
+            Self::PUSH0 => SpecId::Shanghai,
+            Self::TLOAD | Self::TSTORE | Self::MCOPY | Self::BLOBHASH | Self::BLOBBASEFEE => {
+                SpecId::Cancun
+            }
+            _ => SpecId::Frontier,
+        }
+    }
+
+    /// Returns true if the instruction is available in the given fork. Transactions don't carry
+    /// blob hashes yet, so BLOBHASH isn't available in any fork until they do.
+    pub fn is_active_in(&self, spec_id: SpecId) -> bool {
+        *self != Self::BLOBHASH && spec_id.is_enabled_in(self.introduced_in())
+    }
 }
 
+/// Iterates over the instructions and push data of some bytecode, as seen by the given fork.
+/// Instructions the fork doesn't have yet, and bytes that aren't instructions at all, are returned
+/// as INVALID, the way it would run them.
 pub struct Iterator<'a> {
     pc: usize,
     rom: &'a [u8],
+    spec_id: SpecId,
 }
 
 impl<'a> Iterator<'a> {
-    pub fn new(rom: &'a [u8]) -> Self {
-        Self { pc: 0, rom }
+    pub fn new(rom: &'a [u8], spec_id: SpecId) -> Self {
+        Self {
+            pc: 0,
+            rom,
+            spec_id,
+        }
     }
 }
 
 pub enum IteratorItem {
     Instr(usize, Instruction),
     PushData(usize, [u8; 32]),
-    Invalid(usize),
 }
 
 impl<'a> std::iter::Iterator for Iterator<'a> {
@@ -224,38 +259,36 @@ impl<'a> std::iter::Iterator for Iterator<'a> {
             return None;
         }
 
-        // If the next byte isn't a valid instruction, return an error
+        // Bytes that aren't assigned to an instruction run as INVALID, like the instructions the
+        // fork doesn't have yet. Contracts often have them in data at the end of their code.
         let pc = self.pc;
         let instr = match Instruction::try_from(self.rom[pc]) {
-            Ok(instr) => instr,
-            Err(_) => return Some(IteratorItem::Invalid(pc)),
+            Ok(instr) if instr.is_active_in(self.spec_id) => instr,
+            _ => {
+                self.pc += 1;
+                return Some(IteratorItem::Instr(pc, Instruction::INVALID));
+            }
         };
-
         // If the instruction is not a PUSH then increment the PC and return the instruction
         if !instr.is_push() {
             self.pc += 1;
             return Some(IteratorItem::Instr(pc, instr));
         };
 
-        // We have a PUSH instruction, so emit the next N bytes
+        // We have a PUSH instruction, so emit the next N bytes. A PUSH cut off by the end of the
+        // code pushes the bytes that are there followed by zeros.
         let push_len = instr as usize - Instruction::PUSH0 as usize;
-        let push_start = pc + 1;
-        let push_end = push_start + push_len;
+        let push_start = (pc + 1).min(self.rom.len());
+        let push_end = (push_start + push_len).min(self.rom.len());
+        let mut push_bytes = self.rom[push_start..push_end].to_vec();
+        push_bytes.resize(push_len, 0);
 
         // Copy the push data into a 32-byte array, converting from big endian to little endian
         let push_data = {
             let mut data = [0; 32];
-            match push_len {
-                0 => (),
-                _ => {
-                    let reversed = &self.rom[push_start..push_end]
-                        .iter()
-                        .rev()
-                        .cloned()
-                        .collect::<Vec<u8>>();
-                    data[..push_len].copy_from_slice(reversed.as_slice())
-                }
-            };
+            for (dest, src) in data.iter_mut().zip(push_bytes.iter().rev()) {
+                *dest = *src;
+            }
             data
         };
 
@@ -7,8 +7,17 @@ use jet::{
     builder::env::{Mode::Debug, Options},
     engine,
     engine::Engine,
+    instructions::Instruction,
 };
-use jet_runtime::{self, exec, exec::ReturnCode};
+use jet_runtime::{
+    self, exec,
+    exec::{AccessListItem, Address, Log, ReturnCode, Word},
+    spec::SpecId,
+    state::{Account, InMemoryStorage, Storage},
+};
+
+/// Gas given to runs of tests that don't set a gas limit of their own.
+pub(crate) const TEST_GAS_LIMIT: u64 = 10_000_000;
 
 #[derive(Error, Debug)]
 #[error(transparent)]
@@ -49,8 +58,15 @@ macro_rules! assert_eq_named {
     };
 }
 
+#[derive(Default)]
 pub(crate) struct Test {
     pub(crate) roms: Vec<Vec<u8>>,
+    pub(crate) input: Vec<u8>,
+    pub(crate) balances: Vec<(Address, Word)>,
+    pub(crate) storage: Vec<(Address, Word, Word)>,
+    pub(crate) gas_limit: Option<u64>,
+    pub(crate) access_list: Vec<AccessListItem>,
+    pub(crate) spec_id: SpecId,
     pub(crate) expected: TestContractRun,
 }
 
@@ -63,13 +79,45 @@ pub(crate) struct TestContractRun {
     pub(crate) return_length: u32,
     pub(crate) stack: Vec<[u8; 32]>,
     pub(crate) memory: Option<Vec<u8>>,
+    pub(crate) storage: Vec<(Address, Word, Word)>,
+    pub(crate) balances: Vec<(Address, Word)>,
+    pub(crate) nonces: Vec<(Address, u64)>,
+    pub(crate) sub_calls: Option<Vec<TestContractRun>>,
+    pub(crate) logs: Option<Vec<Log>>,
+    pub(crate) gas_used: Option<u64>,
+    pub(crate) gas_refund: Option<u64>,
 }
 
 impl TestContractRun {
-    fn assert_eq(&self, run: &exec::ContractRun) {
+    pub(crate) fn assert_eq(&self, run: &exec::ContractRun) {
         assert_eq!(run.result(), self.result);
+        self.assert_ctx_eq(run.ctx());
+
+        for (address, key, expected_value) in self.storage.iter() {
+            assert_eq_named!("storage", run.storage().load(address, key), *expected_value);
+        }
+
+        for (address, expected_balance) in self.balances.iter() {
+            let account = run.storage().account(address);
+            assert_eq_named!("balance", *account.balance(), *expected_balance);
+        }
+
+        for (address, expected_nonce) in self.nonces.iter() {
+            let account = run.storage().account(address);
+            assert_eq_named!("nonce", account.nonce(), *expected_nonce);
+        }
+
+        if let Some(expected_logs) = &self.logs {
+            assert_eq_named!("logs", run.logs(), expected_logs.as_slice());
+        }
 
-        let ctx = run.ctx();
+        if let Some(expected_gas_refund) = self.gas_refund {
+            assert_eq_named!("gas_refund", run.gas_refund(), expected_gas_refund);
+        }
+    }
+
+    fn assert_ctx_eq(&self, ctx: &exec::Context) {
+        assert_eq_named!("result", ctx.result(), self.result);
         assert_eq_named!("stack_ptr", ctx.stack_ptr(), self.stack_ptr);
         assert_eq_named!("jump_ptr", ctx.jump_ptr(), self.jump_ptr);
         assert_eq_named!("return_off", ctx.return_off(), self.return_offset);
@@ -91,6 +139,10 @@ impl TestContractRun {
         //     assert_eq_named!("stack word", actual_word, *expected_word);
         // }
 
+        if let Some(expected_gas_used) = self.gas_used {
+            assert_eq_named!("gas_used", ctx.gas_used(), expected_gas_used);
+        }
+
         if let Some(expected_memory) = &self.memory {
             assert_eq_named!(
                 "memory",
@@ -98,45 +150,123 @@ impl TestContractRun {
                 expected_memory.as_slice()
             );
         }
+
+        if let Some(expected_sub_calls) = &self.sub_calls {
+            assert_eq_named!(
+                "sub_calls_len",
+                ctx.sub_calls().len(),
+                expected_sub_calls.len()
+            );
+            for (sub_ctx, expected) in ctx.sub_calls().iter().zip(expected_sub_calls.iter()) {
+                expected.assert_ctx_eq(sub_ctx);
+            }
+        }
     }
 }
 
 pub(crate) fn _test_rom_body(t: Test, use_vstack: bool) -> Result<(), Error> {
     let llvm_ctx = Context::create();
-    let opts = Options::new(Debug, use_vstack, false, true);
+    let opts = Options::new(Debug, use_vstack, false, true, t.spec_id);
     let block_info = new_test_block_info();
 
-    let mut engine = Engine::new(&llvm_ctx, opts)?;
+    let engine = Engine::new(&llvm_ctx, opts)?;
 
     assert_ne!(t.roms.len(), 0);
     for (i, rom) in t.roms.iter().enumerate() {
-        let addr = hex::encode(vec![0, i as u8]);
+        let addr = hex::encode(test_address(i as u8));
         let prefixed_addr = format!("0x{}", addr);
         trace!("Building contract at address {}", prefixed_addr);
         engine.build_contract(prefixed_addr.as_str(), rom.as_slice())?;
     }
 
-    let run = engine.run_contract("0x0000", &block_info)?;
+    let tx_info = new_test_tx_info();
+    let mut storage = Box::new(InMemoryStorage::new());
+    for (address, balance) in t.balances.iter() {
+        storage.set_account(address, Account::new(*balance, 0, Vec::new()));
+    }
+    for (address, key, value) in t.storage.iter() {
+        storage.store(address, key, *value);
+    }
+    let gas_limit = t.gas_limit.unwrap_or(TEST_GAS_LIMIT);
+    let run = engine.run_contract(
+        &tx_info,
+        &block_info,
+        &t.input,
+        gas_limit,
+        &t.access_list,
+        storage,
+    )?;
     t.expected.assert_eq(&run);
 
     Ok(())
 }
 
+/// Returns the address the test contract with the given index is built at.
+pub(crate) fn test_address(i: u8) -> Address {
+    let mut address = [0; jet_runtime::ADDRESS_SIZE_BYTES];
+    address[jet_runtime::ADDRESS_SIZE_BYTES - 1] = i;
+    address
+}
+
+/// Returns the address of the account that sends every test transaction.
+pub(crate) fn test_sender() -> Address {
+    test_address(0xee)
+}
+
 pub(crate) fn stack_word(bytes: &[u8]) -> [u8; 32] {
     let mut word = [0; 32];
     word[..bytes.len()].copy_from_slice(bytes);
     word
 }
 
-fn new_test_block_info() -> exec::BlockInfo {
+/// Builds a stack word from big-endian bytes, as they would be written in memory or on chain.
+pub(crate) fn stack_word_be(bytes: &[u8]) -> [u8; 32] {
+    let mut word = [0; 32];
+    for (dest, src) in word.iter_mut().zip(bytes.iter().rev()) {
+        *dest = *src;
+    }
+    word
+}
+
+/// Builds a big-endian word holding the two's complement of the given number.
+pub(crate) fn signed_word_be(value: i64) -> Word {
+    let mut word = if value < 0 { [0xff; 32] } else { [0; 32] };
+    word[24..].copy_from_slice(&value.to_be_bytes());
+    word
+}
+
+/// Builds a rom that runs a binary instruction on the given big-endian operands, leaving its result
+/// on the stack. The first operand ends up on top of the stack, as the instruction expects.
+pub(crate) fn binary_op_rom(instr: Instruction, a: Word, b: Word) -> Vec<u8> {
+    let mut rom = vec![Instruction::PUSH32.opcode()];
+    rom.extend_from_slice(&b);
+    rom.push(Instruction::PUSH32.opcode());
+    rom.extend_from_slice(&a);
+    rom.push(instr.opcode());
+    rom
+}
+
+/// Builds a rom that runs a ternary instruction on the given big-endian operands, leaving its
+/// result on the stack. The first operand ends up on top of the stack.
+pub(crate) fn ternary_op_rom(instr: Instruction, a: Word, b: Word, c: Word) -> Vec<u8> {
+    let mut rom = vec![Instruction::PUSH32.opcode()];
+    rom.extend_from_slice(&c);
+    rom.extend(binary_op_rom(instr, a, b));
+    rom
+}
+
+pub(crate) fn new_test_block_info() -> exec::BlockInfo {
     let hash = [
         0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15, 16, 17, 18, 19, 20, 21, 22, 23, 24,
         25, 26, 27, 28, 29, 30, 31,
     ];
     let hash_history = new_test_block_info_hash_history();
-    let coinbase = [1, 0];
+    let coinbase = [
+        0x01, 0x02, 0x03, 0x04, 0x05, 0x06, 0x07, 0x08, 0x09, 0x0a, 0x0b, 0x0c, 0x0d, 0x0e, 0x0f,
+        0x10, 0x11, 0x12, 0x13, 0x14,
+    ];
     exec::BlockInfo::new(
-        42,
+        300,
         100,
         100,
         1717354173,
@@ -149,6 +279,18 @@ fn new_test_block_info() -> exec::BlockInfo {
     )
 }
 
+/// Returns a transaction from the test sender to the first test contract, with a gas price of 10
+/// and a value of 5.
+pub(crate) fn new_test_tx_info() -> exec::TxInfo {
+    exec::TxInfo::new(
+        test_sender(),
+        stack_word(&[0x0a]),
+        test_sender(),
+        test_address(0),
+        stack_word(&[0x05]),
+    )
+}
+
 fn new_test_block_info_hash_history() -> exec::HashHistory {
     let mut hash_history = [[0; 32]; jet_runtime::BLOCK_HASH_HISTORY_SIZE];
 
@@ -157,6 +299,8 @@ fn new_test_block_info_hash_history() -> exec::HashHistory {
         .enumerate()
         .take(jet_runtime::BLOCK_HASH_HISTORY_SIZE)
         .for_each(|(i, hash)| {
+            // Keep every hash non-zero, so that it can't be mistaken for a missing one
+            hash[0] = 0xff;
             hash[31] = i as u8;
         });
 
@@ -1,5 +1,19 @@
-use jet::instructions::Instruction;
-use jet_runtime::exec::ReturnCode;
+use inkwell::{context::Context, targets::TargetData};
+
+use jet::{
+    builder::env::{Env, Mode::Debug, Options},
+    engine::Engine,
+    instructions::Instruction,
+};
+use jet_runtime::{
+    exec::{
+        AccessListItem, address_to_word, BlockInfo, create2_address, create_address,
+        parse_address, ReturnCode, TxInfo,
+    },
+    gas::{MAX_CODE_SIZE, MAX_INITCODE_SIZE},
+    spec::SpecId,
+    state::{hash_code, InMemoryStorage, Storage},
+};
 use roms::*;
 
 mod roms;
@@ -18,6 +32,7 @@ rom_tests! {
             stack: vec![stack_word(&[0x03])],
             ..Default::default()
         },
+        ..Default::default()
     },
 
     basic_jump: Test {
@@ -35,6 +50,7 @@ rom_tests! {
             stack: vec![stack_word(&[42])],
             ..Default::default()
         },
+        ..Default::default()
     },
 
     basic_mem_ops: Test {
@@ -59,9 +75,22 @@ rom_tests! {
         ]],
         expected: TestContractRun {
             stack_ptr: 2,
-            stack: vec![stack_word(&[0x00, 0x00, 0xFF]), stack_word(&[0xFF, 0x00, 0xFF])],
+            stack: vec![
+                stack_word(&[]),
+                stack_word(&[
+                    0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+                    0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+                    0x00, 0x00, 0x00, 0x00, 0x00, 0xFF,
+                ]),
+            ],
+            memory: Some(vec![
+                0xFF, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+                0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+                0x00, 0x00, 0x00, 0x00, 0x00, 0xFF,
+            ]),
             ..Default::default()
         },
+        ..Default::default()
     },
 
     vstack_accesses_real_stack_after_jump: Test{
@@ -84,6 +113,7 @@ rom_tests! {
             stack: vec![stack_word(&[0x03]), stack_word(&[0x2A])],
             ..Default::default()
         },
+        ..Default::default()
     },
 
     return_sets_offset_and_length: Test{
@@ -100,6 +130,7 @@ rom_tests! {
             return_length: 0x20,
             ..Default::default()
         },
+        ..Default::default()
     },
 
     basic_call_with_return_data: Test {
@@ -117,8 +148,7 @@ rom_tests! {
             Instruction::PUSH2.opcode(), // Address
             0x00,
             0x01,
-            Instruction::PUSH1.opcode(), // Gas
-            0x00,
+            Instruction::GAS.opcode(), // Gas
             Instruction::CALL.opcode(), // Mem: 0x00FF
             Instruction::RETURNDATASIZE.opcode(),
             Instruction::PUSH1.opcode(), // Len
@@ -133,12 +163,12 @@ rom_tests! {
             0xFF,
             Instruction::PUSH1.opcode(),
             0x01,
-            Instruction::MSTORE.opcode(), // Mem: 0x00FF
+            Instruction::MSTORE8.opcode(), // Mem: 0x00FF
             Instruction::PUSH1.opcode(),
             0xFF,
             Instruction::PUSH1.opcode(),
             0x0A,
-            Instruction::MSTORE.opcode(), // Mem: 0x00FF0000000000000000FF
+            Instruction::MSTORE8.opcode(), // Mem: 0x00FF0000000000000000FF
             Instruction::PUSH1.opcode(),
             0x0A,
             Instruction::PUSH1.opcode(),
@@ -148,24 +178,98 @@ rom_tests! {
         expected: TestContractRun {
             stack_ptr: 2,
             stack: vec![
-                stack_word(&[0x00]),
+                stack_word(&[0x01]),
                 stack_word(&[0x0A])
             ],
             memory: Some(vec![0x00, 0xFF, 0x00, 0xFF]),
             ..Default::default()
         },
+        ..Default::default()
     },
 
     keccak256_empty_hash: Test {
         roms: vec![vec![
-            Instruction::PUSH0.opcode(),
+            Instruction::PUSH0.opcode(), // Size
+            Instruction::PUSH0.opcode(), // Offset
+            Instruction::KECCAK256.opcode(),
+            Instruction::MSIZE.opcode(),
+        ]],
+        expected: TestContractRun {
+            stack_ptr: 2,
+            stack: vec![
+                stack_word_be(&[
+                    0xc5, 0xd2, 0x46, 0x01, 0x86, 0xf7, 0x23, 0x3c, 0x92, 0x7e, 0x7d, 0xb2, 0xdc,
+                    0xc7, 0x03, 0xc0, 0xe5, 0x00, 0xb6, 0x53, 0xca, 0x82, 0x27, 0x3b, 0x7b, 0xfa,
+                    0xd8, 0x04, 0x5d, 0x85, 0xa4, 0x70,
+                ]),
+                stack_word(&[]),
+            ],
+            ..Default::default()
+        },
+        ..Default::default()
+    },
+
+    keccak256_empty_range_ignores_offset: Test {
+        roms: vec![vec![
+            Instruction::PUSH0.opcode(), // Size
+            Instruction::PUSH4.opcode(), // Offset
+            0xFF,
+            0xFF,
+            0xFF,
+            0xFF,
             Instruction::KECCAK256.opcode(),
         ]],
         expected: TestContractRun {
             stack_ptr: 1,
-            stack: vec![stack_word(&[0x29, 0x0d, 0xec, 0xd9, 0x54, 0x8b, 0x62, 0xa8, 0xd6, 0x03, 0x45, 0xa9, 0x88, 0x38, 0x6f, 0xc8, 0x4b, 0xa6, 0xbc, 0x95, 0x48, 0x40, 0x08, 0xf6, 0x36, 0x2f, 0x93, 0x16, 0x0e, 0xf3, 0xe5, 0x63])],
+            stack: vec![stack_word_be(&[
+                0xc5, 0xd2, 0x46, 0x01, 0x86, 0xf7, 0x23, 0x3c, 0x92, 0x7e, 0x7d, 0xb2, 0xdc, 0xc7,
+                0x03, 0xc0, 0xe5, 0x00, 0xb6, 0x53, 0xca, 0x82, 0x27, 0x3b, 0x7b, 0xfa, 0xd8, 0x04,
+                0x5d, 0x85, 0xa4, 0x70,
+            ])],
+            ..Default::default()
+        },
+        ..Default::default()
+    },
+
+    keccak256_zero_word: Test {
+        roms: vec![vec![
+            Instruction::PUSH1.opcode(), // Size
+            0x20,
+            Instruction::PUSH1.opcode(), // Offset
+            0x20,
+            Instruction::KECCAK256.opcode(),
+            Instruction::MSIZE.opcode(),
+        ]],
+        expected: TestContractRun {
+            stack_ptr: 2,
+            stack: vec![
+                stack_word_be(&[
+                    0x29, 0x0d, 0xec, 0xd9, 0x54, 0x8b, 0x62, 0xa8, 0xd6, 0x03, 0x45, 0xa9, 0x88,
+                    0x38, 0x6f, 0xc8, 0x4b, 0xa6, 0xbc, 0x95, 0x48, 0x40, 0x08, 0xf6, 0x36, 0x2f,
+                    0x93, 0x16, 0x0e, 0xf3, 0xe5, 0x63,
+                ]),
+                stack_word(&[0x40]),
+            ],
+            ..Default::default()
+        },
+        ..Default::default()
+    },
+
+    keccak256_past_memory_cap_fails: Test {
+        roms: vec![vec![
+            Instruction::PUSH4.opcode(), // Size
+            0x7F,
+            0xFF,
+            0xFF,
+            0xFF,
+            Instruction::PUSH0.opcode(), // Offset
+            Instruction::KECCAK256.opcode(),
+        ]],
+        expected: TestContractRun {
+            result: ReturnCode::InvalidMemoryAccess,
             ..Default::default()
         },
+        ..Default::default()
     },
 
     program_counter: Test {
@@ -185,5 +289,3398 @@ rom_tests! {
             stack: vec![stack_word(&[]), stack_word(&[0x01]), stack_word(&[0x02]), stack_word(&[0x07])],
             ..Default::default()
         },
+        ..Default::default()
+    },
+
+    sstore_then_sload: Test {
+        roms: vec![vec![
+            Instruction::PUSH1.opcode(), // Value
+            0x2A,
+            Instruction::PUSH1.opcode(), // Key
+            0x01,
+            Instruction::SSTORE.opcode(),
+            Instruction::PUSH1.opcode(), // Key
+            0x01,
+            Instruction::SLOAD.opcode(),
+        ]],
+        expected: TestContractRun {
+            stack_ptr: 1,
+            stack: vec![stack_word(&[0x2A])],
+            storage: vec![(test_address(0), stack_word(&[0x01]), stack_word(&[0x2A]))],
+            ..Default::default()
+        },
+        ..Default::default()
+    },
+
+    sstore_in_sub_call_uses_callee_address: Test {
+        roms: vec![vec![
+            Instruction::PUSH1.opcode(), // Output len
+            0x00,
+            Instruction::PUSH1.opcode(), // Output offset
+            0x00,
+            Instruction::PUSH1.opcode(), // Input len
+            0x00,
+            Instruction::PUSH1.opcode(), // Input offset
+            0x00,
+            Instruction::PUSH1.opcode(), // Value
+            0x00,
+            Instruction::PUSH2.opcode(), // Address
+            0x00,
+            0x01,
+            Instruction::GAS.opcode(), // Gas
+            Instruction::CALL.opcode(),
+        ], vec![
+            Instruction::PUSH1.opcode(), // Value
+            0x07,
+            Instruction::PUSH1.opcode(), // Key
+            0x01,
+            Instruction::SSTORE.opcode(),
+        ]],
+        expected: TestContractRun {
+            stack_ptr: 1,
+            stack: vec![stack_word(&[0x01])],
+            storage: vec![
+                (test_address(0), stack_word(&[0x01]), stack_word(&[])),
+                (test_address(1), stack_word(&[0x01]), stack_word(&[0x07])),
+            ],
+            ..Default::default()
+        },
+        ..Default::default()
+    },
+
+    tstore_then_tload: Test {
+        roms: vec![vec![
+            Instruction::PUSH1.opcode(), // Value
+            0x2A,
+            Instruction::PUSH1.opcode(), // Key
+            0x01,
+            Instruction::TSTORE.opcode(),
+            Instruction::PUSH1.opcode(), // Key
+            0x01,
+            Instruction::TLOAD.opcode(),
+            Instruction::PUSH1.opcode(), // Unset key
+            0x02,
+            Instruction::TLOAD.opcode(),
+        ]],
+        expected: TestContractRun {
+            stack_ptr: 2,
+            stack: vec![stack_word(&[0x2A]), stack_word(&[])],
+            ..Default::default()
+        },
+        ..Default::default()
+    },
+
+    revert_discards_storage_writes: Test {
+        roms: vec![vec![
+            Instruction::PUSH1.opcode(), // Value
+            0x07,
+            Instruction::PUSH1.opcode(), // Key
+            0x01,
+            Instruction::SSTORE.opcode(),
+            Instruction::PUSH0.opcode(),
+            Instruction::PUSH0.opcode(),
+            Instruction::REVERT.opcode(),
+        ]],
+        expected: TestContractRun {
+            result: ReturnCode::Revert,
+            storage: vec![(test_address(0), stack_word(&[0x01]), stack_word(&[]))],
+            ..Default::default()
+        },
+        ..Default::default()
+    },
+
+    reverted_sub_call_discards_storage_writes: Test {
+        roms: vec![vec![
+            Instruction::PUSH1.opcode(), // Value
+            0x07,
+            Instruction::PUSH1.opcode(), // Key
+            0x01,
+            Instruction::SSTORE.opcode(),
+            Instruction::PUSH1.opcode(), // Output len
+            0x00,
+            Instruction::PUSH1.opcode(), // Output offset
+            0x00,
+            Instruction::PUSH1.opcode(), // Input len
+            0x00,
+            Instruction::PUSH1.opcode(), // Input offset
+            0x00,
+            Instruction::PUSH1.opcode(), // Value
+            0x00,
+            Instruction::PUSH2.opcode(), // Address
+            0x00,
+            0x01,
+            Instruction::GAS.opcode(), // Gas
+            Instruction::CALL.opcode(),
+        ], vec![
+            Instruction::PUSH1.opcode(), // Value
+            0x08,
+            Instruction::PUSH1.opcode(), // Key
+            0x01,
+            Instruction::SSTORE.opcode(),
+            Instruction::PUSH1.opcode(), // Value
+            0x09,
+            Instruction::PUSH1.opcode(), // Key
+            0x01,
+            Instruction::TSTORE.opcode(),
+            Instruction::PUSH0.opcode(),
+            Instruction::PUSH0.opcode(),
+            Instruction::REVERT.opcode(),
+        ]],
+        expected: TestContractRun {
+            stack_ptr: 1,
+            stack: vec![stack_word(&[0x00])],
+            storage: vec![
+                (test_address(0), stack_word(&[0x01]), stack_word(&[0x07])),
+                (test_address(1), stack_word(&[0x01]), stack_word(&[])),
+            ],
+            ..Default::default()
+        },
+        ..Default::default()
+    },
+
+    msize_tracks_memory_expansion: Test {
+        roms: vec![vec![
+            Instruction::MSIZE.opcode(),
+            Instruction::PUSH1.opcode(), // Value
+            0x01,
+            Instruction::PUSH1.opcode(), // Offset
+            0x20,
+            Instruction::MSTORE8.opcode(),
+            Instruction::MSIZE.opcode(),
+            Instruction::PUSH1.opcode(), // Offset
+            0x21,
+            Instruction::MLOAD.opcode(),
+            Instruction::POP.opcode(),
+            Instruction::MSIZE.opcode(),
+        ]],
+        expected: TestContractRun {
+            stack_ptr: 3,
+            stack: vec![stack_word(&[]), stack_word(&[0x40]), stack_word(&[0x60])],
+            ..Default::default()
+        },
+        ..Default::default()
+    },
+
+    memory_grows_past_initial_size: Test {
+        roms: vec![vec![
+            Instruction::PUSH1.opcode(), // Value
+            0x2A,
+            Instruction::PUSH3.opcode(), // Offset
+            0x01,
+            0x00,
+            0x00,
+            Instruction::MSTORE.opcode(),
+            Instruction::PUSH3.opcode(), // Offset
+            0x01,
+            0x00,
+            0x00,
+            Instruction::MLOAD.opcode(),
+            Instruction::MSIZE.opcode(),
+        ]],
+        expected: TestContractRun {
+            stack_ptr: 2,
+            stack: vec![stack_word(&[0x2A]), stack_word(&[0x20, 0x00, 0x01])],
+            ..Default::default()
+        },
+        ..Default::default()
+    },
+
+    mstore_past_memory_cap_fails: Test {
+        roms: vec![vec![
+            Instruction::PUSH1.opcode(), // Value
+            0x2A,
+            Instruction::PUSH4.opcode(), // Offset
+            0x7F,
+            0xFF,
+            0xFF,
+            0xFF,
+            Instruction::MSTORE.opcode(),
+            Instruction::PUSH1.opcode(),
+            0x01,
+        ]],
+        expected: TestContractRun {
+            result: ReturnCode::InvalidMemoryAccess,
+            ..Default::default()
+        },
+        ..Default::default()
+    },
+
+    mload_with_unaddressable_offset_fails: Test {
+        roms: vec![vec![
+            Instruction::PUSH5.opcode(), // Offset
+            0x01,
+            0x00,
+            0x00,
+            0x00,
+            0x00,
+            Instruction::MLOAD.opcode(),
+        ]],
+        expected: TestContractRun {
+            result: ReturnCode::InvalidMemoryAccess,
+            ..Default::default()
+        },
+        ..Default::default()
+    },
+
+    calldataload_pads_past_end_of_input: Test {
+        roms: vec![vec![
+            Instruction::CALLDATASIZE.opcode(),
+            Instruction::PUSH0.opcode(), // Offset
+            Instruction::CALLDATALOAD.opcode(),
+            Instruction::PUSH1.opcode(), // Offset
+            0x02,
+            Instruction::CALLDATALOAD.opcode(),
+            Instruction::PUSH1.opcode(), // Offset
+            0xFF,
+            Instruction::CALLDATALOAD.opcode(),
+        ]],
+        input: vec![0xAA, 0xBB, 0xCC, 0xDD],
+        expected: TestContractRun {
+            stack_ptr: 4,
+            stack: vec![
+                stack_word(&[0x04]),
+                stack_word_be(&[
+                    0xAA, 0xBB, 0xCC, 0xDD, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+                    0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+                    0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+                ]),
+                stack_word_be(&[
+                    0xCC, 0xDD, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+                    0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+                    0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+                ]),
+                stack_word(&[]),
+            ],
+            ..Default::default()
+        },
+    },
+
+    calldatacopy_pads_past_end_of_input: Test {
+        roms: vec![vec![
+            Instruction::PUSH1.opcode(), // Value
+            0xFF,
+            Instruction::PUSH1.opcode(), // Offset
+            0x06,
+            Instruction::MSTORE8.opcode(),
+            Instruction::PUSH1.opcode(), // Size
+            0x05,
+            Instruction::PUSH1.opcode(), // Offset
+            0x01,
+            Instruction::PUSH1.opcode(), // Dest offset
+            0x02,
+            Instruction::CALLDATACOPY.opcode(),
+            Instruction::MSIZE.opcode(),
+        ]],
+        input: vec![0x01, 0x02, 0x03],
+        expected: TestContractRun {
+            stack_ptr: 1,
+            stack: vec![stack_word(&[0x20])],
+            memory: Some(vec![0x00, 0x00, 0x02, 0x03, 0x00, 0x00, 0x00, 0x00]),
+            ..Default::default()
+        },
+    },
+
+    call_passes_input_to_callee: Test {
+        roms: vec![vec![
+            Instruction::PUSH1.opcode(), // Value
+            0x2A,
+            Instruction::PUSH0.opcode(), // Offset
+            Instruction::MSTORE8.opcode(),
+            Instruction::PUSH1.opcode(), // Output len
+            0x40,
+            Instruction::PUSH1.opcode(), // Output offset
+            0x20,
+            Instruction::PUSH1.opcode(), // Input len
+            0x01,
+            Instruction::PUSH1.opcode(), // Input offset
+            0x00,
+            Instruction::PUSH1.opcode(), // Value
+            0x00,
+            Instruction::PUSH2.opcode(), // Address
+            0x00,
+            0x01,
+            Instruction::GAS.opcode(), // Gas
+            Instruction::CALL.opcode(),
+        ], vec![
+            Instruction::PUSH0.opcode(), // Offset
+            Instruction::CALLDATALOAD.opcode(),
+            Instruction::PUSH0.opcode(), // Offset
+            Instruction::MSTORE.opcode(),
+            Instruction::CALLDATASIZE.opcode(),
+            Instruction::PUSH1.opcode(), // Offset
+            0x20,
+            Instruction::MSTORE.opcode(),
+            Instruction::PUSH1.opcode(), // Size
+            0x40,
+            Instruction::PUSH0.opcode(), // Offset
+            Instruction::RETURN.opcode(),
+        ]],
+        expected: TestContractRun {
+            stack_ptr: 1,
+            stack: vec![stack_word(&[0x01])],
+            memory: Some([vec![0x2A], vec![0x00; 31], vec![0x2A], vec![0x00; 62], vec![0x01]].concat()),
+            ..Default::default()
+        },
+        ..Default::default()
     },
+
+    revert_returns_data_to_caller: Test {
+        roms: vec![vec![
+            Instruction::PUSH1.opcode(), // Output len
+            0x20,
+            Instruction::PUSH1.opcode(), // Output offset
+            0x00,
+            Instruction::PUSH1.opcode(), // Input len
+            0x00,
+            Instruction::PUSH1.opcode(), // Input offset
+            0x00,
+            Instruction::PUSH1.opcode(), // Value
+            0x00,
+            Instruction::PUSH2.opcode(), // Address
+            0x00,
+            0x01,
+            Instruction::GAS.opcode(), // Gas
+            Instruction::CALL.opcode(),
+            Instruction::RETURNDATASIZE.opcode(),
+        ], vec![
+            Instruction::PUSH1.opcode(), // Value
+            0x2A,
+            Instruction::PUSH0.opcode(), // Offset
+            Instruction::MSTORE.opcode(),
+            Instruction::PUSH1.opcode(), // Size
+            0x20,
+            Instruction::PUSH0.opcode(), // Offset
+            Instruction::REVERT.opcode(),
+        ]],
+        expected: TestContractRun {
+            stack_ptr: 2,
+            stack: vec![stack_word(&[0x00]), stack_word(&[0x20])],
+            memory: Some([vec![0x00; 31], vec![0x2A]].concat()),
+            ..Default::default()
+        },
+        ..Default::default()
+    },
+
+    revert_sets_return_data: Test {
+        roms: vec![vec![
+            Instruction::PUSH1.opcode(), // Size
+            0x20,
+            Instruction::PUSH1.opcode(), // Offset
+            0x40,
+            Instruction::REVERT.opcode(),
+        ]],
+        expected: TestContractRun {
+            result: ReturnCode::Revert,
+            return_offset: 0x40,
+            return_length: 0x20,
+            ..Default::default()
+        },
+        ..Default::default()
+    },
+
+    call_to_account_without_code_succeeds: Test {
+        roms: vec![vec![
+            Instruction::PUSH1.opcode(), // Output len
+            0x00,
+            Instruction::PUSH1.opcode(), // Output offset
+            0x00,
+            Instruction::PUSH1.opcode(), // Input len
+            0x00,
+            Instruction::PUSH1.opcode(), // Input offset
+            0x00,
+            Instruction::PUSH1.opcode(), // Value
+            0x00,
+            Instruction::PUSH2.opcode(), // Address
+            0x00,
+            0x05,
+            Instruction::GAS.opcode(), // Gas
+            Instruction::CALL.opcode(),
+            Instruction::RETURNDATASIZE.opcode(),
+        ]],
+        expected: TestContractRun {
+            stack_ptr: 2,
+            stack: vec![stack_word(&[0x01]), stack_word(&[])],
+            ..Default::default()
+        },
+        ..Default::default()
+    },
+
+    returndatasize_without_call_is_zero: Test {
+        roms: vec![vec![
+            Instruction::RETURNDATASIZE.opcode(),
+        ]],
+        expected: TestContractRun {
+            stack_ptr: 1,
+            stack: vec![stack_word(&[])],
+            ..Default::default()
+        },
+        ..Default::default()
+    },
+
+    returndatacopy_past_end_fails: Test {
+        roms: vec![vec![
+            Instruction::PUSH1.opcode(), // Size
+            0x01,
+            Instruction::PUSH0.opcode(), // Offset
+            Instruction::PUSH0.opcode(), // Dest offset
+            Instruction::RETURNDATACOPY.opcode(),
+        ]],
+        expected: TestContractRun {
+            result: ReturnCode::InvalidReturnDataAccess,
+            ..Default::default()
+        },
+        ..Default::default()
+    },
+
+    calls_keep_every_sub_call_frame: Test {
+        roms: vec![vec![
+            Instruction::PUSH0.opcode(), // Output len
+            Instruction::PUSH0.opcode(), // Output offset
+            Instruction::PUSH1.opcode(), // Input len
+            0x01,
+            Instruction::PUSH0.opcode(), // Input offset
+            Instruction::PUSH0.opcode(), // Value
+            Instruction::PUSH1.opcode(), // Address
+            0x01,
+            Instruction::GAS.opcode(), // Gas
+            Instruction::CALL.opcode(),
+            Instruction::PUSH0.opcode(), // Output len
+            Instruction::PUSH0.opcode(), // Output offset
+            Instruction::PUSH1.opcode(), // Input len
+            0x02,
+            Instruction::PUSH0.opcode(), // Input offset
+            Instruction::PUSH0.opcode(), // Value
+            Instruction::PUSH1.opcode(), // Address
+            0x01,
+            Instruction::GAS.opcode(), // Gas
+            Instruction::CALL.opcode(),
+        ], vec![
+            Instruction::CALLDATASIZE.opcode(),
+        ]],
+        expected: TestContractRun {
+            stack_ptr: 2,
+            stack: vec![stack_word(&[0x01]), stack_word(&[0x01])],
+            sub_calls: Some(vec![
+                TestContractRun {
+                    stack_ptr: 1,
+                    stack: vec![stack_word(&[0x01])],
+                    ..Default::default()
+                },
+                TestContractRun {
+                    stack_ptr: 1,
+                    stack: vec![stack_word(&[0x02])],
+                    ..Default::default()
+                },
+            ]),
+            ..Default::default()
+        },
+        ..Default::default()
+    },
+
+    reentrant_call_into_caller: Test {
+        roms: vec![vec![
+            Instruction::CALLDATASIZE.opcode(),
+            Instruction::PUSH1.opcode(), // Re-entered
+            0x0E,
+            Instruction::JUMPI.opcode(),
+            Instruction::PUSH0.opcode(), // Output len
+            Instruction::PUSH0.opcode(), // Output offset
+            Instruction::PUSH0.opcode(), // Input len
+            Instruction::PUSH0.opcode(), // Input offset
+            Instruction::PUSH0.opcode(), // Value
+            Instruction::PUSH1.opcode(), // Address
+            0x01,
+            Instruction::GAS.opcode(), // Gas
+            Instruction::CALL.opcode(),
+            Instruction::STOP.opcode(),
+            Instruction::JUMPDEST.opcode(),
+            Instruction::PUSH1.opcode(), // Value
+            0x2A,
+            Instruction::PUSH1.opcode(), // Key
+            0x01,
+            Instruction::SSTORE.opcode(),
+        ], vec![
+            Instruction::PUSH0.opcode(), // Output len
+            Instruction::PUSH0.opcode(), // Output offset
+            Instruction::PUSH1.opcode(), // Input len
+            0x01,
+            Instruction::PUSH0.opcode(), // Input offset
+            Instruction::PUSH0.opcode(), // Value
+            Instruction::PUSH0.opcode(), // Address
+            Instruction::GAS.opcode(), // Gas
+            Instruction::CALL.opcode(),
+        ]],
+        expected: TestContractRun {
+            result: ReturnCode::Stop,
+            stack_ptr: 1,
+            jump_ptr: 0x0E,
+            stack: vec![stack_word(&[0x01])],
+            storage: vec![(test_address(0), stack_word(&[0x01]), stack_word(&[0x2A]))],
+            sub_calls: Some(vec![TestContractRun {
+                stack_ptr: 1,
+                stack: vec![stack_word(&[0x01])],
+                sub_calls: Some(vec![TestContractRun {
+                    jump_ptr: 0x0E,
+                    sub_calls: Some(vec![]),
+                    ..Default::default()
+                }]),
+                ..Default::default()
+            }]),
+            ..Default::default()
+        },
+        ..Default::default()
+    },
+
+    call_depth_is_limited: Test {
+        roms: vec![vec![
+            Instruction::PUSH0.opcode(), // Key
+            Instruction::SLOAD.opcode(),
+            Instruction::PUSH1.opcode(),
+            0x01,
+            Instruction::ADD.opcode(),
+            Instruction::PUSH0.opcode(), // Key
+            Instruction::SSTORE.opcode(),
+            Instruction::PUSH0.opcode(), // Output len
+            Instruction::PUSH0.opcode(), // Output offset
+            Instruction::PUSH0.opcode(), // Input len
+            Instruction::PUSH0.opcode(), // Input offset
+            Instruction::PUSH0.opcode(), // Value
+            Instruction::PUSH0.opcode(), // Address
+            Instruction::GAS.opcode(), // Gas
+            Instruction::CALL.opcode(),
+        ]],
+        expected: TestContractRun {
+            stack_ptr: 1,
+            stack: vec![stack_word(&[0x01])],
+            // The top-level frame plus 1024 nested calls
+            storage: vec![(test_address(0), stack_word(&[]), stack_word(&[0x01, 0x04]))],
+            ..Default::default()
+        },
+        // Every call keeps a 64th of the gas back, so reaching the limit takes a lot of it
+        gas_limit: Some(u64::MAX),
+        ..Default::default()
+    },
+
+    create_past_depth_limit_keeps_nonce: Test {
+        roms: vec![vec![
+            Instruction::PUSH0.opcode(), // Output len
+            Instruction::PUSH0.opcode(), // Output offset
+            Instruction::PUSH0.opcode(), // Input len
+            Instruction::PUSH0.opcode(), // Input offset
+            Instruction::PUSH0.opcode(), // Value
+            Instruction::PUSH0.opcode(), // Address
+            Instruction::GAS.opcode(), // Gas
+            Instruction::CALL.opcode(),
+            Instruction::PUSH0.opcode(), // Size
+            Instruction::PUSH0.opcode(), // Offset
+            Instruction::PUSH0.opcode(), // Value
+            Instruction::CREATE.opcode(),
+        ]],
+        expected: TestContractRun {
+            // Every frame but the innermost one, which is at the limit, creates a contract, and
+            // the top-level frame does so last
+            stack_ptr: 2,
+            stack: vec![
+                stack_word(&[0x01]),
+                address_to_word(&create_address(&test_address(0), 1023)),
+            ],
+            nonces: vec![(test_address(0), 1024)],
+            ..Default::default()
+        },
+        gas_limit: Some(u64::MAX),
+        ..Default::default()
+    },
+
+    delegatecall_uses_caller_storage: Test {
+        roms: vec![vec![
+            Instruction::PUSH0.opcode(), // Output len
+            Instruction::PUSH0.opcode(), // Output offset
+            Instruction::PUSH0.opcode(), // Input len
+            Instruction::PUSH0.opcode(), // Input offset
+            Instruction::PUSH1.opcode(), // Address
+            0x01,
+            Instruction::GAS.opcode(), // Gas
+            Instruction::DELEGATECALL.opcode(),
+        ], vec![
+            Instruction::PUSH1.opcode(), // Value
+            0x07,
+            Instruction::PUSH1.opcode(), // Key
+            0x01,
+            Instruction::SSTORE.opcode(),
+        ]],
+        expected: TestContractRun {
+            stack_ptr: 1,
+            stack: vec![stack_word(&[0x01])],
+            storage: vec![
+                (test_address(0), stack_word(&[0x01]), stack_word(&[0x07])),
+                (test_address(1), stack_word(&[0x01]), stack_word(&[])),
+            ],
+            ..Default::default()
+        },
+        ..Default::default()
+    },
+
+    callcode_uses_caller_storage: Test {
+        roms: vec![vec![
+            Instruction::PUSH0.opcode(), // Output len
+            Instruction::PUSH0.opcode(), // Output offset
+            Instruction::PUSH0.opcode(), // Input len
+            Instruction::PUSH0.opcode(), // Input offset
+            Instruction::PUSH1.opcode(), // Value
+            0x00,
+            Instruction::PUSH1.opcode(), // Address
+            0x01,
+            Instruction::GAS.opcode(), // Gas
+            Instruction::CALLCODE.opcode(),
+        ], vec![
+            Instruction::PUSH1.opcode(), // Value
+            0x07,
+            Instruction::PUSH1.opcode(), // Key
+            0x01,
+            Instruction::SSTORE.opcode(),
+        ]],
+        expected: TestContractRun {
+            stack_ptr: 1,
+            stack: vec![stack_word(&[0x01])],
+            storage: vec![
+                (test_address(0), stack_word(&[0x01]), stack_word(&[0x07])),
+                (test_address(1), stack_word(&[0x01]), stack_word(&[])),
+            ],
+            ..Default::default()
+        },
+        ..Default::default()
+    },
+
+    staticcall_allows_reads: Test {
+        roms: vec![vec![
+            Instruction::PUSH0.opcode(), // Output len
+            Instruction::PUSH0.opcode(), // Output offset
+            Instruction::PUSH0.opcode(), // Input len
+            Instruction::PUSH0.opcode(), // Input offset
+            Instruction::PUSH1.opcode(), // Address
+            0x01,
+            Instruction::GAS.opcode(), // Gas
+            Instruction::STATICCALL.opcode(),
+        ], vec![
+            Instruction::PUSH1.opcode(), // Key
+            0x01,
+            Instruction::SLOAD.opcode(),
+            Instruction::PUSH1.opcode(), // Key
+            0x01,
+            Instruction::TLOAD.opcode(),
+        ]],
+        expected: TestContractRun {
+            stack_ptr: 1,
+            stack: vec![stack_word(&[0x01])],
+            ..Default::default()
+        },
+        ..Default::default()
+    },
+
+    staticcall_rejects_sstore: Test {
+        roms: vec![vec![
+            Instruction::PUSH0.opcode(), // Output len
+            Instruction::PUSH0.opcode(), // Output offset
+            Instruction::PUSH0.opcode(), // Input len
+            Instruction::PUSH0.opcode(), // Input offset
+            Instruction::PUSH1.opcode(), // Address
+            0x01,
+            Instruction::GAS.opcode(), // Gas
+            Instruction::STATICCALL.opcode(),
+        ], vec![
+            Instruction::PUSH1.opcode(), // Value
+            0x07,
+            Instruction::PUSH1.opcode(), // Key
+            0x01,
+            Instruction::SSTORE.opcode(),
+        ]],
+        expected: TestContractRun {
+            stack_ptr: 1,
+            stack: vec![stack_word(&[0x00])],
+            storage: vec![(test_address(1), stack_word(&[0x01]), stack_word(&[]))],
+            sub_calls: Some(vec![TestContractRun {
+                result: ReturnCode::StaticCallViolation,
+                ..Default::default()
+            }]),
+            ..Default::default()
+        },
+        ..Default::default()
+    },
+
+    staticcall_is_inherited_by_nested_calls: Test {
+        roms: vec![vec![
+            Instruction::PUSH0.opcode(), // Output len
+            Instruction::PUSH0.opcode(), // Output offset
+            Instruction::PUSH0.opcode(), // Input len
+            Instruction::PUSH0.opcode(), // Input offset
+            Instruction::PUSH1.opcode(), // Address
+            0x01,
+            Instruction::GAS.opcode(), // Gas
+            Instruction::STATICCALL.opcode(),
+        ], vec![
+            Instruction::PUSH0.opcode(), // Output len
+            Instruction::PUSH0.opcode(), // Output offset
+            Instruction::PUSH0.opcode(), // Input len
+            Instruction::PUSH0.opcode(), // Input offset
+            Instruction::PUSH1.opcode(), // Value
+            0x00,
+            Instruction::PUSH1.opcode(), // Address
+            0x02,
+            Instruction::GAS.opcode(), // Gas
+            Instruction::CALL.opcode(),
+        ], vec![
+            Instruction::PUSH1.opcode(), // Value
+            0x07,
+            Instruction::PUSH1.opcode(), // Key
+            0x01,
+            Instruction::TSTORE.opcode(),
+        ]],
+        expected: TestContractRun {
+            stack_ptr: 1,
+            stack: vec![stack_word(&[0x01])],
+            sub_calls: Some(vec![TestContractRun {
+                stack_ptr: 1,
+                stack: vec![stack_word(&[0x00])],
+                sub_calls: Some(vec![TestContractRun {
+                    result: ReturnCode::StaticCallViolation,
+                    ..Default::default()
+                }]),
+                ..Default::default()
+            }]),
+            ..Default::default()
+        },
+        ..Default::default()
+    },
+
+    staticcall_rejects_call_with_value: Test {
+        roms: vec![vec![
+            Instruction::PUSH0.opcode(), // Output len
+            Instruction::PUSH0.opcode(), // Output offset
+            Instruction::PUSH0.opcode(), // Input len
+            Instruction::PUSH0.opcode(), // Input offset
+            Instruction::PUSH1.opcode(), // Address
+            0x01,
+            Instruction::GAS.opcode(), // Gas
+            Instruction::STATICCALL.opcode(),
+        ], vec![
+            Instruction::PUSH0.opcode(), // Output len
+            Instruction::PUSH0.opcode(), // Output offset
+            Instruction::PUSH0.opcode(), // Input len
+            Instruction::PUSH0.opcode(), // Input offset
+            Instruction::PUSH1.opcode(), // Value
+            0x01,
+            Instruction::PUSH1.opcode(), // Address
+            0x02,
+            Instruction::GAS.opcode(), // Gas
+            Instruction::CALL.opcode(),
+        ]],
+        expected: TestContractRun {
+            stack_ptr: 1,
+            stack: vec![stack_word(&[0x00])],
+            sub_calls: Some(vec![TestContractRun {
+                result: ReturnCode::StaticCallViolation,
+                sub_calls: Some(vec![]),
+                ..Default::default()
+            }]),
+            ..Default::default()
+        },
+        ..Default::default()
+    },
+
+    log2_records_topics_and_data: Test {
+        roms: vec![vec![
+            Instruction::PUSH1.opcode(), // Value
+            0xAB,
+            Instruction::PUSH0.opcode(), // Offset
+            Instruction::MSTORE8.opcode(),
+            Instruction::PUSH1.opcode(), // Topic 1
+            0x22,
+            Instruction::PUSH1.opcode(), // Topic 0
+            0x11,
+            Instruction::PUSH1.opcode(), // Size
+            0x01,
+            Instruction::PUSH0.opcode(), // Offset
+            Instruction::LOG2.opcode(),
+        ]],
+        expected: TestContractRun {
+            logs: Some(vec![Log::new(
+                test_address(0),
+                vec![stack_word(&[0x11]), stack_word(&[0x22])],
+                vec![0xAB],
+            )]),
+            ..Default::default()
+        },
+        ..Default::default()
+    },
+
+    log0_without_data: Test {
+        roms: vec![vec![
+            Instruction::PUSH0.opcode(), // Size
+            Instruction::PUSH0.opcode(), // Offset
+            Instruction::LOG0.opcode(),
+            Instruction::MSIZE.opcode(),
+        ]],
+        expected: TestContractRun {
+            stack_ptr: 1,
+            stack: vec![stack_word(&[])],
+            logs: Some(vec![Log::new(test_address(0), vec![], vec![])]),
+            ..Default::default()
+        },
+        ..Default::default()
+    },
+
+    reverted_sub_call_discards_logs: Test {
+        roms: vec![vec![
+            Instruction::PUSH0.opcode(), // Size
+            Instruction::PUSH0.opcode(), // Offset
+            Instruction::LOG0.opcode(),
+            Instruction::PUSH0.opcode(), // Output len
+            Instruction::PUSH0.opcode(), // Output offset
+            Instruction::PUSH0.opcode(), // Input len
+            Instruction::PUSH0.opcode(), // Input offset
+            Instruction::PUSH0.opcode(), // Value
+            Instruction::PUSH1.opcode(), // Address
+            0x01,
+            Instruction::GAS.opcode(), // Gas
+            Instruction::CALL.opcode(),
+            Instruction::PUSH0.opcode(), // Output len
+            Instruction::PUSH0.opcode(), // Output offset
+            Instruction::PUSH0.opcode(), // Input len
+            Instruction::PUSH0.opcode(), // Input offset
+            Instruction::PUSH0.opcode(), // Value
+            Instruction::PUSH1.opcode(), // Address
+            0x02,
+            Instruction::GAS.opcode(), // Gas
+            Instruction::CALL.opcode(),
+        ], vec![
+            Instruction::PUSH1.opcode(), // Topic 0
+            0x01,
+            Instruction::PUSH0.opcode(), // Size
+            Instruction::PUSH0.opcode(), // Offset
+            Instruction::LOG1.opcode(),
+        ], vec![
+            Instruction::PUSH1.opcode(), // Topic 0
+            0x02,
+            Instruction::PUSH0.opcode(), // Size
+            Instruction::PUSH0.opcode(), // Offset
+            Instruction::LOG1.opcode(),
+            Instruction::PUSH0.opcode(), // Size
+            Instruction::PUSH0.opcode(), // Offset
+            Instruction::REVERT.opcode(),
+        ]],
+        expected: TestContractRun {
+            stack_ptr: 2,
+            stack: vec![stack_word(&[0x01]), stack_word(&[0x00])],
+            logs: Some(vec![
+                Log::new(test_address(0), vec![], vec![]),
+                Log::new(test_address(1), vec![stack_word(&[0x01])], vec![]),
+            ]),
+            ..Default::default()
+        },
+        ..Default::default()
+    },
+
+    log_in_staticcall_fails: Test {
+        roms: vec![vec![
+            Instruction::PUSH0.opcode(), // Output len
+            Instruction::PUSH0.opcode(), // Output offset
+            Instruction::PUSH0.opcode(), // Input len
+            Instruction::PUSH0.opcode(), // Input offset
+            Instruction::PUSH1.opcode(), // Address
+            0x01,
+            Instruction::GAS.opcode(), // Gas
+            Instruction::STATICCALL.opcode(),
+        ], vec![
+            Instruction::PUSH0.opcode(), // Size
+            Instruction::PUSH0.opcode(), // Offset
+            Instruction::LOG0.opcode(),
+        ]],
+        expected: TestContractRun {
+            stack_ptr: 1,
+            stack: vec![stack_word(&[0x00])],
+            logs: Some(vec![]),
+            ..Default::default()
+        },
+        ..Default::default()
+    },
+
+    create_deploys_returned_code: Test {
+        roms: vec![[
+            vec![Instruction::PUSH17.opcode()], // Init code
+            store_init_code(),
+            vec![
+                Instruction::PUSH0.opcode(), // Offset
+                Instruction::MSTORE.opcode(),
+                Instruction::PUSH1.opcode(), // Size
+                0x11,
+                Instruction::PUSH1.opcode(), // Offset
+                0x0f,
+                Instruction::PUSH0.opcode(), // Value
+                Instruction::CREATE.opcode(),
+                Instruction::PUSH0.opcode(), // Output len
+                Instruction::PUSH0.opcode(), // Output offset
+                Instruction::PUSH0.opcode(), // Input len
+                Instruction::PUSH0.opcode(), // Input offset
+                Instruction::PUSH0.opcode(), // Value
+                Instruction::DUP6.opcode(), // Address
+                Instruction::GAS.opcode(), // Gas
+                Instruction::CALL.opcode(),
+            ],
+        ].concat()],
+        expected: TestContractRun {
+            stack_ptr: 2,
+            stack: vec![
+                address_to_word(&create_address(&test_address(0), 0)),
+                stack_word(&[0x01]),
+            ],
+            storage: vec![
+                (create_address(&test_address(0), 0), stack_word(&[0x01]), stack_word(&[0x07])),
+                (create_address(&test_address(0), 0), stack_word(&[]), stack_word(&[0x2a])),
+            ],
+            ..Default::default()
+        },
+        ..Default::default()
+    },
+
+    create_undone_by_revert_leaves_no_code: Test {
+        roms: vec![
+            [
+                vec![
+                    Instruction::PUSH0.opcode(), // Output len
+                    Instruction::PUSH0.opcode(), // Output offset
+                    Instruction::PUSH0.opcode(), // Input len
+                    Instruction::PUSH0.opcode(), // Input offset
+                    Instruction::PUSH0.opcode(), // Value
+                    Instruction::PUSH1.opcode(), // Address
+                    0x01,
+                    Instruction::GAS.opcode(), // Gas
+                    Instruction::CALL.opcode(),
+                    Instruction::PUSH0.opcode(), // Output len
+                    Instruction::PUSH0.opcode(), // Output offset
+                    Instruction::PUSH0.opcode(), // Input len
+                    Instruction::PUSH0.opcode(), // Input offset
+                    Instruction::PUSH0.opcode(), // Value
+                    Instruction::PUSH20.opcode(), // Address
+                ],
+                create_address(&test_address(1), 0).to_vec(),
+                vec![
+                    Instruction::GAS.opcode(), // Gas
+                    Instruction::CALL.opcode(),
+                ],
+            ].concat(),
+            [
+                vec![Instruction::PUSH17.opcode()], // Init code
+                store_init_code(),
+                vec![
+                    Instruction::PUSH0.opcode(), // Offset
+                    Instruction::MSTORE.opcode(),
+                    Instruction::PUSH1.opcode(), // Size
+                    0x11,
+                    Instruction::PUSH1.opcode(), // Offset
+                    0x0f,
+                    Instruction::PUSH0.opcode(), // Value
+                    Instruction::CREATE.opcode(),
+                    Instruction::PUSH0.opcode(), // Size
+                    Instruction::PUSH0.opcode(), // Offset
+                    Instruction::REVERT.opcode(),
+                ],
+            ].concat(),
+        ],
+        expected: TestContractRun {
+            // The code compiled for the reverted creation must not run when the address is called
+            stack_ptr: 2,
+            stack: vec![stack_word(&[]), stack_word(&[0x01])],
+            storage: vec![
+                (create_address(&test_address(1), 0), stack_word(&[0x01]), stack_word(&[])),
+                (create_address(&test_address(1), 0), stack_word(&[]), stack_word(&[])),
+            ],
+            ..Default::default()
+        },
+        ..Default::default()
+    },
+
+    create_runs_init_code_at_size_limit: Test {
+        roms: vec![create_with_zero_init_code(MAX_INITCODE_SIZE)],
+        expected: TestContractRun {
+            stack_ptr: 1,
+            stack: vec![address_to_word(&create_address(&test_address(0), 0))],
+            ..Default::default()
+        },
+        ..Default::default()
+    },
+
+    create_init_code_over_size_limit_fails_creator: Test {
+        roms: vec![create_with_zero_init_code(MAX_INITCODE_SIZE + 1)],
+        expected: TestContractRun {
+            result: ReturnCode::OutOfGas,
+            gas_used: Some(TEST_GAS_LIMIT),
+            ..Default::default()
+        },
+        ..Default::default()
+    },
+
+    create_deploys_code_at_size_limit: Test {
+        roms: vec![create_with_zero_code(MAX_CODE_SIZE)],
+        expected: TestContractRun {
+            stack_ptr: 1,
+            stack: vec![address_to_word(&create_address(&test_address(0), 0))],
+            ..Default::default()
+        },
+        ..Default::default()
+    },
+
+    create_code_over_size_limit_fails: Test {
+        roms: vec![create_with_zero_code(MAX_CODE_SIZE + 1)],
+        expected: TestContractRun {
+            stack_ptr: 1,
+            stack: vec![stack_word(&[])],
+            ..Default::default()
+        },
+        ..Default::default()
+    },
+
+    create_runs_init_code_over_size_limit_before_shanghai: Test {
+        roms: vec![create_with_zero_init_code(MAX_INITCODE_SIZE + 1)],
+        spec_id: SpecId::Paris,
+        expected: TestContractRun {
+            stack_ptr: 1,
+            stack: vec![address_to_word(&create_address(&test_address(0), 0))],
+            ..Default::default()
+        },
+        ..Default::default()
+    },
+
+    create_init_code_over_size_limit_fails_creator_in_shanghai: Test {
+        roms: vec![create_with_zero_init_code(MAX_INITCODE_SIZE + 1)],
+        spec_id: SpecId::Shanghai,
+        expected: TestContractRun {
+            result: ReturnCode::OutOfGas,
+            gas_used: Some(TEST_GAS_LIMIT),
+            ..Default::default()
+        },
+        ..Default::default()
+    },
+
+    create_deploys_code_over_size_limit_before_spurious_dragon: Test {
+        roms: vec![create_with_zero_code(MAX_CODE_SIZE + 1)],
+        spec_id: SpecId::TangerineWhistle,
+        expected: TestContractRun {
+            stack_ptr: 1,
+            stack: vec![address_to_word(&create_address(&test_address(0), 0))],
+            ..Default::default()
+        },
+        ..Default::default()
+    },
+
+    create_code_over_size_limit_fails_in_spurious_dragon: Test {
+        roms: vec![create_with_zero_code(MAX_CODE_SIZE + 1)],
+        spec_id: SpecId::SpuriousDragon,
+        expected: TestContractRun {
+            stack_ptr: 1,
+            stack: vec![stack_word(&[])],
+            ..Default::default()
+        },
+        ..Default::default()
+    },
+
+    create_uses_increasing_nonces: Test {
+        roms: vec![vec![
+            Instruction::PUSH0.opcode(), // Size
+            Instruction::PUSH0.opcode(), // Offset
+            Instruction::PUSH0.opcode(), // Value
+            Instruction::CREATE.opcode(),
+            Instruction::PUSH0.opcode(), // Size
+            Instruction::PUSH0.opcode(), // Offset
+            Instruction::PUSH0.opcode(), // Value
+            Instruction::CREATE.opcode(),
+        ]],
+        expected: TestContractRun {
+            stack_ptr: 2,
+            stack: vec![
+                address_to_word(&create_address(&test_address(0), 0)),
+                address_to_word(&create_address(&test_address(0), 1)),
+            ],
+            ..Default::default()
+        },
+        ..Default::default()
+    },
+
+    create2_derives_address_from_salt: Test {
+        roms: vec![[
+            vec![Instruction::PUSH17.opcode()], // Init code
+            store_init_code(),
+            vec![
+                Instruction::PUSH0.opcode(), // Offset
+                Instruction::MSTORE.opcode(),
+                Instruction::PUSH1.opcode(), // Salt
+                0x05,
+                Instruction::PUSH1.opcode(), // Size
+                0x11,
+                Instruction::PUSH1.opcode(), // Offset
+                0x0f,
+                Instruction::PUSH0.opcode(), // Value
+                Instruction::CREATE2.opcode(),
+            ],
+        ].concat()],
+        expected: TestContractRun {
+            stack_ptr: 1,
+            stack: vec![address_to_word(&create2_address(
+                &test_address(0),
+                &stack_word(&[0x05]),
+                &store_init_code(),
+            ))],
+            storage: vec![(
+                create2_address(&test_address(0), &stack_word(&[0x05]), &store_init_code()),
+                stack_word(&[0x01]),
+                stack_word(&[0x07]),
+            )],
+            ..Default::default()
+        },
+        ..Default::default()
+    },
+
+    create2_at_used_address_fails: Test {
+        roms: vec![vec![
+            Instruction::PUSH1.opcode(), // Salt
+            0x05,
+            Instruction::PUSH0.opcode(), // Size
+            Instruction::PUSH0.opcode(), // Offset
+            Instruction::PUSH0.opcode(), // Value
+            Instruction::CREATE2.opcode(),
+            Instruction::PUSH1.opcode(), // Salt
+            0x05,
+            Instruction::PUSH0.opcode(), // Size
+            Instruction::PUSH0.opcode(), // Offset
+            Instruction::PUSH0.opcode(), // Value
+            Instruction::CREATE2.opcode(),
+        ]],
+        expected: TestContractRun {
+            stack_ptr: 2,
+            stack: vec![
+                address_to_word(&create2_address(&test_address(0), &stack_word(&[0x05]), &[])),
+                stack_word(&[0x00]),
+            ],
+            ..Default::default()
+        },
+        ..Default::default()
+    },
+
+    create2_undone_by_revert_can_be_repeated: Test {
+        roms: vec![
+            [
+                vec![
+                    Instruction::PUSH0.opcode(), // Output len
+                    Instruction::PUSH0.opcode(), // Output offset
+                    Instruction::PUSH1.opcode(), // Input len
+                    0x01,
+                    Instruction::PUSH0.opcode(), // Input offset
+                    Instruction::PUSH0.opcode(), // Value
+                    Instruction::PUSH1.opcode(), // Address
+                    0x01,
+                    Instruction::GAS.opcode(), // Gas
+                    Instruction::CALL.opcode(),
+                    Instruction::PUSH0.opcode(), // Output len
+                    Instruction::PUSH0.opcode(), // Output offset
+                    Instruction::PUSH0.opcode(), // Input len
+                    Instruction::PUSH0.opcode(), // Input offset
+                    Instruction::PUSH0.opcode(), // Value
+                    Instruction::PUSH1.opcode(), // Address
+                    0x01,
+                    Instruction::GAS.opcode(), // Gas
+                    Instruction::CALL.opcode(),
+                    Instruction::PUSH0.opcode(), // Output len
+                    Instruction::PUSH0.opcode(), // Output offset
+                    Instruction::PUSH0.opcode(), // Input len
+                    Instruction::PUSH0.opcode(), // Input offset
+                    Instruction::PUSH0.opcode(), // Value
+                    Instruction::PUSH20.opcode(), // Address
+                ],
+                create2_address(&test_address(1), &stack_word(&[0x05]), &store_init_code())
+                    .to_vec(),
+                vec![
+                    Instruction::GAS.opcode(), // Gas
+                    Instruction::CALL.opcode(),
+                ],
+            ].concat(),
+            [
+                vec![Instruction::PUSH17.opcode()], // Init code
+                store_init_code(),
+                vec![
+                    Instruction::PUSH0.opcode(), // Offset
+                    Instruction::MSTORE.opcode(),
+                    Instruction::PUSH1.opcode(), // Salt
+                    0x05,
+                    Instruction::PUSH1.opcode(), // Size
+                    0x11,
+                    Instruction::PUSH1.opcode(), // Offset
+                    0x0f,
+                    Instruction::PUSH0.opcode(), // Value
+                    Instruction::CREATE2.opcode(),
+                    // Revert the creation when called with input
+                    Instruction::CALLDATASIZE.opcode(),
+                    Instruction::PUSH1.opcode(),
+                    0x21,
+                    Instruction::JUMPI.opcode(),
+                    Instruction::STOP.opcode(),
+                    Instruction::JUMPDEST.opcode(),
+                    Instruction::PUSH0.opcode(), // Size
+                    Instruction::PUSH0.opcode(), // Offset
+                    Instruction::REVERT.opcode(),
+                ],
+            ].concat(),
+        ],
+        expected: TestContractRun {
+            // The address of the reverted creation is free again, and the code deployed by the
+            // repeated creation runs when it is called
+            stack_ptr: 3,
+            stack: vec![stack_word(&[]), stack_word(&[0x01]), stack_word(&[0x01])],
+            storage: vec![
+                (
+                    create2_address(&test_address(1), &stack_word(&[0x05]), &store_init_code()),
+                    stack_word(&[0x01]),
+                    stack_word(&[0x07]),
+                ),
+                (
+                    create2_address(&test_address(1), &stack_word(&[0x05]), &store_init_code()),
+                    stack_word(&[]),
+                    stack_word(&[0x2a]),
+                ),
+            ],
+            ..Default::default()
+        },
+        ..Default::default()
+    },
+
+    create_with_reverting_init_code_fails: Test {
+        roms: vec![vec![
+            Instruction::PUSH8.opcode(), // Init code
+            Instruction::PUSH1.opcode(), // Value
+            0x01,
+            Instruction::PUSH0.opcode(), // Key
+            Instruction::SSTORE.opcode(),
+            Instruction::PUSH1.opcode(), // Size
+            0x01,
+            Instruction::PUSH0.opcode(), // Offset
+            Instruction::REVERT.opcode(),
+            Instruction::PUSH0.opcode(), // Offset
+            Instruction::MSTORE.opcode(),
+            Instruction::PUSH1.opcode(), // Size
+            0x08,
+            Instruction::PUSH1.opcode(), // Offset
+            0x18,
+            Instruction::PUSH0.opcode(), // Value
+            Instruction::CREATE.opcode(),
+            Instruction::RETURNDATASIZE.opcode(),
+        ]],
+        expected: TestContractRun {
+            stack_ptr: 2,
+            stack: vec![stack_word(&[0x00]), stack_word(&[0x01])],
+            storage: vec![(
+                create_address(&test_address(0), 0),
+                stack_word(&[]),
+                stack_word(&[]),
+            )],
+            ..Default::default()
+        },
+        ..Default::default()
+    },
+
+    create_in_staticcall_fails: Test {
+        roms: vec![vec![
+            Instruction::PUSH0.opcode(), // Output len
+            Instruction::PUSH0.opcode(), // Output offset
+            Instruction::PUSH0.opcode(), // Input len
+            Instruction::PUSH0.opcode(), // Input offset
+            Instruction::PUSH1.opcode(), // Address
+            0x01,
+            Instruction::GAS.opcode(), // Gas
+            Instruction::STATICCALL.opcode(),
+        ], vec![
+            Instruction::PUSH0.opcode(), // Size
+            Instruction::PUSH0.opcode(), // Offset
+            Instruction::PUSH0.opcode(), // Value
+            Instruction::CREATE.opcode(),
+        ]],
+        expected: TestContractRun {
+            stack_ptr: 1,
+            stack: vec![stack_word(&[0x00])],
+            ..Default::default()
+        },
+        ..Default::default()
+    },
+
+    coinbase: Test {
+        roms: vec![vec![Instruction::COINBASE.opcode()]],
+        expected: TestContractRun {
+            stack_ptr: 1,
+            stack: vec![stack_word_be(&[
+                0x01, 0x02, 0x03, 0x04, 0x05, 0x06, 0x07, 0x08, 0x09, 0x0a, 0x0b, 0x0c, 0x0d, 0x0e,
+                0x0f, 0x10, 0x11, 0x12, 0x13, 0x14,
+            ])],
+            ..Default::default()
+        },
+        ..Default::default()
+    },
+
+    block_number_and_timestamp: Test {
+        roms: vec![vec![
+            Instruction::NUMBER.opcode(),
+            Instruction::TIMESTAMP.opcode(),
+        ]],
+        expected: TestContractRun {
+            stack_ptr: 2,
+            stack: vec![
+                stack_word(&300u64.to_le_bytes()),
+                stack_word(&1717354173u64.to_le_bytes()),
+            ],
+            ..Default::default()
+        },
+        ..Default::default()
+    },
+
+    block_chain_and_fee_info: Test {
+        roms: vec![vec![
+            Instruction::DIFFICULTY.opcode(),
+            Instruction::GASLIMIT.opcode(),
+            Instruction::CHAINID.opcode(),
+            Instruction::BASEFEE.opcode(),
+            Instruction::BLOBBASEFEE.opcode(),
+        ]],
+        expected: TestContractRun {
+            stack_ptr: 5,
+            stack: vec![
+                stack_word(&100u64.to_le_bytes()),
+                stack_word(&100u64.to_le_bytes()),
+                stack_word(&1u64.to_le_bytes()),
+                stack_word(&5_000_000u64.to_le_bytes()),
+                stack_word(&1_000_000u64.to_le_bytes()),
+            ],
+            ..Default::default()
+        },
+        ..Default::default()
+    },
+
+    block_info_is_passed_to_sub_calls: Test {
+        roms: vec![vec![
+            Instruction::PUSH0.opcode(), // Output len
+            Instruction::PUSH0.opcode(), // Output offset
+            Instruction::PUSH0.opcode(), // Input len
+            Instruction::PUSH0.opcode(), // Input offset
+            Instruction::PUSH0.opcode(), // Value
+            Instruction::PUSH1.opcode(), // Address
+            0x01,
+            Instruction::GAS.opcode(), // Gas
+            Instruction::CALL.opcode(),
+        ], vec![
+            Instruction::NUMBER.opcode(),
+        ]],
+        expected: TestContractRun {
+            stack_ptr: 1,
+            stack: vec![stack_word(&[0x01])],
+            sub_calls: Some(vec![TestContractRun {
+                stack_ptr: 1,
+                stack: vec![stack_word(&300u64.to_le_bytes())],
+                ..Default::default()
+            }]),
+            ..Default::default()
+        },
+        ..Default::default()
+    },
+
+    blockhash_of_parent_block: Test {
+        roms: vec![vec![
+            Instruction::PUSH2.opcode(), // Block number
+            0x01,
+            0x2b,
+            Instruction::BLOCKHASH.opcode(),
+        ]],
+        expected: TestContractRun {
+            stack_ptr: 1,
+            stack: vec![new_test_block_info().hash_history()[0]],
+            ..Default::default()
+        },
+        ..Default::default()
+    },
+
+    blockhash_of_oldest_block_in_window: Test {
+        roms: vec![vec![
+            Instruction::PUSH2.opcode(), // Block number
+            0x00,
+            0x2c,
+            Instruction::BLOCKHASH.opcode(),
+        ]],
+        expected: TestContractRun {
+            stack_ptr: 1,
+            stack: vec![new_test_block_info().hash_history()[255]],
+            ..Default::default()
+        },
+        ..Default::default()
+    },
+
+    blockhash_before_window_is_zero: Test {
+        roms: vec![vec![
+            Instruction::PUSH2.opcode(), // Block number
+            0x00,
+            0x2b,
+            Instruction::BLOCKHASH.opcode(),
+        ]],
+        expected: TestContractRun {
+            stack_ptr: 1,
+            stack: vec![stack_word(&[0x00])],
+            ..Default::default()
+        },
+        ..Default::default()
+    },
+
+    blockhash_of_current_block_is_zero: Test {
+        roms: vec![vec![
+            Instruction::PUSH2.opcode(), // Block number
+            0x01,
+            0x2c,
+            Instruction::BLOCKHASH.opcode(),
+        ]],
+        expected: TestContractRun {
+            stack_ptr: 1,
+            stack: vec![stack_word(&[0x00])],
+            ..Default::default()
+        },
+        ..Default::default()
+    },
+
+    blockhash_of_future_block_is_zero: Test {
+        roms: vec![vec![
+            Instruction::PUSH2.opcode(), // Block number
+            0x01,
+            0x2d,
+            Instruction::BLOCKHASH.opcode(),
+        ]],
+        expected: TestContractRun {
+            stack_ptr: 1,
+            stack: vec![stack_word(&[0x00])],
+            ..Default::default()
+        },
+        ..Default::default()
+    },
+
+    address_caller_and_origin: Test {
+        roms: vec![vec![
+            Instruction::ADDRESS.opcode(),
+            Instruction::CALLER.opcode(),
+            Instruction::ORIGIN.opcode(),
+        ]],
+        expected: TestContractRun {
+            stack_ptr: 3,
+            stack: vec![
+                address_to_word(&test_address(0)),
+                address_to_word(&test_sender()),
+                address_to_word(&test_sender()),
+            ],
+            ..Default::default()
+        },
+        ..Default::default()
+    },
+
+    callvalue_and_gasprice: Test {
+        roms: vec![vec![
+            Instruction::CALLVALUE.opcode(),
+            Instruction::GASPRICE.opcode(),
+        ]],
+        expected: TestContractRun {
+            stack_ptr: 2,
+            stack: vec![stack_word(&[0x05]), stack_word(&[0x0a])],
+            ..Default::default()
+        },
+        ..Default::default()
+    },
+
+    call_sets_caller_and_address: Test {
+        roms: vec![vec![
+            Instruction::PUSH0.opcode(), // Output len
+            Instruction::PUSH0.opcode(), // Output offset
+            Instruction::PUSH0.opcode(), // Input len
+            Instruction::PUSH0.opcode(), // Input offset
+            Instruction::PUSH0.opcode(), // Value
+            Instruction::PUSH1.opcode(), // Address
+            0x01,
+            Instruction::GAS.opcode(), // Gas
+            Instruction::CALL.opcode(),
+        ], vec![
+            Instruction::ADDRESS.opcode(),
+            Instruction::CALLER.opcode(),
+            Instruction::ORIGIN.opcode(),
+            Instruction::CALLVALUE.opcode(),
+            Instruction::GASPRICE.opcode(),
+        ]],
+        expected: TestContractRun {
+            stack_ptr: 1,
+            stack: vec![stack_word(&[0x01])],
+            sub_calls: Some(vec![TestContractRun {
+                stack_ptr: 5,
+                stack: vec![
+                    address_to_word(&test_address(1)),
+                    address_to_word(&test_address(0)),
+                    address_to_word(&test_sender()),
+                    stack_word(&[0x00]),
+                    stack_word(&[0x0a]),
+                ],
+                ..Default::default()
+            }]),
+            ..Default::default()
+        },
+        ..Default::default()
+    },
+
+    delegatecall_keeps_caller_and_value: Test {
+        roms: vec![vec![
+            Instruction::PUSH0.opcode(), // Output len
+            Instruction::PUSH0.opcode(), // Output offset
+            Instruction::PUSH0.opcode(), // Input len
+            Instruction::PUSH0.opcode(), // Input offset
+            Instruction::PUSH1.opcode(), // Address
+            0x01,
+            Instruction::GAS.opcode(), // Gas
+            Instruction::DELEGATECALL.opcode(),
+        ], vec![
+            Instruction::ADDRESS.opcode(),
+            Instruction::CALLER.opcode(),
+            Instruction::CALLVALUE.opcode(),
+        ]],
+        expected: TestContractRun {
+            stack_ptr: 1,
+            stack: vec![stack_word(&[0x01])],
+            sub_calls: Some(vec![TestContractRun {
+                stack_ptr: 3,
+                stack: vec![
+                    address_to_word(&test_address(0)),
+                    address_to_word(&test_sender()),
+                    stack_word(&[0x05]),
+                ],
+                ..Default::default()
+            }]),
+            ..Default::default()
+        },
+        ..Default::default()
+    },
+
+    balance_and_selfbalance: Test {
+        roms: vec![vec![
+            Instruction::SELFBALANCE.opcode(),
+            Instruction::PUSH1.opcode(), // Address
+            0x01,
+            Instruction::BALANCE.opcode(),
+            Instruction::PUSH1.opcode(), // Address
+            0x99,
+            Instruction::BALANCE.opcode(),
+        ]],
+        balances: vec![
+            (test_address(0), stack_word(&[0x64])),
+            (test_address(1), stack_word(&[0x0a])),
+        ],
+        expected: TestContractRun {
+            stack_ptr: 3,
+            stack: vec![stack_word(&[0x64]), stack_word(&[0x0a]), stack_word(&[])],
+            ..Default::default()
+        },
+        ..Default::default()
+    },
+
+    call_transfers_value: Test {
+        roms: vec![vec![
+            Instruction::PUSH0.opcode(), // Output len
+            Instruction::PUSH0.opcode(), // Output offset
+            Instruction::PUSH0.opcode(), // Input len
+            Instruction::PUSH0.opcode(), // Input offset
+            Instruction::PUSH1.opcode(), // Value
+            0x2A,
+            Instruction::PUSH1.opcode(), // Address
+            0x01,
+            Instruction::GAS.opcode(), // Gas
+            Instruction::CALL.opcode(),
+        ], vec![
+            Instruction::SELFBALANCE.opcode(),
+            Instruction::CALLVALUE.opcode(),
+        ]],
+        balances: vec![(test_address(0), stack_word(&[0x64]))],
+        expected: TestContractRun {
+            stack_ptr: 1,
+            stack: vec![stack_word(&[0x01])],
+            balances: vec![
+                (test_address(0), stack_word(&[0x3A])),
+                (test_address(1), stack_word(&[0x2A])),
+            ],
+            sub_calls: Some(vec![TestContractRun {
+                stack_ptr: 2,
+                stack: vec![stack_word(&[0x2A]), stack_word(&[0x2A])],
+                ..Default::default()
+            }]),
+            ..Default::default()
+        },
+        ..Default::default()
+    },
+
+    call_with_insufficient_balance_fails: Test {
+        roms: vec![vec![
+            Instruction::PUSH0.opcode(), // Output len
+            Instruction::PUSH0.opcode(), // Output offset
+            Instruction::PUSH0.opcode(), // Input len
+            Instruction::PUSH0.opcode(), // Input offset
+            Instruction::PUSH1.opcode(), // Value
+            0x2A,
+            Instruction::PUSH1.opcode(), // Address
+            0x01,
+            Instruction::GAS.opcode(), // Gas
+            Instruction::CALL.opcode(),
+        ], vec![
+            Instruction::PUSH1.opcode(), // Value
+            0x07,
+            Instruction::PUSH1.opcode(), // Key
+            0x01,
+            Instruction::SSTORE.opcode(),
+        ]],
+        balances: vec![(test_address(0), stack_word(&[0x10]))],
+        expected: TestContractRun {
+            stack_ptr: 1,
+            stack: vec![stack_word(&[0x00])],
+            storage: vec![(test_address(1), stack_word(&[0x01]), stack_word(&[]))],
+            balances: vec![
+                (test_address(0), stack_word(&[0x10])),
+                (test_address(1), stack_word(&[])),
+            ],
+            sub_calls: Some(vec![]),
+            ..Default::default()
+        },
+        ..Default::default()
+    },
+
+    call_value_is_returned_when_callee_reverts: Test {
+        roms: vec![vec![
+            Instruction::PUSH0.opcode(), // Output len
+            Instruction::PUSH0.opcode(), // Output offset
+            Instruction::PUSH0.opcode(), // Input len
+            Instruction::PUSH0.opcode(), // Input offset
+            Instruction::PUSH1.opcode(), // Value
+            0x2A,
+            Instruction::PUSH1.opcode(), // Address
+            0x01,
+            Instruction::GAS.opcode(), // Gas
+            Instruction::CALL.opcode(),
+        ], vec![
+            Instruction::PUSH0.opcode(), // Size
+            Instruction::PUSH0.opcode(), // Offset
+            Instruction::REVERT.opcode(),
+        ]],
+        balances: vec![(test_address(0), stack_word(&[0x64]))],
+        expected: TestContractRun {
+            stack_ptr: 1,
+            stack: vec![stack_word(&[0x00])],
+            balances: vec![
+                (test_address(0), stack_word(&[0x64])),
+                (test_address(1), stack_word(&[])),
+            ],
+            sub_calls: Some(vec![TestContractRun {
+                result: ReturnCode::Revert,
+                ..Default::default()
+            }]),
+            ..Default::default()
+        },
+        ..Default::default()
+    },
+
+    create_transfers_value: Test {
+        roms: vec![vec![
+            Instruction::PUSH0.opcode(), // Size
+            Instruction::PUSH0.opcode(), // Offset
+            Instruction::PUSH1.opcode(), // Value
+            0x2A,
+            Instruction::CREATE.opcode(),
+        ]],
+        balances: vec![(test_address(0), stack_word(&[0x64]))],
+        expected: TestContractRun {
+            stack_ptr: 1,
+            stack: vec![address_to_word(&create_address(&test_address(0), 0))],
+            balances: vec![
+                (test_address(0), stack_word(&[0x3A])),
+                (create_address(&test_address(0), 0), stack_word(&[0x2A])),
+            ],
+            ..Default::default()
+        },
+        ..Default::default()
+    },
+
+    create_with_insufficient_balance_keeps_nonce: Test {
+        roms: vec![vec![
+            Instruction::PUSH0.opcode(), // Size
+            Instruction::PUSH0.opcode(), // Offset
+            Instruction::PUSH1.opcode(), // Value
+            0x01,
+            Instruction::CREATE.opcode(),
+            Instruction::PUSH0.opcode(), // Size
+            Instruction::PUSH0.opcode(), // Offset
+            Instruction::PUSH0.opcode(), // Value
+            Instruction::CREATE.opcode(),
+        ]],
+        expected: TestContractRun {
+            stack_ptr: 2,
+            stack: vec![
+                stack_word(&[]),
+                address_to_word(&create_address(&test_address(0), 0)),
+            ],
+            ..Default::default()
+        },
+        ..Default::default()
+    },
+
+    codesize_and_codecopy: Test {
+        roms: vec![vec![
+            Instruction::CODESIZE.opcode(),
+            Instruction::PUSH1.opcode(), // Size
+            0x20,
+            Instruction::PUSH1.opcode(), // Offset
+            0x01,
+            Instruction::PUSH0.opcode(), // Dest offset
+            Instruction::CODECOPY.opcode(),
+        ]],
+        expected: TestContractRun {
+            stack_ptr: 1,
+            stack: vec![stack_word(&[0x07])],
+            memory: Some(
+                [
+                    vec![
+                        Instruction::PUSH1.opcode(),
+                        0x20,
+                        Instruction::PUSH1.opcode(),
+                        0x01,
+                        Instruction::PUSH0.opcode(),
+                        Instruction::CODECOPY.opcode(),
+                    ],
+                    vec![0; 26],
+                ]
+                .concat(),
+            ),
+            ..Default::default()
+        },
+        ..Default::default()
+    },
+
+    extcodesize_and_extcodehash: Test {
+        roms: vec![vec![
+            Instruction::PUSH1.opcode(), // Address
+            0x01,
+            Instruction::EXTCODESIZE.opcode(),
+            Instruction::PUSH1.opcode(), // Address
+            0x01,
+            Instruction::EXTCODEHASH.opcode(),
+            Instruction::PUSH1.opcode(), // Address
+            0x99,
+            Instruction::EXTCODESIZE.opcode(),
+            Instruction::PUSH1.opcode(), // Address
+            0x99,
+            Instruction::EXTCODEHASH.opcode(),
+        ], vec![
+            Instruction::PUSH1.opcode(),
+            0x2A,
+            Instruction::POP.opcode(),
+        ]],
+        expected: TestContractRun {
+            stack_ptr: 4,
+            stack: vec![
+                stack_word(&[0x03]),
+                stack_word_be(&hash_code(&[
+                    Instruction::PUSH1.opcode(),
+                    0x2A,
+                    Instruction::POP.opcode(),
+                ])),
+                stack_word(&[]),
+                stack_word(&[]),
+            ],
+            ..Default::default()
+        },
+        ..Default::default()
+    },
+
+    extcodehash_of_account_without_code: Test {
+        roms: vec![vec![
+            Instruction::PUSH1.opcode(), // Address
+            0x01,
+            Instruction::EXTCODEHASH.opcode(),
+        ]],
+        balances: vec![(test_address(1), stack_word(&[0x01]))],
+        expected: TestContractRun {
+            stack_ptr: 1,
+            stack: vec![stack_word_be(&hash_code(&[]))],
+            ..Default::default()
+        },
+        ..Default::default()
+    },
+
+    extcodecopy_pads_with_zeros: Test {
+        roms: vec![vec![
+            Instruction::PUSH1.opcode(), // Size
+            0x08,
+            Instruction::PUSH1.opcode(), // Offset
+            0x01,
+            Instruction::PUSH0.opcode(), // Dest offset
+            Instruction::PUSH1.opcode(), // Address
+            0x01,
+            Instruction::EXTCODECOPY.opcode(),
+        ], vec![
+            Instruction::PUSH1.opcode(),
+            0x2A,
+            Instruction::POP.opcode(),
+        ]],
+        expected: TestContractRun {
+            memory: Some(vec![0x2A, Instruction::POP.opcode(), 0, 0, 0, 0, 0, 0]),
+            ..Default::default()
+        },
+        ..Default::default()
+    },
+
+    create_with_codecopy_constructor: Test {
+        roms: vec![vec![
+            Instruction::PUSH13.opcode(), // Init code
+            Instruction::PUSH1.opcode(), // Runtime code size
+            0x04,
+            Instruction::DUP1.opcode(),
+            Instruction::PUSH1.opcode(), // Runtime code offset
+            0x09,
+            Instruction::PUSH0.opcode(), // Dest offset
+            Instruction::CODECOPY.opcode(),
+            Instruction::PUSH0.opcode(), // Offset
+            Instruction::RETURN.opcode(),
+            Instruction::PUSH1.opcode(), // Runtime code
+            0x2A,
+            Instruction::PUSH0.opcode(),
+            Instruction::SSTORE.opcode(),
+            Instruction::PUSH0.opcode(), // Offset
+            Instruction::MSTORE.opcode(),
+            Instruction::PUSH1.opcode(), // Size
+            0x0D,
+            Instruction::PUSH1.opcode(), // Offset
+            0x13,
+            Instruction::PUSH0.opcode(), // Value
+            Instruction::CREATE.opcode(),
+            Instruction::DUP1.opcode(),
+            Instruction::EXTCODESIZE.opcode(),
+        ]],
+        expected: TestContractRun {
+            stack_ptr: 2,
+            stack: vec![
+                address_to_word(&create_address(&test_address(0), 0)),
+                stack_word(&[0x04]),
+            ],
+            ..Default::default()
+        },
+        ..Default::default()
+    },
+
+    gas_pushes_gas_left: Test {
+        roms: vec![vec![
+            Instruction::PUSH1.opcode(),
+            0x01,
+            Instruction::PUSH1.opcode(),
+            0x02,
+            Instruction::ADD.opcode(),
+            Instruction::GAS.opcode(),
+        ]],
+        gas_limit: Some(1000),
+        expected: TestContractRun {
+            stack_ptr: 2,
+            stack: vec![stack_word(&[0x03]), stack_word(&989u64.to_le_bytes())],
+            gas_used: Some(11),
+            ..Default::default()
+        },
+        ..Default::default()
+    },
+
+    out_of_gas_halts_before_running_the_block: Test {
+        roms: vec![vec![
+            Instruction::PUSH1.opcode(),
+            0x01,
+            Instruction::PUSH1.opcode(),
+            0x02,
+            Instruction::ADD.opcode(),
+        ]],
+        gas_limit: Some(5),
+        expected: TestContractRun {
+            result: ReturnCode::OutOfGas,
+            gas_used: Some(5),
+            ..Default::default()
+        },
+        ..Default::default()
+    },
+
+    out_of_gas_reverts_state_and_uses_all_gas: Test {
+        roms: vec![vec![
+            Instruction::PUSH1.opcode(), // Value
+            0x01,
+            Instruction::PUSH0.opcode(), // Key
+            Instruction::SSTORE.opcode(),
+            Instruction::JUMPDEST.opcode(),
+            Instruction::PUSH1.opcode(),
+            0x01,
+            Instruction::PUSH1.opcode(),
+            0x02,
+            Instruction::ADD.opcode(),
+        ]],
+        // Enough for the first block and the SSTORE, but not the second block
+        gas_limit: Some(22110),
+        expected: TestContractRun {
+            result: ReturnCode::OutOfGas,
+            storage: vec![(test_address(0), stack_word(&[]), stack_word(&[]))],
+            gas_used: Some(22110),
+            ..Default::default()
+        },
+        ..Default::default()
+    },
+
+    memory_expansion_is_charged: Test {
+        roms: vec![vec![
+            Instruction::PUSH1.opcode(), // Value
+            0x2A,
+            Instruction::PUSH2.opcode(), // Offset
+            0x03,
+            0xE0,
+            Instruction::MSTORE.opcode(), // Expands memory to 32 words
+            Instruction::GAS.opcode(),
+        ]],
+        gas_limit: Some(1000),
+        expected: TestContractRun {
+            stack_ptr: 1,
+            // 11 for the instructions and 98 for the memory
+            stack: vec![stack_word(&891u64.to_le_bytes())],
+            gas_used: Some(109),
+            ..Default::default()
+        },
+        ..Default::default()
+    },
+
+    keccak256_is_charged_per_word: Test {
+        roms: vec![vec![
+            Instruction::PUSH1.opcode(), // Size
+            0x21,
+            Instruction::PUSH0.opcode(), // Offset
+            Instruction::KECCAK256.opcode(),
+            Instruction::POP.opcode(),
+            Instruction::GAS.opcode(),
+        ]],
+        gas_limit: Some(1000),
+        expected: TestContractRun {
+            stack_ptr: 1,
+            // 39 for the instructions, 6 for the memory and 12 for hashing
+            stack: vec![stack_word(&943u64.to_le_bytes())],
+            ..Default::default()
+        },
+        ..Default::default()
+    },
+
+    calldatacopy_is_charged_per_word: Test {
+        roms: vec![vec![
+            Instruction::PUSH1.opcode(), // Size
+            0x40,
+            Instruction::PUSH0.opcode(), // Offset
+            Instruction::PUSH0.opcode(), // Dest offset
+            Instruction::CALLDATACOPY.opcode(),
+            Instruction::GAS.opcode(),
+        ]],
+        gas_limit: Some(1000),
+        expected: TestContractRun {
+            stack_ptr: 1,
+            // 12 for the instructions, 6 for the memory and 6 for copying
+            stack: vec![stack_word(&976u64.to_le_bytes())],
+            ..Default::default()
+        },
+        ..Default::default()
+    },
+
+    log_data_is_charged_per_byte: Test {
+        roms: vec![vec![
+            Instruction::PUSH1.opcode(), // Size
+            0x0A,
+            Instruction::PUSH0.opcode(), // Offset
+            Instruction::LOG0.opcode(),
+            Instruction::GAS.opcode(),
+        ]],
+        gas_limit: Some(1000),
+        expected: TestContractRun {
+            stack_ptr: 1,
+            // 382 for the instructions, 3 for the memory and 80 for the data
+            stack: vec![stack_word(&535u64.to_le_bytes())],
+            ..Default::default()
+        },
+        ..Default::default()
+    },
+
+    sstore_is_charged_by_previous_value: Test {
+        roms: vec![vec![
+            Instruction::PUSH1.opcode(), // Value
+            0x01,
+            Instruction::PUSH0.opcode(), // Key
+            Instruction::SSTORE.opcode(), // Zero to non-zero, cold
+            Instruction::PUSH1.opcode(), // Value
+            0x02,
+            Instruction::PUSH0.opcode(), // Key
+            Instruction::SSTORE.opcode(), // Non-zero to non-zero, warm and already changed
+            Instruction::GAS.opcode(),
+        ]],
+        gas_limit: Some(100_000),
+        expected: TestContractRun {
+            stack_ptr: 1,
+            // 12 for the instructions, 22100 for the first SSTORE and 100 for the second
+            stack: vec![stack_word(&77788u64.to_le_bytes())],
+            gas_used: Some(22212),
+            gas_refund: Some(0),
+            ..Default::default()
+        },
+        ..Default::default()
+    },
+
+    call_passes_requested_gas: Test {
+        roms: vec![vec![
+            Instruction::PUSH0.opcode(), // Output len
+            Instruction::PUSH0.opcode(), // Output offset
+            Instruction::PUSH0.opcode(), // Input len
+            Instruction::PUSH0.opcode(), // Input offset
+            Instruction::PUSH0.opcode(), // Value
+            Instruction::PUSH1.opcode(), // Address
+            0x01,
+            Instruction::PUSH1.opcode(), // Gas
+            0x64,
+            Instruction::CALL.opcode(),
+            Instruction::GAS.opcode(),
+        ], vec![
+            Instruction::GAS.opcode(),
+        ]],
+        gas_limit: Some(10000),
+        expected: TestContractRun {
+            stack_ptr: 2,
+            // 2616 up to the call, 2 used by the callee and 2 for GAS
+            stack: vec![stack_word(&[0x01]), stack_word(&7380u64.to_le_bytes())],
+            gas_used: Some(2620),
+            sub_calls: Some(vec![TestContractRun {
+                stack_ptr: 1,
+                stack: vec![stack_word(&[0x62])],
+                gas_used: Some(2),
+                ..Default::default()
+            }]),
+            ..Default::default()
+        },
+        ..Default::default()
+    },
+
+    call_forwards_all_but_one_64th: Test {
+        roms: vec![vec![
+            Instruction::PUSH0.opcode(), // Output len
+            Instruction::PUSH0.opcode(), // Output offset
+            Instruction::PUSH0.opcode(), // Input len
+            Instruction::PUSH0.opcode(), // Input offset
+            Instruction::PUSH0.opcode(), // Value
+            Instruction::PUSH1.opcode(), // Address
+            0x01,
+            Instruction::GAS.opcode(), // Gas
+            Instruction::CALL.opcode(),
+        ], vec![
+            Instruction::GAS.opcode(),
+        ]],
+        // 64000 is left once the call is paid for, and the callee gets 63000 of it
+        gas_limit: Some(66615),
+        expected: TestContractRun {
+            stack_ptr: 1,
+            stack: vec![stack_word(&[0x01])],
+            gas_used: Some(2617),
+            sub_calls: Some(vec![TestContractRun {
+                stack_ptr: 1,
+                stack: vec![stack_word(&62998u64.to_le_bytes())],
+                gas_used: Some(2),
+                ..Default::default()
+            }]),
+            ..Default::default()
+        },
+        ..Default::default()
+    },
+
+    callee_out_of_gas_uses_forwarded_gas: Test {
+        roms: vec![vec![
+            Instruction::PUSH0.opcode(), // Output len
+            Instruction::PUSH0.opcode(), // Output offset
+            Instruction::PUSH0.opcode(), // Input len
+            Instruction::PUSH0.opcode(), // Input offset
+            Instruction::PUSH0.opcode(), // Value
+            Instruction::PUSH1.opcode(), // Address
+            0x01,
+            Instruction::PUSH1.opcode(), // Gas
+            0x05,
+            Instruction::CALL.opcode(),
+        ], vec![
+            Instruction::PUSH1.opcode(),
+            0x01,
+            Instruction::PUSH1.opcode(),
+            0x02,
+            Instruction::ADD.opcode(),
+        ]],
+        gas_limit: Some(10000),
+        expected: TestContractRun {
+            stack_ptr: 1,
+            stack: vec![stack_word(&[0x00])],
+            gas_used: Some(2621),
+            sub_calls: Some(vec![TestContractRun {
+                result: ReturnCode::OutOfGas,
+                gas_used: Some(5),
+                ..Default::default()
+            }]),
+            ..Default::default()
+        },
+        ..Default::default()
+    },
+
+    call_with_value_gets_stipend: Test {
+        roms: vec![vec![
+            Instruction::PUSH0.opcode(), // Output len
+            Instruction::PUSH0.opcode(), // Output offset
+            Instruction::PUSH0.opcode(), // Input len
+            Instruction::PUSH0.opcode(), // Input offset
+            Instruction::PUSH1.opcode(), // Value
+            0x01,
+            Instruction::PUSH1.opcode(), // Address
+            0x01,
+            Instruction::PUSH0.opcode(), // Gas
+            Instruction::CALL.opcode(),
+        ], vec![
+            Instruction::GAS.opcode(),
+        ]],
+        balances: vec![(test_address(0), stack_word(&[0x01]))],
+        expected: TestContractRun {
+            stack_ptr: 1,
+            stack: vec![stack_word(&[0x01])],
+            // 2616 up to the call, 9000 for the transfer and 2 used by the callee out of its stipend
+            gas_used: Some(11618),
+            balances: vec![(test_address(1), stack_word(&[0x01]))],
+            sub_calls: Some(vec![TestContractRun {
+                stack_ptr: 1,
+                stack: vec![stack_word(&2298u64.to_le_bytes())],
+                ..Default::default()
+            }]),
+            ..Default::default()
+        },
+        ..Default::default()
+    },
+
+    call_with_value_to_new_account_is_charged_for_it: Test {
+        roms: vec![vec![
+            Instruction::PUSH0.opcode(), // Output len
+            Instruction::PUSH0.opcode(), // Output offset
+            Instruction::PUSH0.opcode(), // Input len
+            Instruction::PUSH0.opcode(), // Input offset
+            Instruction::PUSH1.opcode(), // Value
+            0x01,
+            Instruction::PUSH1.opcode(), // Address
+            0x05,
+            Instruction::PUSH0.opcode(), // Gas
+            Instruction::CALL.opcode(),
+        ]],
+        balances: vec![(test_address(0), stack_word(&[0x01]))],
+        expected: TestContractRun {
+            stack_ptr: 1,
+            stack: vec![stack_word(&[0x01])],
+            gas_used: Some(36616),
+            balances: vec![(test_address(5), stack_word(&[0x01]))],
+            ..Default::default()
+        },
+        ..Default::default()
+    },
+    sload_is_charged_more_for_cold_slots: Test {
+        roms: vec![vec![
+            Instruction::PUSH0.opcode(), // Key
+            Instruction::SLOAD.opcode(), // Cold
+            Instruction::POP.opcode(),
+            Instruction::PUSH0.opcode(), // Key
+            Instruction::SLOAD.opcode(), // Warm
+            Instruction::POP.opcode(),
+            Instruction::GAS.opcode(),
+        ]],
+        gas_limit: Some(10000),
+        expected: TestContractRun {
+            stack_ptr: 1,
+            // 210 for the instructions and 2000 for the first access
+            stack: vec![stack_word(&7790u64.to_le_bytes())],
+            gas_used: Some(2210),
+            ..Default::default()
+        },
+        ..Default::default()
+    },
+
+    balance_is_charged_more_for_cold_accounts: Test {
+        roms: vec![vec![
+            Instruction::PUSH1.opcode(), // Address
+            0x05,
+            Instruction::BALANCE.opcode(), // Cold
+            Instruction::POP.opcode(),
+            Instruction::PUSH1.opcode(), // Address
+            0x05,
+            Instruction::BALANCE.opcode(), // Warm
+            Instruction::POP.opcode(),
+            Instruction::ADDRESS.opcode(),
+            Instruction::BALANCE.opcode(), // The contract itself is always warm
+            Instruction::POP.opcode(),
+            Instruction::GAS.opcode(),
+        ]],
+        gas_limit: Some(10000),
+        expected: TestContractRun {
+            stack_ptr: 1,
+            // 316 for the instructions and 2500 for the first access
+            stack: vec![stack_word(&7184u64.to_le_bytes())],
+            gas_used: Some(2816),
+            ..Default::default()
+        },
+        ..Default::default()
+    },
+
+    access_list_warms_accounts_and_slots: Test {
+        roms: vec![vec![
+            Instruction::PUSH1.opcode(), // Address
+            0x05,
+            Instruction::BALANCE.opcode(),
+            Instruction::POP.opcode(),
+            Instruction::PUSH1.opcode(), // Key
+            0x01,
+            Instruction::SLOAD.opcode(),
+            Instruction::POP.opcode(),
+            Instruction::GAS.opcode(),
+        ]],
+        gas_limit: Some(10000),
+        access_list: vec![
+            AccessListItem::new(test_address(5), vec![]),
+            AccessListItem::new(test_address(0), vec![stack_word(&[0x01])]),
+        ],
+        expected: TestContractRun {
+            stack_ptr: 1,
+            stack: vec![stack_word(&9788u64.to_le_bytes())],
+            gas_used: Some(212),
+            ..Default::default()
+        },
+        ..Default::default()
+    },
+
+    accesses_are_reverted_with_the_frame: Test {
+        roms: vec![vec![
+            Instruction::PUSH0.opcode(), // Output len
+            Instruction::PUSH0.opcode(), // Output offset
+            Instruction::PUSH0.opcode(), // Input len
+            Instruction::PUSH0.opcode(), // Input offset
+            Instruction::PUSH0.opcode(), // Value
+            Instruction::PUSH1.opcode(), // Address
+            0x01,
+            Instruction::PUSH2.opcode(), // Gas
+            0x0B,
+            0xB8,
+            Instruction::CALL.opcode(),
+            Instruction::PUSH1.opcode(), // Address
+            0x05,
+            Instruction::BALANCE.opcode(), // Cold again, since the callee failed
+            Instruction::POP.opcode(),
+            Instruction::GAS.opcode(),
+        ], vec![
+            Instruction::PUSH1.opcode(), // Address
+            0x05,
+            Instruction::BALANCE.opcode(),
+            Instruction::INVALID.opcode(),
+        ]],
+        gas_limit: Some(100_000),
+        expected: TestContractRun {
+            stack_ptr: 2,
+            // 2616 for the call, 3000 used by the callee and 2607 for the rest
+            stack: vec![stack_word(&[0x00]), stack_word(&91777u64.to_le_bytes())],
+            gas_used: Some(8223),
+            sub_calls: Some(vec![TestContractRun {
+                result: ReturnCode::Invalid,
+                stack_ptr: 1,
+                stack: vec![stack_word(&[])],
+                gas_used: Some(3000),
+                ..Default::default()
+            }]),
+            ..Default::default()
+        },
+        ..Default::default()
+    },
+
+    sstore_clearing_a_slot_is_refunded: Test {
+        roms: vec![vec![
+            Instruction::PUSH0.opcode(), // Value
+            Instruction::PUSH0.opcode(), // Key
+            Instruction::SSTORE.opcode(),
+        ]],
+        storage: vec![(test_address(0), stack_word(&[]), stack_word(&[0x01]))],
+        gas_limit: Some(10000),
+        expected: TestContractRun {
+            storage: vec![(test_address(0), stack_word(&[]), stack_word(&[]))],
+            // 4 for the instructions, 2100 for the cold slot and 2900 for the change
+            gas_used: Some(5004),
+            gas_refund: Some(4800),
+            ..Default::default()
+        },
+        ..Default::default()
+    },
+
+    sstore_restoring_the_original_value_is_refunded: Test {
+        roms: vec![vec![
+            Instruction::PUSH1.opcode(), // Value
+            0x01,
+            Instruction::PUSH0.opcode(), // Key
+            Instruction::SSTORE.opcode(),
+            Instruction::PUSH0.opcode(), // Value
+            Instruction::PUSH0.opcode(), // Key
+            Instruction::SSTORE.opcode(),
+        ]],
+        gas_limit: Some(100_000),
+        expected: TestContractRun {
+            gas_used: Some(22209),
+            gas_refund: Some(19900),
+            ..Default::default()
+        },
+        ..Default::default()
+    },
+
+    sstore_undoing_a_clear_takes_back_its_refund: Test {
+        roms: vec![vec![
+            Instruction::PUSH0.opcode(), // Value
+            Instruction::PUSH0.opcode(), // Key
+            Instruction::SSTORE.opcode(),
+            Instruction::PUSH1.opcode(), // Value
+            0x02,
+            Instruction::PUSH0.opcode(), // Key
+            Instruction::SSTORE.opcode(),
+        ]],
+        storage: vec![(test_address(0), stack_word(&[]), stack_word(&[0x01]))],
+        gas_limit: Some(100_000),
+        expected: TestContractRun {
+            storage: vec![(test_address(0), stack_word(&[]), stack_word(&[0x02]))],
+            gas_used: Some(5109),
+            gas_refund: Some(0),
+            ..Default::default()
+        },
+        ..Default::default()
+    },
+
+    sstore_fails_without_more_than_the_stipend: Test {
+        roms: vec![vec![
+            Instruction::PUSH1.opcode(), // Value
+            0x01,
+            Instruction::PUSH0.opcode(), // Key
+            Instruction::SSTORE.opcode(),
+        ]],
+        gas_limit: Some(2305),
+        expected: TestContractRun {
+            result: ReturnCode::OutOfGas,
+            storage: vec![(test_address(0), stack_word(&[]), stack_word(&[]))],
+            gas_used: Some(2305),
+            ..Default::default()
+        },
+        ..Default::default()
+    },
+
+    refunds_are_reverted_with_the_frame: Test {
+        roms: vec![vec![
+            Instruction::PUSH0.opcode(), // Output len
+            Instruction::PUSH0.opcode(), // Output offset
+            Instruction::PUSH0.opcode(), // Input len
+            Instruction::PUSH0.opcode(), // Input offset
+            Instruction::PUSH0.opcode(), // Value
+            Instruction::PUSH1.opcode(), // Address
+            0x01,
+            Instruction::GAS.opcode(), // Gas
+            Instruction::CALL.opcode(),
+        ], vec![
+            Instruction::PUSH0.opcode(), // Value
+            Instruction::PUSH0.opcode(), // Key
+            Instruction::SSTORE.opcode(),
+            Instruction::PUSH0.opcode(), // Size
+            Instruction::PUSH0.opcode(), // Offset
+            Instruction::REVERT.opcode(),
+        ]],
+        storage: vec![(test_address(1), stack_word(&[]), stack_word(&[0x01]))],
+        expected: TestContractRun {
+            stack_ptr: 1,
+            stack: vec![stack_word(&[0x00])],
+            storage: vec![(test_address(1), stack_word(&[]), stack_word(&[0x01]))],
+            gas_refund: Some(0),
+            sub_calls: Some(vec![TestContractRun {
+                result: ReturnCode::Revert,
+                ..Default::default()
+            }]),
+            ..Default::default()
+        },
+        ..Default::default()
+    },
+
+    push0_is_invalid_before_shanghai: Test {
+        roms: vec![vec![
+            Instruction::PUSH0.opcode(),
+        ]],
+        spec_id: SpecId::Paris,
+        gas_limit: Some(1000),
+        expected: TestContractRun {
+            result: ReturnCode::Invalid,
+            gas_used: Some(1000),
+            ..Default::default()
+        },
+        ..Default::default()
+    },
+
+    tload_is_invalid_before_cancun: Test {
+        roms: vec![vec![
+            Instruction::PUSH0.opcode(), // Key
+            Instruction::TLOAD.opcode(),
+        ]],
+        spec_id: SpecId::Shanghai,
+        gas_limit: Some(1000),
+        expected: TestContractRun {
+            result: ReturnCode::Invalid,
+            stack_ptr: 1,
+            stack: vec![stack_word(&[])],
+            gas_used: Some(1000),
+            ..Default::default()
+        },
+        ..Default::default()
+    },
+
+    mcopy_copies_memory_in_cancun: Test {
+        roms: vec![vec![
+            Instruction::PUSH1.opcode(), // Value
+            0x2a,
+            Instruction::PUSH0.opcode(), // Offset
+            Instruction::MSTORE.opcode(),
+            Instruction::PUSH1.opcode(), // Size
+            0x20,
+            Instruction::PUSH0.opcode(), // Offset
+            Instruction::PUSH1.opcode(), // Dest offset
+            0x20,
+            Instruction::MCOPY.opcode(), // Expands memory to 2 words
+            Instruction::MSIZE.opcode(),
+        ]],
+        spec_id: SpecId::Cancun,
+        expected: TestContractRun {
+            stack_ptr: 1,
+            stack: vec![stack_word(&[0x40])],
+            memory: Some([vec![0; 31], vec![0x2a], vec![0; 31], vec![0x2a]].concat()),
+            gas_used: Some(30),
+            ..Default::default()
+        },
+        ..Default::default()
+    },
+
+    mcopy_copies_overlapping_ranges: Test {
+        roms: vec![vec![
+            Instruction::PUSH1.opcode(), // Value
+            0x2a,
+            Instruction::PUSH0.opcode(), // Offset
+            Instruction::MSTORE.opcode(),
+            Instruction::PUSH1.opcode(), // Size
+            0x1f,
+            Instruction::PUSH1.opcode(), // Offset
+            0x01,
+            Instruction::PUSH0.opcode(), // Dest offset
+            Instruction::MCOPY.opcode(),
+        ]],
+        expected: TestContractRun {
+            memory: Some([vec![0; 30], vec![0x2a, 0x2a]].concat()),
+            ..Default::default()
+        },
+        ..Default::default()
+    },
+
+    mcopy_is_invalid_before_cancun: Test {
+        roms: vec![vec![
+            Instruction::PUSH0.opcode(), // Size
+            Instruction::PUSH0.opcode(), // Offset
+            Instruction::PUSH0.opcode(), // Dest offset
+            Instruction::MCOPY.opcode(),
+        ]],
+        spec_id: SpecId::Shanghai,
+        gas_limit: Some(1000),
+        expected: TestContractRun {
+            result: ReturnCode::Invalid,
+            stack_ptr: 3,
+            stack: vec![stack_word(&[]), stack_word(&[]), stack_word(&[])],
+            gas_used: Some(1000),
+            ..Default::default()
+        },
+        ..Default::default()
+    },
+
+    blobhash_is_invalid_until_implemented: Test {
+        roms: vec![vec![
+            Instruction::PUSH0.opcode(), // Index
+            Instruction::BLOBHASH.opcode(),
+        ]],
+        spec_id: SpecId::Cancun,
+        gas_limit: Some(1000),
+        expected: TestContractRun {
+            result: ReturnCode::Invalid,
+            stack_ptr: 1,
+            stack: vec![stack_word(&[])],
+            gas_used: Some(1000),
+            ..Default::default()
+        },
+        ..Default::default()
+    },
+
+    sload_costs_800_in_istanbul: Test {
+        roms: vec![vec![
+            Instruction::PUSH1.opcode(), // Key
+            0x00,
+            Instruction::SLOAD.opcode(),
+            Instruction::POP.opcode(),
+            Instruction::GAS.opcode(),
+        ]],
+        spec_id: SpecId::Istanbul,
+        gas_limit: Some(10000),
+        expected: TestContractRun {
+            stack_ptr: 1,
+            stack: vec![stack_word(&9193u64.to_le_bytes())],
+            ..Default::default()
+        },
+        ..Default::default()
+    },
+
+    sload_costs_50_in_frontier: Test {
+        roms: vec![vec![
+            Instruction::PUSH1.opcode(), // Key
+            0x00,
+            Instruction::SLOAD.opcode(),
+            Instruction::POP.opcode(),
+            Instruction::GAS.opcode(),
+        ]],
+        spec_id: SpecId::Frontier,
+        gas_limit: Some(10000),
+        expected: TestContractRun {
+            stack_ptr: 1,
+            stack: vec![stack_word(&9943u64.to_le_bytes())],
+            ..Default::default()
+        },
+        ..Default::default()
+    },
+
+    coinbase_is_cold_before_shanghai: Test {
+        roms: vec![vec![
+            Instruction::COINBASE.opcode(),
+            Instruction::BALANCE.opcode(),
+            Instruction::POP.opcode(),
+            Instruction::GAS.opcode(),
+        ]],
+        spec_id: SpecId::Paris,
+        gas_limit: Some(10000),
+        expected: TestContractRun {
+            stack_ptr: 1,
+            // 106 for the instructions and 2500 for the first access
+            stack: vec![stack_word(&7394u64.to_le_bytes())],
+            ..Default::default()
+        },
+        ..Default::default()
+    },
+
+    sstore_is_not_net_metered_in_petersburg: Test {
+        roms: vec![vec![
+            Instruction::PUSH1.opcode(), // Value
+            0x01,
+            Instruction::PUSH1.opcode(), // Key
+            0x00,
+            Instruction::SSTORE.opcode(),
+            Instruction::PUSH1.opcode(), // Value
+            0x02,
+            Instruction::PUSH1.opcode(), // Key
+            0x00,
+            Instruction::SSTORE.opcode(),
+        ]],
+        spec_id: SpecId::Petersburg,
+        gas_limit: Some(100_000),
+        expected: TestContractRun {
+            gas_used: Some(25012),
+            gas_refund: Some(0),
+            ..Default::default()
+        },
+        ..Default::default()
+    },
+
+    sstore_is_net_metered_in_istanbul: Test {
+        roms: vec![vec![
+            Instruction::PUSH1.opcode(), // Value
+            0x01,
+            Instruction::PUSH1.opcode(), // Key
+            0x00,
+            Instruction::SSTORE.opcode(),
+            Instruction::PUSH1.opcode(), // Value
+            0x02,
+            Instruction::PUSH1.opcode(), // Key
+            0x00,
+            Instruction::SSTORE.opcode(),
+        ]],
+        spec_id: SpecId::Istanbul,
+        gas_limit: Some(100_000),
+        expected: TestContractRun {
+            gas_used: Some(20812),
+            gas_refund: Some(0),
+            ..Default::default()
+        },
+        ..Default::default()
+    },
+
+    sstore_clear_refund_is_larger_before_london: Test {
+        roms: vec![vec![
+            Instruction::PUSH1.opcode(), // Value
+            0x00,
+            Instruction::PUSH1.opcode(), // Key
+            0x00,
+            Instruction::SSTORE.opcode(),
+        ]],
+        storage: vec![(test_address(0), stack_word(&[]), stack_word(&[0x01]))],
+        spec_id: SpecId::Berlin,
+        gas_limit: Some(10000),
+        expected: TestContractRun {
+            gas_used: Some(5006),
+            gas_refund: Some(15000),
+            ..Default::default()
+        },
+        ..Default::default()
+    },
+
+    call_before_tangerine_whistle_needs_all_the_requested_gas: Test {
+        roms: vec![vec![
+            Instruction::PUSH1.opcode(), // Output len
+            0x00,
+            Instruction::PUSH1.opcode(), // Output offset
+            0x00,
+            Instruction::PUSH1.opcode(), // Input len
+            0x00,
+            Instruction::PUSH1.opcode(), // Input offset
+            0x00,
+            Instruction::PUSH1.opcode(), // Value
+            0x00,
+            Instruction::PUSH1.opcode(), // Address
+            0x01,
+            Instruction::PUSH2.opcode(), // Gas
+            0xFF,
+            0xFF,
+            Instruction::CALL.opcode(),
+        ], vec![
+            Instruction::STOP.opcode(),
+        ]],
+        spec_id: SpecId::Homestead,
+        gas_limit: Some(10000),
+        expected: TestContractRun {
+            result: ReturnCode::OutOfGas,
+            gas_used: Some(10000),
+            sub_calls: Some(vec![]),
+            ..Default::default()
+        },
+        ..Default::default()
+    },
+
+    exp_of_pushed_operands: Test {
+        roms: vec![vec![
+            Instruction::PUSH1.opcode(), // Exponent
+            0x03,
+            Instruction::PUSH1.opcode(), // Base
+            0x02,
+            Instruction::EXP.opcode(),
+        ]],
+        expected: TestContractRun {
+            stack_ptr: 1,
+            stack: vec![stack_word(&[0x08])],
+            gas_used: Some(66),
+            ..Default::default()
+        },
+        ..Default::default()
+    },
+
+    exp_of_computed_operands: Test {
+        roms: vec![vec![
+            Instruction::PUSH1.opcode(), // Exponent
+            0x03,
+            Instruction::PUSH1.opcode(), // Base
+            0x02,
+            Instruction::PUSH0.opcode(),
+            Instruction::ADD.opcode(),
+            Instruction::EXP.opcode(),
+        ]],
+        expected: TestContractRun {
+            stack_ptr: 1,
+            stack: vec![stack_word(&[0x08])],
+            gas_used: Some(71),
+            ..Default::default()
+        },
+        ..Default::default()
+    },
+
+    exp_charges_per_exponent_byte: Test {
+        roms: vec![vec![
+            Instruction::PUSH2.opcode(), // Exponent
+            0x01,
+            0x00,
+            Instruction::PUSH1.opcode(), // Base
+            0x02,
+            Instruction::PUSH0.opcode(),
+            Instruction::ADD.opcode(),
+            Instruction::EXP.opcode(),
+        ]],
+        expected: TestContractRun {
+            stack_ptr: 1,
+            stack: vec![stack_word(&[])],
+            gas_used: Some(121),
+            ..Default::default()
+        },
+        ..Default::default()
+    },
+
+    exp_by_zero_is_one: Test {
+        roms: vec![vec![
+            Instruction::PUSH0.opcode(), // Exponent
+            Instruction::PUSH1.opcode(), // Base
+            0x07,
+            Instruction::EXP.opcode(),
+        ]],
+        expected: TestContractRun {
+            stack_ptr: 1,
+            stack: vec![stack_word(&[0x01])],
+            gas_used: Some(15),
+            ..Default::default()
+        },
+        ..Default::default()
+    },
+
+    exp_keeps_the_high_bit: Test {
+        roms: vec![vec![
+            Instruction::PUSH1.opcode(), // Exponent
+            0xff,
+            Instruction::PUSH1.opcode(), // Base
+            0x02,
+            Instruction::EXP.opcode(),
+        ]],
+        expected: TestContractRun {
+            stack_ptr: 1,
+            stack: vec![{
+                let mut word = [0; 32];
+                word[31] = 0x80;
+                word
+            }],
+            gas_used: Some(66),
+            ..Default::default()
+        },
+        ..Default::default()
+    },
+
+    exp_of_the_largest_word_wraps_around: Test {
+        roms: vec![[
+            vec![
+                Instruction::PUSH1.opcode(), // Exponent
+                0x03,
+                Instruction::PUSH32.opcode(), // Base
+            ],
+            vec![0xff; 32],
+            vec![
+                Instruction::PUSH0.opcode(),
+                Instruction::ADD.opcode(),
+                Instruction::EXP.opcode(),
+            ],
+        ].concat()],
+        expected: TestContractRun {
+            stack_ptr: 1,
+            stack: vec![[0xff; 32]],
+            gas_used: Some(71),
+            ..Default::default()
+        },
+        ..Default::default()
+    },
+
+    exp_bytes_cost_less_before_spurious_dragon: Test {
+        roms: vec![vec![
+            Instruction::PUSH1.opcode(), // Exponent
+            0x03,
+            Instruction::PUSH1.opcode(), // Base
+            0x02,
+            Instruction::PUSH1.opcode(),
+            0x00,
+            Instruction::ADD.opcode(),
+            Instruction::EXP.opcode(),
+        ]],
+        spec_id: SpecId::Homestead,
+        expected: TestContractRun {
+            stack_ptr: 1,
+            stack: vec![stack_word(&[0x08])],
+            gas_used: Some(32),
+            ..Default::default()
+        },
+        ..Default::default()
+    },
+
+    create_deploys_code_with_unassigned_bytes: Test {
+        roms: vec![[
+            vec![Instruction::PUSH19.opcode()], // Init code
+            vec![
+                Instruction::PUSH1.opcode(), // Size
+                0x09,
+                Instruction::PUSH1.opcode(), // Offset
+                0x0a,
+                Instruction::PUSH0.opcode(), // Dest offset
+                Instruction::CODECOPY.opcode(),
+                Instruction::PUSH1.opcode(), // Size
+                0x09,
+                Instruction::PUSH0.opcode(), // Offset
+                Instruction::RETURN.opcode(),
+                // Runtime code, ending with unassigned bytes and a PUSH2 cut off by the end
+                Instruction::PUSH1.opcode(),
+                0x2a,
+                Instruction::PUSH0.opcode(),
+                Instruction::SSTORE.opcode(),
+                Instruction::STOP.opcode(),
+                0x0c,
+                0x22,
+                Instruction::PUSH2.opcode(),
+                0x01,
+            ],
+            vec![
+                Instruction::PUSH0.opcode(), // Offset
+                Instruction::MSTORE.opcode(),
+                Instruction::PUSH1.opcode(), // Size
+                0x13,
+                Instruction::PUSH1.opcode(), // Offset
+                0x0d,
+                Instruction::PUSH0.opcode(), // Value
+                Instruction::CREATE.opcode(),
+                Instruction::PUSH0.opcode(), // Output len
+                Instruction::PUSH0.opcode(), // Output offset
+                Instruction::PUSH0.opcode(), // Input len
+                Instruction::PUSH0.opcode(), // Input offset
+                Instruction::PUSH0.opcode(), // Value
+                Instruction::DUP6.opcode(), // Address
+                Instruction::GAS.opcode(), // Gas
+                Instruction::CALL.opcode(),
+            ],
+        ].concat()],
+        expected: TestContractRun {
+            stack_ptr: 2,
+            stack: vec![
+                address_to_word(&create_address(&test_address(0), 0)),
+                stack_word(&[0x01]),
+            ],
+            storage: vec![
+                (create_address(&test_address(0), 0), stack_word(&[]), stack_word(&[0x2a])),
+            ],
+            ..Default::default()
+        },
+        ..Default::default()
+    },
+
+    unassigned_byte_runs_as_invalid: Test {
+        roms: vec![vec![
+            Instruction::PUSH1.opcode(),
+            0x01,
+            0x0c,
+            Instruction::PUSH1.opcode(),
+            0x02,
+        ]],
+        gas_limit: Some(1000),
+        expected: TestContractRun {
+            result: ReturnCode::Invalid,
+            stack_ptr: 1,
+            stack: vec![stack_word(&[0x01])],
+            gas_used: Some(1000),
+            ..Default::default()
+        },
+        ..Default::default()
+    },
+
+    truncated_push_is_padded_with_zeros: Test {
+        roms: vec![vec![
+            Instruction::PUSH3.opcode(),
+            0x12,
+            0x34,
+        ]],
+        expected: TestContractRun {
+            stack_ptr: 1,
+            stack: vec![stack_word_be(&[0x12, 0x34, 0x00])],
+            ..Default::default()
+        },
+        ..Default::default()
+    },
+}
+
+/// Creates a contract from init code made of the given number of zero bytes, which stops right
+/// away.
+fn create_with_zero_init_code(len: usize) -> Vec<u8> {
+    let len = (len as u16).to_be_bytes();
+    vec![
+        Instruction::PUSH2.opcode(), // Size
+        len[0],
+        len[1],
+        Instruction::PUSH1.opcode(), // Offset
+        0x00,
+        Instruction::PUSH1.opcode(), // Value
+        0x00,
+        Instruction::CREATE.opcode(),
+    ]
+}
+
+/// Creates a contract whose init code returns the given number of zero bytes as its code.
+fn create_with_zero_code(len: usize) -> Vec<u8> {
+    let len = (len as u16).to_be_bytes();
+    vec![
+        Instruction::PUSH6.opcode(), // Init code
+        Instruction::PUSH2.opcode(), // Size
+        len[0],
+        len[1],
+        Instruction::PUSH1.opcode(), // Offset
+        0x00,
+        Instruction::RETURN.opcode(),
+        Instruction::PUSH1.opcode(), // Offset
+        0x00,
+        Instruction::MSTORE.opcode(),
+        Instruction::PUSH1.opcode(), // Size
+        0x06,
+        Instruction::PUSH1.opcode(), // Offset
+        0x1a,
+        Instruction::PUSH1.opcode(), // Value
+        0x00,
+        Instruction::CREATE.opcode(),
+    ]
+}
+
+/// Init code that stores 0x07 at key 0x01 and deploys code that stores 0x2A at key 0x00.
+fn store_init_code() -> Vec<u8> {
+    vec![
+        Instruction::PUSH1.opcode(), // Value
+        0x07,
+        Instruction::PUSH1.opcode(), // Key
+        0x01,
+        Instruction::SSTORE.opcode(),
+        Instruction::PUSH4.opcode(), // Runtime code
+        Instruction::PUSH1.opcode(),
+        0x2a,
+        Instruction::PUSH0.opcode(),
+        Instruction::SSTORE.opcode(),
+        Instruction::PUSH0.opcode(), // Offset
+        Instruction::MSTORE.opcode(),
+        Instruction::PUSH1.opcode(), // Size
+        0x04,
+        Instruction::PUSH1.opcode(), // Offset
+        0x1c,
+        Instruction::RETURN.opcode(),
+    ]
+}
+
+#[test]
+fn deploy_builds_returned_code() -> Result<(), Error> {
+    let llvm_ctx = Context::create();
+    let opts = Options::new(Debug, false, false, true, SpecId::default());
+    let engine = Engine::new(&llvm_ctx, opts)?;
+    let block_info = new_test_block_info();
+
+    let tx_info = new_test_tx_info();
+    let storage = Box::new(InMemoryStorage::new());
+    let (address, run) = engine.deploy(
+        &tx_info,
+        3,
+        &store_init_code(),
+        &block_info,
+        TEST_GAS_LIMIT,
+        &[],
+        storage,
+    )?;
+    assert_eq!(address, create_address(&test_sender(), 3));
+    assert_eq!(run.result(), ReturnCode::ExplicitReturn);
+    assert_eq!(
+        run.storage().load(&address, &stack_word(&[0x01])),
+        stack_word(&[0x07])
+    );
+
+    let storage = Box::new(InMemoryStorage::new());
+    let tx_info = tx_info.with_address(address);
+    let run = engine.run_contract(&tx_info, &block_info, &[], TEST_GAS_LIMIT, &[], storage)?;
+    assert_eq!(run.result(), ReturnCode::ImplicitReturn);
+    assert_eq!(
+        run.storage().load(&address, &stack_word(&[])),
+        stack_word(&[0x2a])
+    );
+    Ok(())
+}
+
+#[test]
+fn deploy_charges_for_returned_code() -> Result<(), Error> {
+    let llvm_ctx = Context::create();
+    let opts = Options::new(Debug, false, false, true, SpecId::default());
+    let engine = Engine::new(&llvm_ctx, opts)?;
+    let block_info = new_test_block_info();
+    let tx_info = new_test_tx_info();
+
+    // Returning four bytes costs 8 gas, and deploying them costs another 800
+    let init_code = [
+        Instruction::PUSH1.opcode(), // Size
+        0x04,
+        Instruction::PUSH0.opcode(), // Offset
+        Instruction::RETURN.opcode(),
+    ];
+
+    let storage = Box::new(InMemoryStorage::new());
+    let (address, run) = engine.deploy(&tx_info, 0, &init_code, &block_info, 807, &[], storage)?;
+    assert_eq!(run.result(), ReturnCode::OutOfGas);
+    assert_eq!(run.gas_used(), 807);
+    assert!(engine.code_registry().code(&address).is_none());
+
+    let storage = Box::new(InMemoryStorage::new());
+    let (address, run) = engine.deploy(&tx_info, 1, &init_code, &block_info, 808, &[], storage)?;
+    assert_eq!(run.result(), ReturnCode::ExplicitReturn);
+    assert_eq!(run.gas_used(), 808);
+    assert!(engine.code_registry().code(&address).is_some());
+    Ok(())
+}
+
+#[test]
+fn deploy_rejects_init_code_over_size_limit() -> Result<(), Error> {
+    let llvm_ctx = Context::create();
+    let opts = Options::new(Debug, false, false, true, SpecId::default());
+    let engine = Engine::new(&llvm_ctx, opts)?;
+    let block_info = new_test_block_info();
+    let tx_info = new_test_tx_info();
+    let gas = TEST_GAS_LIMIT;
+
+    let init_code = vec![0; MAX_INITCODE_SIZE];
+    let storage = Box::new(InMemoryStorage::new());
+    let (_, run) = engine.deploy(&tx_info, 0, &init_code, &block_info, gas, &[], storage)?;
+    assert_eq!(run.result(), ReturnCode::Stop);
+
+    let init_code = vec![0; MAX_INITCODE_SIZE + 1];
+    let storage = Box::new(InMemoryStorage::new());
+    match engine.deploy(&tx_info, 1, &init_code, &block_info, gas, &[], storage) {
+        Err(jet::engine::Error::InitCodeTooLarge(len)) => assert_eq!(len, init_code.len()),
+        _ => panic!("init code over the size limit was not rejected"),
+    }
+    Ok(())
+}
+
+#[test]
+fn deploy_runs_init_code_over_size_limit_before_shanghai() -> Result<(), Error> {
+    let llvm_ctx = Context::create();
+    let opts = Options::new(Debug, false, false, true, SpecId::Paris);
+    let engine = Engine::new(&llvm_ctx, opts)?;
+    let block_info = new_test_block_info();
+    let tx_info = new_test_tx_info();
+    let gas = TEST_GAS_LIMIT;
+
+    let init_code = vec![0; MAX_INITCODE_SIZE + 1];
+    let storage = Box::new(InMemoryStorage::new());
+    let (_, run) = engine.deploy(&tx_info, 0, &init_code, &block_info, gas, &[], storage)?;
+    assert_eq!(run.result(), ReturnCode::Stop);
+    Ok(())
+}
+
+#[test]
+fn deploy_rejects_code_over_size_limit() -> Result<(), Error> {
+    let llvm_ctx = Context::create();
+    let opts = Options::new(Debug, false, false, true, SpecId::default());
+    let engine = Engine::new(&llvm_ctx, opts)?;
+    let block_info = new_test_block_info();
+    let tx_info = new_test_tx_info();
+    let gas = TEST_GAS_LIMIT;
+
+    for (nonce, len, deployed) in [(0, MAX_CODE_SIZE, true), (1, MAX_CODE_SIZE + 1, false)] {
+        let len = (len as u16).to_be_bytes();
+        let init_code = [
+            Instruction::PUSH2.opcode(), // Size
+            len[0],
+            len[1],
+            Instruction::PUSH0.opcode(), // Offset
+            Instruction::RETURN.opcode(),
+        ];
+        let storage = Box::new(InMemoryStorage::new());
+        let (address, run) =
+            engine.deploy(&tx_info, nonce, &init_code, &block_info, gas, &[], storage)?;
+        assert_eq!(run.result().is_success(), deployed);
+        assert_eq!(engine.code_registry().code(&address).is_some(), deployed);
+    }
+    Ok(())
+}
+
+#[test]
+fn create_addresses_match_known_vectors() {
+    let creator = parse_address("0x6ac7ea33f8831ea9dcc53393aaa88b25a785dbf0").unwrap();
+    assert_eq!(
+        create_address(&creator, 0),
+        parse_address("0xcd234a471b72ba2f1ccf0a70fcaba648a5eecd8d").unwrap()
+    );
+    assert_eq!(
+        create_address(&creator, 1),
+        parse_address("0x343c43a37d37dff08ae8c4a11544c718abb4fcf8").unwrap()
+    );
+
+    // Example 0 from EIP-1014
+    assert_eq!(
+        create2_address(&[0; 20], &[0; 32], &[0x00]),
+        parse_address("0x4d1a2e2bb4f88f0250f26ffff098b0b30b26bf38").unwrap()
+    );
+}
+
+#[test]
+fn build_contract_rejects_short_address() -> Result<(), Error> {
+    let llvm_ctx = Context::create();
+    let opts = Options::new(Debug, false, false, true, SpecId::default());
+    let engine = Engine::new(&llvm_ctx, opts)?;
+    let rom = [Instruction::STOP.opcode()];
+    assert!(matches!(
+        engine.build_contract("0x1234", &rom),
+        Err(jet::engine::Error::InvalidAddress(_))
+    ));
+    Ok(())
+}
+
+#[test]
+fn division_by_zero_and_overflow_follow_the_evm() -> Result<(), Error> {
+    let int_min = {
+        let mut word = [0; 32];
+        word[0] = 0x80;
+        word
+    };
+    let max = [0xff; 32];
+    let w = signed_word_be;
+
+    let cases = [
+        (Instruction::DIV, w(7), w(2), w(3)),
+        (Instruction::DIV, w(7), w(0), w(0)),
+        (Instruction::DIV, w(0), w(0), w(0)),
+        (Instruction::DIV, max, w(1), max),
+        (Instruction::SDIV, w(-7), w(2), w(-3)),
+        (Instruction::SDIV, w(7), w(-2), w(-3)),
+        (Instruction::SDIV, w(7), w(0), w(0)),
+        (Instruction::SDIV, w(7), w(-1), w(-7)),
+        (Instruction::SDIV, int_min, w(-1), int_min),
+        (Instruction::SDIV, int_min, w(1), int_min),
+        (Instruction::MOD, w(7), w(3), w(1)),
+        (Instruction::MOD, w(7), w(0), w(0)),
+        (Instruction::MOD, max, w(2), w(1)),
+        (Instruction::SMOD, w(-7), w(3), w(-1)),
+        (Instruction::SMOD, w(7), w(-3), w(1)),
+        (Instruction::SMOD, w(7), w(0), w(0)),
+        (Instruction::SMOD, w(7), w(-1), w(0)),
+        (Instruction::SMOD, int_min, w(-1), w(0)),
+    ];
+
+    for (instr, a, b, expected) in cases {
+        let t = Test {
+            roms: vec![binary_op_rom(instr, a, b)],
+            expected: TestContractRun {
+                stack_ptr: 1,
+                stack: vec![stack_word_be(&expected)],
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        _test_rom_body(t, false)?;
+    }
+    Ok(())
+}
+
+#[test]
+fn modular_arithmetic_does_not_wrap() -> Result<(), Error> {
+    let max = [0xff; 32];
+    let w = signed_word_be;
+
+    // The sums and products of the maximal word don't fit into a word
+    let cases = [
+        (Instruction::ADDMOD, w(10), w(10), w(8), w(4)),
+        (Instruction::ADDMOD, max, w(2), w(3), w(2)),
+        (Instruction::ADDMOD, max, w(1), max, w(1)),
+        (Instruction::ADDMOD, max, max, w(0), w(0)),
+        (Instruction::MULMOD, w(10), w(10), w(8), w(4)),
+        (Instruction::MULMOD, max, max, w(12), w(9)),
+        (Instruction::MULMOD, max, max, max, w(0)),
+        (Instruction::MULMOD, w(5), w(5), w(0), w(0)),
+    ];
+
+    for (instr, a, b, n, expected) in cases {
+        let t = Test {
+            roms: vec![ternary_op_rom(instr, a, b, n)],
+            expected: TestContractRun {
+                stack_ptr: 1,
+                stack: vec![stack_word_be(&expected)],
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        _test_rom_body(t, false)?;
+    }
+    Ok(())
+}
+
+#[test]
+fn shifts_and_byte_handle_out_of_range_operands() -> Result<(), Error> {
+    let int_min = {
+        let mut word = [0; 32];
+        word[0] = 0x80;
+        word
+    };
+    let max = [0xff; 32];
+    let mut max_shl_4 = max;
+    max_shl_4[31] = 0xf0;
+    let w = signed_word_be;
+
+    // The shift or index is the first operand, on top of the stack
+    let cases = [
+        (Instruction::SHL, w(1), w(1), w(2)),
+        (Instruction::SHL, w(4), max, max_shl_4),
+        (Instruction::SHL, w(255), w(1), int_min),
+        (Instruction::SHL, w(256), w(1), w(0)),
+        (Instruction::SHL, max, w(1), w(0)),
+        (Instruction::SHR, w(1), w(2), w(1)),
+        (Instruction::SHR, w(255), int_min, w(1)),
+        (Instruction::SHR, w(256), int_min, w(0)),
+        (Instruction::SHR, max, max, w(0)),
+        (Instruction::SAR, w(1), w(-4), w(-2)),
+        (Instruction::SAR, w(1), w(4), w(2)),
+        (Instruction::SAR, w(255), int_min, w(-1)),
+        (Instruction::SAR, w(256), int_min, w(-1)),
+        (Instruction::SAR, max, w(-1), w(-1)),
+        (Instruction::SAR, w(300), w(5), w(0)),
+        (Instruction::BYTE, w(31), w(0x1234), w(0x34)),
+        (Instruction::BYTE, w(30), w(0x1234), w(0x12)),
+        (Instruction::BYTE, w(0), int_min, w(0x80)),
+        (Instruction::BYTE, w(32), max, w(0)),
+        (Instruction::BYTE, max, max, w(0)),
+    ];
+
+    for (instr, a, b, expected) in cases {
+        let t = Test {
+            roms: vec![binary_op_rom(instr, a, b)],
+            expected: TestContractRun {
+                stack_ptr: 1,
+                stack: vec![stack_word_be(&expected)],
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        _test_rom_body(t, false)?;
+    }
+    Ok(())
+}
+
+#[test]
+fn signextend_extends_from_the_given_byte() -> Result<(), Error> {
+    let max = [0xff; 32];
+    let mut high_bit = [0; 32];
+    high_bit[0] = 0x80;
+    let w = signed_word_be;
+
+    // The byte index is the first operand, on top of the stack
+    let cases = [
+        (w(0), w(0x7f), w(0x7f)),
+        (w(0), w(0x80), w(-0x80)),
+        (w(0), w(0xff), w(-1)),
+        (w(0), w(0x1234), w(0x34)),
+        (w(1), w(0x1234), w(0x1234)),
+        (w(1), w(0x8234), w(-0x7dcc)),
+        (w(1), w(0x12_8234), w(-0x7dcc)),
+        (w(30), high_bit, w(0)),
+        (w(31), w(0xff), w(0xff)),
+        (w(31), high_bit, high_bit),
+        (w(32), w(0xff), w(0xff)),
+        (max, w(0x80), w(0x80)),
+    ];
+
+    for (b, x, expected) in cases {
+        let t = Test {
+            roms: vec![binary_op_rom(Instruction::SIGNEXTEND, b, x)],
+            expected: TestContractRun {
+                stack_ptr: 1,
+                stack: vec![stack_word_be(&expected)],
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        _test_rom_body(t, false)?;
+    }
+    Ok(())
+}
+
+#[test]
+fn runtime_info_matches_the_generated_types() {
+    let llvm_ctx = Context::create();
+    let module = jet_runtime::module::load(&llvm_ctx).unwrap();
+    let data_layout = module.get_data_layout();
+    let target_data = TargetData::create(data_layout.as_str().to_str().unwrap());
+    let opts = Options::new(Debug, false, false, true, SpecId::default());
+    let env = Env::new(&llvm_ctx, module, opts);
+    let t = env.types();
+
+    for (i, offset) in TxInfo::FIELD_OFFSETS.into_iter().enumerate() {
+        let ir_offset = target_data.offset_of_element(&t.tx_info, i as u32);
+        assert_eq!(ir_offset, Some(offset as u64), "tx info field {}", i);
+    }
+    let size = size_of::<TxInfo>() as u64;
+    assert_eq!(target_data.get_abi_size(&t.tx_info), size);
+
+    // Only the offsets are compared here, since the Rust struct is padded at the end to the
+    // alignment of its u64 fields
+    for (i, offset) in BlockInfo::FIELD_OFFSETS.into_iter().enumerate() {
+        let ir_offset = target_data.offset_of_element(&t.block_info, i as u32);
+        assert_eq!(ir_offset, Some(offset as u64), "block info field {}", i);
+    }
+}
+
+#[test]
+fn nested_call_sets_full_caller_and_address() -> Result<(), Error> {
+    let llvm_ctx = Context::create();
+    let opts = Options::new(Debug, false, false, true, SpecId::default());
+    let engine = Engine::new(&llvm_ctx, opts)?;
+    let block_info = new_test_block_info();
+
+    // Every byte of the addresses is set, so a field read from the wrong offset shows up
+    let caller = "0x0123456789abcdef0123456789abcdef01234567";
+    let callee = "0xfedcba9876543210fedcba9876543210fedcba98";
+    let caller_address = parse_address(caller).unwrap();
+    let callee_address = parse_address(callee).unwrap();
+    let caller_rom = [
+        vec![
+            Instruction::ADDRESS.opcode(),
+            Instruction::CALLER.opcode(),
+            Instruction::PUSH0.opcode(),  // Output len
+            Instruction::PUSH0.opcode(),  // Output offset
+            Instruction::PUSH0.opcode(),  // Input len
+            Instruction::PUSH0.opcode(),  // Input offset
+            Instruction::PUSH0.opcode(),  // Value
+            Instruction::PUSH20.opcode(), // Address
+        ],
+        callee_address.to_vec(),
+        vec![
+            Instruction::GAS.opcode(), // Gas
+            Instruction::CALL.opcode(),
+        ],
+    ]
+    .concat();
+    let callee_rom = vec![
+        Instruction::ADDRESS.opcode(),
+        Instruction::CALLER.opcode(),
+        Instruction::ORIGIN.opcode(),
+    ];
+    engine.build_contract(caller, &caller_rom)?;
+    engine.build_contract(callee, &callee_rom)?;
+
+    let tx_info = new_test_tx_info().with_address(caller_address);
+    let storage = Box::new(InMemoryStorage::new());
+    let run = engine.run_contract(&tx_info, &block_info, &[], TEST_GAS_LIMIT, &[], storage)?;
+    let expected = TestContractRun {
+        stack_ptr: 3,
+        stack: vec![
+            address_to_word(&caller_address),
+            address_to_word(&test_sender()),
+            stack_word(&[0x01]),
+        ],
+        sub_calls: Some(vec![TestContractRun {
+            stack_ptr: 3,
+            stack: vec![
+                address_to_word(&callee_address),
+                address_to_word(&caller_address),
+                address_to_word(&test_sender()),
+            ],
+            ..Default::default()
+        }]),
+        ..Default::default()
+    };
+    expected.assert_eq(&run);
+    Ok(())
 }
@@ -42,16 +42,19 @@ impl fmt::Display for exec::Context {
             self.memory_len(),
             self.memory_cap()
         )?;
-        for i in 0..1 {
-            let offset = (32 * i) as usize;
-            let end = offset + 32;
+
+        write!(
+            f,
+            "Gas:\n  {{ limit: {}, remaining: {} }}\n",
+            self.gas_limit(),
+            self.gas_remaining()
+        )?;
+        for (i, word) in self.memory().chunks(32).enumerate() {
             writeln!(
                 f,
                 "  {}: {}",
                 i,
-                self.memory()[offset..end]
-                    .iter()
-                    .take(32)
+                word.iter()
                     .fold(String::new(), |acc, x| acc.clone() + &format!("{:02X}", x))
             )?;
         }
@@ -77,13 +80,11 @@ impl fmt::Display for exec::Context {
             )?;
         }
 
-        match self.sub_ctx() {
-            Some(sub_ctx) => {
-                writeln!(f, "Sub Call:\n{}", sub_ctx)?;
-            }
-            None => {
-                writeln!(f, "Sub Call: None")?;
-            }
+        if self.sub_calls().is_empty() {
+            writeln!(f, "Sub Calls: None")?;
+        }
+        for (i, sub_ctx) in self.sub_calls().iter().enumerate() {
+            writeln!(f, "Sub Call {} ({:?}):\n{}", i, sub_ctx.result(), sub_ctx)?;
         }
 
         Ok(())
@@ -116,8 +117,10 @@ impl fmt::Display for exec::ContractRun {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         write!(
             f,
-            "ContractRun:\nResult: {:?}\n{}",
+            "ContractRun:\nResult: {:?}\nGas used: {}\nGas refund: {}\n{}",
             self.result(),
+            self.gas_used(),
+            self.gas_refund(),
             self.ctx()
         )
     }
@@ -1,9 +1,17 @@
+use std::ops::Range;
+
 use inkwell::execution_engine::ExecutionEngine;
-use log::trace;
 
 use crate::{
-    ADDRESS_SIZE_BYTES,
-    exec::{Context, ContractFunc, jet_contract_fn_lookup, ReturnCode, Word}, WORD_SIZE_BYTES,
+    exec::{
+        Address, address_from_word, address_to_word, BlockInfo, CallKind, Context, ContractFunc,
+        create2_address, create_address, jet_deployed_contract_fn_try_lookup, Log, read_padded,
+        ReturnCode, TxInfo, Word, word_checked_sub, word_exp, word_to_u32, word_to_u64,
+    },
+    gas,
+    spec::SpecId,
+    state::hash_code,
+    WORD_SIZE_BYTES,
 };
 
 //  Core
@@ -54,175 +62,998 @@ pub unsafe extern "C" fn stack_swap(ctx: *mut Context, swap_idx: u8) -> bool {
     ctx.stack_swap(swap_idx as u32)
 }
 
-///  Stores a word in memory.
+///  Stores a word in memory, expanding it if needed.
 ///
 ///  # Safety
 ///
 ///  This function is unsafe because it dereferences the given pointers. The caller must ensure that
 ///  all the pointers are valid.
-pub unsafe extern "C" fn mem_store(ctx: *mut Context, loc: *const u32, val: *const Word) -> i8 {
+pub unsafe extern "C" fn mem_store(ctx: *mut Context, loc: *const Word, val: *const Word) -> i8 {
     let ctx = unsafe { ctx.as_mut() }.unwrap();
-    let loc = unsafe { *loc };
-    let word_ref = unsafe { &*val };
+    let loc = unsafe { &*loc };
+    let word = unsafe { &*val };
+
+    let range = match ctx.memory_range_with_len(loc, WORD_SIZE_BYTES) {
+        Ok(range) => range,
+        Err(code) => return code as i8,
+    };
 
-    let end_loc = loc.saturating_add(WORD_SIZE_BYTES);
-    // TODO: Handle this after we correctly handle memory_len
-    // if end_loc > ctx.memory_len {
-    //     return -1; // Out of bounds
-    // }
-    let start = loc as usize;
-    let end = end_loc as usize;
-    ctx.memory[start..end].copy_from_slice(word_ref);
+    // Words are little-endian on the stack but big-endian in memory
+    for (dest, src) in ctx.memory[range].iter_mut().zip(word.iter().rev()) {
+        *dest = *src;
+    }
     0
 }
 
-///  Stores a byte in memory.
+///  Stores the least significant byte of a word in memory, expanding it if needed.
 ///
 ///  # Safety
 ///
 ///  This function is unsafe because it dereferences the given pointers. The caller must ensure that
 ///  all the pointers are valid.
-pub unsafe extern "C" fn mem_store_byte(ctx: *mut Context, loc: *const u32, val: *const u8) -> i8 {
+pub unsafe extern "C" fn mem_store_byte(
+    ctx: *mut Context,
+    loc: *const Word,
+    val: *const Word,
+) -> i8 {
+    let ctx = unsafe { ctx.as_mut() }.unwrap();
+    let loc = unsafe { &*loc };
+    let word = unsafe { &*val };
+
+    let range = match ctx.memory_range_with_len(loc, 1) {
+        Ok(range) => range,
+        Err(code) => return code as i8,
+    };
+    ctx.memory[range.start] = word[0];
+    0
+}
+
+/// Loads a word from memory, expanding it if needed, and pushes it onto the stack.
+///
+/// # Safety
+///
+/// This function is unsafe because it dereferences the given pointers. The caller must ensure that
+/// all the pointers are valid.
+pub unsafe extern "C" fn mem_load(ctx: *mut Context, loc: *const Word) -> i8 {
+    let ctx = unsafe { ctx.as_mut() }.unwrap();
+    let loc = unsafe { &*loc };
+
+    let range = match ctx.memory_range_with_len(loc, WORD_SIZE_BYTES) {
+        Ok(range) => range,
+        Err(code) => return code as i8,
+    };
+
+    // Words are big-endian in memory but little-endian on the stack
+    let mut word: Word = [0; WORD_SIZE_BYTES as usize];
+    for (dest, src) in word.iter_mut().zip(ctx.memory[range].iter().rev()) {
+        *dest = *src;
+    }
+    ctx.stack_push(word);
+    0
+}
+
+/// Expands memory to cover the given range without otherwise touching it.
+///
+/// # Safety
+///
+/// This function is unsafe because it dereferences the given pointers. The caller must ensure that
+/// all the pointers are valid.
+pub unsafe extern "C" fn mem_expand(
+    ctx: *mut Context,
+    offset: *const Word,
+    len: *const Word,
+) -> i8 {
+    let ctx = unsafe { ctx.as_mut() }.unwrap();
+    let offset = unsafe { &*offset };
+    let len = unsafe { &*len };
+
+    match ctx.memory_range(offset, len) {
+        Ok(_) => 0,
+        Err(code) => code as i8,
+    }
+}
+
+/// Copies a range of memory to another place in memory, expanding it to cover both ranges if
+/// needed. The ranges may overlap.
+///
+/// # Safety
+///
+/// This function is unsafe because it dereferences the given pointers. The caller must ensure that
+/// all the pointers are valid.
+pub unsafe extern "C" fn mem_copy(
+    ctx: *mut Context,
+    dest_offset: *const Word,
+    offset: *const Word,
+    len: *const Word,
+) -> i8 {
+    let ctx = unsafe { ctx.as_mut() }.unwrap();
+    let dest_offset = unsafe { &*dest_offset };
+    let offset = unsafe { &*offset };
+    let len = unsafe { &*len };
+
+    let src_range = match ctx.memory_range(offset, len) {
+        Ok(range) => range,
+        Err(code) => return code as i8,
+    };
+    let dest_range = match ctx.memory_range(dest_offset, len) {
+        Ok(range) => range,
+        Err(code) => return code as i8,
+    };
+    if let Err(code) = ctx.use_gas(gas::word_cost(gas::COPY_WORD, dest_range.len())) {
+        return code as i8;
+    }
+    ctx.memory.copy_within(src_range, dest_range.start);
+    0
+}
+
+// Call data
+//
+
+/// Loads a word of input starting at the given offset and pushes it onto the stack. Bytes past
+/// the end of the input are read as zero.
+///
+/// # Safety
+///
+/// This function is unsafe because it dereferences the given pointers. The caller must ensure that
+/// all the pointers are valid.
+pub unsafe extern "C" fn calldata_load(ctx: *mut Context, offset: *const Word) -> i8 {
     let ctx = unsafe { ctx.as_mut() }.unwrap();
-    let loc = unsafe { *loc };
-    let byte = unsafe { *val };
+    let offset = unsafe { &*offset };
+
+    let mut input = [0; WORD_SIZE_BYTES as usize];
+    ctx.read_input(offset, &mut input);
 
-    // TODO: Handle this after we correctly handle memory_len
-    // if loc >= ctx.memory_len {
-    //     return -1; // Out of bounds
-    // }
-    ctx.memory[loc as usize] = byte;
+    // Input is big-endian but words are little-endian on the stack
+    input.reverse();
+    ctx.stack_push(input);
     0
 }
 
-/// Loads a word from memory.
+/// Pushes the size of the input onto the stack.
 ///
 /// # Safety
 ///
 /// This function is unsafe because it dereferences the given pointer. The caller must ensure that
 /// the pointer is valid.
-pub unsafe extern "C" fn mem_load(ctx: *const Context, loc: *const u32) -> *const Word {
-    let ctx = unsafe { ctx.as_ref() }.unwrap();
-    let loc = unsafe { *loc };
+pub unsafe extern "C" fn calldata_size(ctx: *mut Context) -> i8 {
+    let ctx = unsafe { ctx.as_mut() }.unwrap();
+
+    let mut size: Word = [0; WORD_SIZE_BYTES as usize];
+    size[..8].copy_from_slice(&(ctx.input().len() as u64).to_le_bytes());
+    ctx.stack_push(size);
+    0
+}
+
+/// Copies input into memory, expanding it if needed. Bytes past the end of the input are copied
+/// as zero.
+///
+/// # Safety
+///
+/// This function is unsafe because it dereferences the given pointers. The caller must ensure that
+/// all the pointers are valid.
+pub unsafe extern "C" fn calldata_copy(
+    ctx: *mut Context,
+    dest_offset: *const Word,
+    offset: *const Word,
+    len: *const Word,
+) -> i8 {
+    let ctx = unsafe { ctx.as_mut() }.unwrap();
+    let dest_offset = unsafe { &*dest_offset };
+    let offset = unsafe { &*offset };
+    let len = unsafe { &*len };
 
-    let end_loc = loc.saturating_add(WORD_SIZE_BYTES);
-    // TODO: Handle this after we correctly handle memory_len
-    // if end_loc > ctx.memory_len {
-    //     return std::ptr::null(); // Out of bounds
-    // }
-    let start = loc as usize;
-    let end = end_loc as usize;
+    let range = match ctx.memory_range(dest_offset, len) {
+        Ok(range) => range,
+        Err(code) => return code as i8,
+    };
+    if let Err(code) = ctx.use_gas(gas::word_cost(gas::COPY_WORD, range.len())) {
+        return code as i8;
+    }
 
-    ctx.memory[start..end].as_ptr() as *const Word
+    let mut data = vec![0; range.len()];
+    ctx.read_input(offset, &mut data);
+    ctx.memory[range].copy_from_slice(&data);
+    0
 }
 
-// Contract calls
+// Code
+//
+
+/// Copies the given code into memory, expanding it if needed. Bytes past the end of the code are
+/// copied as zero. Every contract function passes its own code, which is embedded in its module.
+///
+/// # Safety
+///
+/// This function is unsafe because it dereferences the given pointers. The caller must ensure that
+/// all the pointers are valid, and that the code pointer points to code_len bytes.
+pub unsafe extern "C" fn code_copy(
+    ctx: *mut Context,
+    code: *const u8,
+    code_len: u32,
+    dest_offset: *const Word,
+    offset: *const Word,
+    len: *const Word,
+) -> i8 {
+    let ctx = unsafe { ctx.as_mut() }.unwrap();
+    let code = unsafe { std::slice::from_raw_parts(code, code_len as usize) };
+    let dest_offset = unsafe { &*dest_offset };
+    let offset = unsafe { &*offset };
+    let len = unsafe { &*len };
+
+    let range = match ctx.memory_range(dest_offset, len) {
+        Ok(range) => range,
+        Err(code) => return code as i8,
+    };
+    if let Err(code) = ctx.use_gas(gas::word_cost(gas::COPY_WORD, range.len())) {
+        return code as i8;
+    }
+    read_padded(code, offset, &mut ctx.memory[range]);
+    0
+}
+
+/// Pushes the size of the code of the account at the given address onto the stack.
+///
+/// # Safety
+///
+/// This function is unsafe because it dereferences the given pointers. The caller must ensure that
+/// all the pointers are valid.
+pub unsafe extern "C" fn ext_code_size(ctx: *mut Context, addr: *const Word) -> i8 {
+    let ctx = unsafe { ctx.as_mut() }.unwrap();
+    let address = address_from_word(unsafe { &*addr });
+    if let Err(code) = ctx.access_account(&address) {
+        return code as i8;
+    }
+
+    let code_len = ctx.execution_mut().code(&address).len();
+    let mut size: Word = [0; WORD_SIZE_BYTES as usize];
+    size[..8].copy_from_slice(&(code_len as u64).to_le_bytes());
+    ctx.stack_push(size);
+    0
+}
+
+/// Copies the code of the account at the given address into memory, expanding it if needed. Bytes
+/// past the end of the code are copied as zero.
+///
+/// # Safety
+///
+/// This function is unsafe because it dereferences the given pointers. The caller must ensure that
+/// all the pointers are valid.
+pub unsafe extern "C" fn ext_code_copy(
+    ctx: *mut Context,
+    addr: *const Word,
+    dest_offset: *const Word,
+    offset: *const Word,
+    len: *const Word,
+) -> i8 {
+    let ctx = unsafe { ctx.as_mut() }.unwrap();
+    let address = address_from_word(unsafe { &*addr });
+    let dest_offset = unsafe { &*dest_offset };
+    let offset = unsafe { &*offset };
+    let len = unsafe { &*len };
+
+    if let Err(code) = ctx.access_account(&address) {
+        return code as i8;
+    }
+    let range = match ctx.memory_range(dest_offset, len) {
+        Ok(range) => range,
+        Err(code) => return code as i8,
+    };
+    if let Err(code) = ctx.use_gas(gas::word_cost(gas::COPY_WORD, range.len())) {
+        return code as i8;
+    }
+    let code = ctx.execution_mut().code(&address);
+    read_padded(&code, offset, &mut ctx.memory[range]);
+    0
+}
+
+/// Pushes the hash of the code of the account at the given address onto the stack, or zero if
+/// the account doesn't exist.
+///
+/// # Safety
+///
+/// This function is unsafe because it dereferences the given pointers. The caller must ensure that
+/// all the pointers are valid.
+pub unsafe extern "C" fn ext_code_hash(ctx: *mut Context, addr: *const Word) -> i8 {
+    let ctx = unsafe { ctx.as_mut() }.unwrap();
+    let address = address_from_word(unsafe { &*addr });
+    if let Err(code) = ctx.access_account(&address) {
+        return code as i8;
+    }
+
+    // The hash is big-endian but words are little-endian on the stack
+    let mut hash = ctx.execution_mut().code_hash(&address);
+    hash.reverse();
+    ctx.stack_push(hash);
+    0
+}
+
+// Storage
+//
+
+/// Loads a word from the current contract's storage and pushes it onto the stack.
+///
+/// # Safety
+///
+/// This function is unsafe because it dereferences the given pointers. The caller must ensure that
+/// all the pointers are valid.
+pub unsafe extern "C" fn storage_load(ctx: *mut Context, key: *const Word) -> i8 {
+    let ctx = unsafe { ctx.as_mut() }.unwrap();
+    let key = unsafe { *key };
+
+    if let Err(code) = ctx.access_slot(&key) {
+        return code as i8;
+    }
+    let address = *ctx.address();
+    let value = ctx.execution_mut().storage().load(&address, &key);
+    ctx.stack_push(value);
+    0
+}
+
+/// Stores a word in the current contract's storage. Fails in a static context, or, since Istanbul,
+/// if no more than the call stipend is left. The cost and refund depend on the value the slot had
+/// when the transaction started and the value it has now, and slots that haven't been accessed yet
+/// in the transaction cost extra.
+///
+/// # Safety
+///
+/// This function is unsafe because it dereferences the given pointers. The caller must ensure that
+/// all the pointers are valid.
+pub unsafe extern "C" fn storage_store(
+    ctx: *mut Context,
+    key: *const Word,
+    value: *const Word,
+) -> i8 {
+    let ctx = unsafe { ctx.as_mut() }.unwrap();
+    let key = unsafe { *key };
+    let value = unsafe { *value };
+
+    if ctx.is_static() {
+        return ReturnCode::StaticCallViolation as i8;
+    }
+
+    // Calls made with only the stipend can't change storage
+    let spec_id = ctx.spec_id();
+    if spec_id.is_enabled_in(SpecId::Istanbul) && ctx.gas_remaining() <= gas::SSTORE_SENTRY {
+        return ReturnCode::OutOfGas as i8;
+    }
+
+    let address = *ctx.address();
+    let execution = ctx.execution_mut();
+    let original = execution.original_storage_load(&address, &key);
+    let current = execution.storage().load(&address, &key);
+    let (mut cost, refund) = gas::sstore_cost(spec_id, &original, &current, &value);
+    if spec_id.is_enabled_in(SpecId::Berlin) && execution.warm_slot(&address, &key) {
+        cost += gas::COLD_SLOAD;
+    }
+    if let Err(code) = ctx.use_gas(cost) {
+        return code as i8;
+    }
+
+    let execution = ctx.execution_mut();
+    execution.add_refund(refund);
+    execution.storage_store(&address, &key, value);
+    0
+}
+
+/// Loads a word from the current contract's transient storage and pushes it onto the stack.
+///
+/// # Safety
+///
+/// This function is unsafe because it dereferences the given pointers. The caller must ensure that
+/// all the pointers are valid.
+pub unsafe extern "C" fn transient_storage_load(ctx: *mut Context, key: *const Word) -> i8 {
+    let ctx = unsafe { ctx.as_mut() }.unwrap();
+    let key = unsafe { *key };
+
+    let address = *ctx.address();
+    let value = ctx.execution_mut().transient_storage_load(&address, &key);
+    ctx.stack_push(value);
+    0
+}
+
+/// Stores a word in the current contract's transient storage. Fails in a static context.
+///
+/// # Safety
+///
+/// This function is unsafe because it dereferences the given pointers. The caller must ensure that
+/// all the pointers are valid.
+pub unsafe extern "C" fn transient_storage_store(
+    ctx: *mut Context,
+    key: *const Word,
+    value: *const Word,
+) -> i8 {
+    let ctx = unsafe { ctx.as_mut() }.unwrap();
+    let key = unsafe { *key };
+    let value = unsafe { *value };
+
+    if ctx.is_static() {
+        return ReturnCode::StaticCallViolation as i8;
+    }
+
+    let address = *ctx.address();
+    ctx.execution_mut()
+        .transient_storage_store(&address, &key, value);
+    0
+}
+
+// Accounts
 //
 
-/// Calls the contract at the given address.
+/// Pushes the balance of the account at the given address onto the stack.
 ///
 /// # Safety
 ///
 /// This function is unsafe because it dereferences the given pointers. The caller must ensure that
 /// all the pointers are valid.
+pub unsafe extern "C" fn balance(ctx: *mut Context, addr: *const Word) -> i8 {
+    let ctx = unsafe { ctx.as_mut() }.unwrap();
+    let address = address_from_word(unsafe { &*addr });
+    if let Err(code) = ctx.access_account(&address) {
+        return code as i8;
+    }
+
+    let balance = ctx.execution_mut().balance(&address);
+    ctx.stack_push(balance);
+    0
+}
+
+/// Pushes the balance of the current contract onto the stack.
+///
+/// # Safety
+///
+/// This function is unsafe because it dereferences the given pointer. The caller must ensure that
+/// the pointer is valid.
+pub unsafe extern "C" fn self_balance(ctx: *mut Context) -> i8 {
+    let ctx = unsafe { ctx.as_mut() }.unwrap();
+
+    let address = *ctx.address();
+    let balance = ctx.execution_mut().balance(&address);
+    ctx.stack_push(balance);
+    0
+}
+
+// Logs
+//
+
+/// Records a log with the given range of memory as its data and the first topic_count of the
+/// given topics. Pointers to unused topics may be null. Fails in a static context.
+///
+/// # Safety
+///
+/// This function is unsafe because it dereferences the given pointers. The caller must ensure that
+/// all the pointers, other than those to unused topics, are valid.
+#[allow(clippy::too_many_arguments)]
+pub unsafe extern "C" fn log(
+    ctx: *mut Context,
+    offset: *const Word,
+    len: *const Word,
+    topic_count: u8,
+    topic0: *const Word,
+    topic1: *const Word,
+    topic2: *const Word,
+    topic3: *const Word,
+) -> i8 {
+    let ctx = unsafe { ctx.as_mut() }.unwrap();
+    let offset = unsafe { &*offset };
+    let len = unsafe { &*len };
+
+    if ctx.is_static() {
+        return ReturnCode::StaticCallViolation as i8;
+    }
+
+    let range = match ctx.memory_range(offset, len) {
+        Ok(range) => range,
+        Err(code) => return code as i8,
+    };
+    if let Err(code) = ctx.use_gas(gas::LOG_DATA_BYTE * range.len() as u64) {
+        return code as i8;
+    }
+
+    let topics = [topic0, topic1, topic2, topic3]
+        .iter()
+        .take(topic_count as usize)
+        .map(|topic| unsafe { **topic })
+        .collect();
+    let log = Log::new(*ctx.address(), topics, ctx.memory[range].to_vec());
+    ctx.execution_mut().add_log(log);
+    0
+}
+
+// Contract calls
+//
+
+/// Calls the contract at the given address and pushes 1 onto the stack if the call succeeded, or
+/// 0 if it failed. The output of the call is copied into the given range of the caller's memory
+/// and kept as return data, whether the call succeeded or reverted. Value sent with the call is
+/// moved from the caller to the callee, and the call fails if the caller can't afford it.
+///
+/// The callee gets the gas asked for, up to all but one 64th of the caller's gas since Tangerine
+/// Whistle, plus a stipend when value is sent. Whatever it doesn't use goes back to the caller.
+///
+/// The value pointer may be null for kinds of calls that don't take a value operand.
+///
+/// # Safety
+///
+/// This function is unsafe because it dereferences the given pointers. The caller must ensure that
+/// all the pointers, other than the value pointer, are valid.
+#[allow(clippy::too_many_arguments)]
 pub unsafe extern "C" fn jet_contract_call(
     ctx: *mut Context,
     jit_engine: *const ExecutionEngine,
-    addr: *const u8,
-    ret_dest: *const u32,
-    ret_len: *const u32,
+    block_info: *const BlockInfo,
+    kind: CallKind,
+    gas_limit: *const Word,
+    addr: *const Word,
+    value: *const Word,
+    in_off: *const Word,
+    in_len: *const Word,
+    out_off: *const Word,
+    out_len: *const Word,
 ) -> i8 {
-    // Look up the contract function
-    let jit_engine = unsafe { jit_engine.as_ref() }.unwrap();
-    let addr_slice = unsafe { std::slice::from_raw_parts(addr, ADDRESS_SIZE_BYTES) };
-    let fn_ptr = jet_contract_fn_lookup(jit_engine, addr_slice);
-    if fn_ptr == 0 {
-        return 1; // Lookup failed
+    // Memory is expanded to cover both the input and the output before the call is made
+    let caller_ctx = unsafe { ctx.as_mut() }.unwrap();
+    let input = match caller_ctx.memory_range(unsafe { &*in_off }, unsafe { &*in_len }) {
+        Ok(range) => caller_ctx.memory[range].to_vec(),
+        Err(code) => return code as i8,
+    };
+    let out_range = match caller_ctx.memory_range(unsafe { &*out_off }, unsafe { &*out_len }) {
+        Ok(range) => range,
+        Err(code) => return code as i8,
+    };
+
+    // Sending value is a state change
+    let value = match unsafe { value.as_ref() } {
+        Some(value) => *value,
+        None => [0; WORD_SIZE_BYTES as usize],
+    };
+    let sends_value = value.iter().any(|byte| *byte != 0);
+    if kind == CallKind::Call && sends_value && caller_ctx.is_static() {
+        return ReturnCode::StaticCallViolation as i8;
     }
 
-    // Instantiate a sub context
-    let caller_ctx = unsafe { ctx.as_mut() }.unwrap();
-    let callee_ctx = caller_ctx.init_sub_call();
+    // The code that runs always belongs to the called address, but the account it runs as
+    // depends on the kind of call
+    let code_address = address_from_word(unsafe { &*addr });
+    let callee_address = kind.callee_address(caller_ctx, code_address);
 
-    // let callee_ctx = caller_ctx.sub_ctx_mut().unwrap();
-    // let callee_ctx_ptr = callee_ctx as *mut Context;
-    // caller_ctx.set_sub_call(callee_ctx_ptr as usize);
+    // Calling an account costs extra the first time, as does sending value, and more so when the
+    // call brings a new account into existence. Since Spurious Dragon, only calls that send value
+    // can do that.
+    if let Err(code) = caller_ctx.access_account(&code_address) {
+        return code as i8;
+    }
+    let spec_id = caller_ctx.spec_id();
+    let mut cost = 0;
+    if sends_value {
+        cost += gas::CALL_VALUE_TRANSFER;
+    }
+    let creates_account = match spec_id.is_enabled_in(SpecId::SpuriousDragon) {
+        true => sends_value,
+        false => true,
+    };
+    if kind == CallKind::Call
+        && creates_account
+        && !caller_ctx.execution_mut().account_exists(&code_address)
+    {
+        cost += gas::CALL_NEW_ACCOUNT;
+    }
+    if let Err(code) = caller_ctx.use_gas(cost) {
+        return code as i8;
+    }
 
-    // Execute the contract function
-    let contract_func: ContractFunc = unsafe { std::mem::transmute(fn_ptr) };
-    let result = unsafe { contract_func(callee_ctx) };
-    if result != ReturnCode::ExplicitReturn && result != ReturnCode::ImplicitReturn {
-        return 2; // Invocation failed
+    // The gas for the callee is set aside up front, and the stipend comes on top of it for free.
+    // Before Tangerine Whistle, the caller had to have all the gas asked for.
+    let requested_gas = word_to_u64(unsafe { &*gas_limit }).unwrap_or(u64::MAX);
+    let mut callee_gas = match spec_id.is_enabled_in(SpecId::TangerineWhistle) {
+        true => requested_gas.min(gas::all_but_one_64th(caller_ctx.gas_remaining())),
+        false => requested_gas,
+    };
+    if let Err(code) = caller_ctx.use_gas(callee_gas) {
+        return code as i8;
+    }
+    if sends_value {
+        callee_gas += gas::CALL_STIPEND;
+    }
+
+    // Calls beyond the depth limit fail without running anything
+    if !caller_ctx.execution_mut().enter_frame(callee_address) {
+        return complete_call(caller_ctx, out_range, false, Vec::new(), callee_gas);
     }
 
-    // Copy return data
-    if callee_ctx.return_len() == 0 {
-        return 0; // Success, but no return data
+    // The value moves within the callee's frame, so it goes back to the caller if the callee fails
+    let sender = *caller_ctx.address();
+    let execution = caller_ctx.execution_mut();
+    if sends_value && !execution.transfer(&sender, &callee_address, &value) {
+        execution.exit_frame(false);
+        return complete_call(caller_ctx, out_range, false, Vec::new(), callee_gas);
     }
 
-    let ret_dest = unsafe { *ret_dest };
-    let ret_len = unsafe { *ret_len };
-    let copy_ret = jet_contract_call_return_data_copy(ctx, callee_ctx, ret_dest, 0, ret_len);
-    copy_ret as i8
+    // Look up the contract function, but only for accounts that have code. Calling an account
+    // without code always succeeds and returns nothing.
+    let jit_engine = unsafe { jit_engine.as_ref() }.unwrap();
+    let fn_ptr = match execution.has_code(&code_address) {
+        true => execution.contract_fn(jit_engine, &code_address),
+        false => None,
+    };
+    let fn_ptr = match fn_ptr {
+        Some(fn_ptr) => fn_ptr,
+        None => {
+            execution.exit_frame(true);
+            return complete_call(caller_ctx, out_range, true, Vec::new(), callee_gas);
+        }
+    };
+
+    // Instantiate a sub context
+    let callee_ctx = caller_ctx.init_sub_call(kind, code_address, value, input, callee_gas);
+
+    // Execute the contract function; everything the callee changed is undone if it failed
+    let contract_func: ContractFunc = unsafe { std::mem::transmute(fn_ptr) };
+    let tx_info: *const TxInfo = callee_ctx.tx_info();
+    let result = unsafe { contract_func(callee_ctx, block_info, tx_info) };
+    callee_ctx.set_result(result.clone());
+    callee_ctx.execution_mut().exit_frame(result.is_success());
+
+    // Only successful and reverted calls produce output
+    let return_data = if result.is_success() || result == ReturnCode::Revert {
+        callee_ctx.return_data().to_vec()
+    } else {
+        Vec::new()
+    };
+    let gas_left = callee_ctx.gas_remaining();
+    complete_call(
+        caller_ctx,
+        out_range,
+        result.is_success(),
+        return_data,
+        gas_left,
+    )
 }
 
-/// Copies return data from the sub context to the parent context.
+/// Hands the outcome of a call back to the caller. As much of the output as fits is copied into
+/// the range of memory set aside for it, the output is kept as return data, the gas the callee
+/// didn't use is given back and the success flag is pushed onto the stack.
+fn complete_call(
+    caller_ctx: &mut Context,
+    out_range: Range<usize>,
+    success: bool,
+    return_data: Vec<u8>,
+    gas_left: u64,
+) -> i8 {
+    let copy_len = out_range.len().min(return_data.len());
+    let out_start = out_range.start;
+    caller_ctx.memory[out_start..out_start + copy_len].copy_from_slice(&return_data[..copy_len]);
+    caller_ctx.last_call_return_data = return_data;
+    caller_ctx.return_gas(gas_left);
+
+    caller_ctx.stack_push(bool_word(success));
+    0
+}
+
+/// Creates a contract by running init code from memory in a new frame and compiling the runtime
+/// code it returns. Pushes the address of the new contract onto the stack, or zero if the creation
+/// failed. CREATE2 passes a salt, which decides the address in place of the creator's nonce. The
+/// value is moved from the creator to the new contract before its init code runs.
+///
+/// The init code gets all but one 64th of the creator's gas since Tangerine Whistle, and whatever
+/// it doesn't use, after paying for the code it returns, goes back to the creator.
 ///
 /// # Safety
 ///
-/// This function is unsafe because it dereferences the given pointers. The caller must ensure
-/// that all the pointers are valid.
-pub unsafe extern "C" fn jet_contract_call_return_data_copy(
+/// This function is unsafe because it dereferences the given pointers. The caller must ensure that
+/// all the pointers, other than the nullable salt, are valid.
+pub unsafe extern "C" fn jet_contract_create(
     ctx: *mut Context,
-    sub_ctx: *const Context,
-    dest_offset: u32,
-    src_offset: u32,
-    requested_ret_len: u32,
-) -> u8 {
-    let ctx = unsafe { ctx.as_mut() }.unwrap();
-    let sub_ctx = unsafe { sub_ctx.as_ref() }.unwrap();
+    jit_engine: *const ExecutionEngine,
+    block_info: *const BlockInfo,
+    value: *const Word,
+    offset: *const Word,
+    len: *const Word,
+    salt: *const Word,
+) -> i8 {
+    let creator_ctx = unsafe { ctx.as_mut() }.unwrap();
+    if creator_ctx.is_static() {
+        return ReturnCode::StaticCallViolation as i8;
+    }
 
-    // Get return and memory data from the callee
-    let ret_offset = sub_ctx.return_off();
-    let ret_len = sub_ctx.return_len();
-    let mem_len = sub_ctx.memory_len();
+    let init_code = match creator_ctx.memory_range(unsafe { &*offset }, unsafe { &*len }) {
+        Ok(range) => creator_ctx.memory[range].to_vec(),
+        Err(code) => return code as i8,
+    };
+    let value = unsafe { *value };
+    let salt = unsafe { salt.as_ref() };
 
-    trace!("jet_contracts_call_return_data_copy:\ndest_offset: {}\nrequested_ret_len: {}\n\nret_offset: {}\nret_len: {}\nmem_len: {}", dest_offset, requested_ret_len, ret_offset, ret_len, mem_len);
+    // Init code over the size limit since Shanghai fails the creator as if it ran out of gas
+    let spec_id = creator_ctx.spec_id();
+    if gas::max_initcode_size(spec_id).is_some_and(|max| init_code.len() > max) {
+        return ReturnCode::OutOfGas as i8;
+    }
 
-    // Bounds checks for the memory and return data
-    if src_offset + requested_ret_len > ret_len {
-        return 3;
+    // Init code is paid for by the word since Shanghai, and CREATE2 pays again for hashing it
+    let mut cost = 0;
+    if spec_id.is_enabled_in(SpecId::Shanghai) {
+        cost += gas::word_cost(gas::INIT_CODE_WORD, init_code.len());
     }
-    let ret_offset_end = ret_offset + requested_ret_len;
-    if ret_offset_end > ret_len {
-        return 4;
+    if salt.is_some() {
+        cost += gas::word_cost(gas::KECCAK256_WORD, init_code.len());
+    }
+    if let Err(code) = creator_ctx.use_gas(cost) {
+        return code as i8;
+    }
+
+    // Creations beyond the depth limit, and creators that can't afford the value, fail before
+    // anything else happens. Otherwise, the creator's nonce goes up whether or not the creation
+    // succeeds.
+    let creator = *creator_ctx.address();
+    let execution = creator_ctx.execution_mut();
+    if !execution.can_enter_frame()
+        || word_checked_sub(&execution.balance(&creator), &value).is_none()
+    {
+        return complete_create(creator_ctx, None, Vec::new(), 0);
+    }
+    let nonce = execution.nonce(&creator);
+    let address = match salt {
+        Some(salt) => create2_address(&creator, salt, &init_code),
+        None => create_address(&creator, nonce),
+    };
+    execution.set_nonce(&creator, nonce + 1);
+
+    // The new address is warm from here on, even if the creation fails
+    execution.warm_account(&address);
+
+    // The depth was checked above, so the frame can always be entered
+    if !execution.enter_frame(address) {
+        return complete_create(creator_ctx, None, Vec::new(), 0);
+    }
+
+    // Everything the init code changed is undone if the creation failed. Before Tangerine Whistle,
+    // the init code got all the gas that was left.
+    let init_gas = match spec_id.is_enabled_in(SpecId::TangerineWhistle) {
+        true => gas::all_but_one_64th(creator_ctx.gas_remaining()),
+        false => creator_ctx.gas_remaining(),
+    };
+    creator_ctx.gas_remaining -= init_gas;
+    let jit_engine = unsafe { jit_engine.as_ref() }.unwrap();
+    let (code, revert_data, gas_left) = deploy_contract(
+        creator_ctx,
+        jit_engine,
+        block_info,
+        address,
+        value,
+        &init_code,
+        init_gas,
+    );
+    let created = code.is_some();
+    let execution = creator_ctx.execution_mut();
+    execution.exit_frame(created);
+
+    // The code goes into the account only once the frame has been committed. Calls only run
+    // contracts whose account has code, so code compiled for a creation that was undone never runs.
+    if let Some(code) = code {
+        execution.set_code(&address, code);
+    }
+    complete_create(
+        creator_ctx,
+        created.then_some(address),
+        revert_data,
+        gas_left,
+    )
+}
+
+/// Runs the init code of a contract being created at the given address with the given gas, and
+/// compiles the runtime code it returns. Returns the runtime code if the contract was created,
+/// along with the data the init code reverted with, if any, and the gas left over. Failures other
+/// than reverts use up all the gas.
+fn deploy_contract(
+    creator_ctx: &mut Context,
+    jit_engine: &ExecutionEngine,
+    block_info: *const BlockInfo,
+    address: Address,
+    value: Word,
+    init_code: &[u8],
+    gas_limit: u64,
+) -> (Option<Vec<u8>>, Vec<u8>, u64) {
+    let creator = *creator_ctx.address();
+    let spec_id = creator_ctx.spec_id();
+    let execution = creator_ctx.execution_mut();
+    let deployer = match execution.deployer() {
+        Some(deployer) => deployer,
+        None => return (None, Vec::new(), 0),
+    };
+
+    // Addresses that are already in use can't be created again
+    if execution.nonce(&address) != 0 || execution.has_code(&address) {
+        return (None, Vec::new(), 0);
+    }
+
+    // New contracts start with a nonce of 1 since Spurious Dragon
+    if spec_id.is_enabled_in(SpecId::SpuriousDragon) {
+        execution.set_nonce(&address, 1);
+    }
+    if !execution.transfer(&creator, &address, &value) {
+        return (None, Vec::new(), 0);
+    }
+
+    // Empty init code creates an account without code
+    if init_code.is_empty() {
+        return (Some(Vec::new()), Vec::new(), gas_limit);
+    }
+    let init_func = match deployer.build_init_code(&address, init_code) {
+        Some(init_func) => init_func,
+        None => return (None, Vec::new(), 0),
+    };
+
+    // Init code runs as the new contract, without input
+    let init_ctx = creator_ctx.init_sub_call(CallKind::Call, address, value, Vec::new(), gas_limit);
+    let tx_info: *const TxInfo = init_ctx.tx_info();
+    let result = unsafe { init_func(init_ctx, block_info, tx_info) };
+    init_ctx.set_result(result.clone());
+    if !result.is_success() {
+        let revert_data = match result {
+            ReturnCode::Revert => init_ctx.return_data().to_vec(),
+            _ => Vec::new(),
+        };
+        return (None, revert_data, init_ctx.gas_remaining());
     }
-    // TODO: Enable this check after adding memory len handling
-    // if ret_offset_end > mem_len {
-    //     return 3;
-    // }
 
-    // Copy the data
-    let src_range = src_offset as usize..(src_offset + requested_ret_len) as usize;
-    let dest_range = dest_offset as usize..(dest_offset + requested_ret_len) as usize;
-    let dest = &mut ctx.memory_mut()[dest_range];
-    dest.copy_from_slice(&sub_ctx.return_data()[src_range]);
+    // The code returned by the init code becomes the code of the contract, if it can be deployed
+    // and paid for
+    if !init_ctx.check_code_deposit() || !init_ctx.charge_code_deposit() {
+        return (None, Vec::new(), 0);
+    }
+    let code = init_ctx.return_data().to_vec();
+    let gas_left = init_ctx.gas_remaining();
+    if code.is_empty() {
+        return (Some(code), Vec::new(), gas_left);
+    }
+
+    // Code compiled for an earlier creation of the same contract, which was undone, is used again
+    let compiled = jet_deployed_contract_fn_try_lookup(jit_engine, &address, &hash_code(&code));
+    if compiled.is_none() && !deployer.build_contract(&address, &code) {
+        return (None, Vec::new(), 0);
+    }
+    (Some(code), Vec::new(), gas_left)
+}
+
+/// Hands the outcome of a contract creation back to the creator. Data the init code reverted with
+/// is kept as return data, the gas the init code didn't use is given back, and the address of the
+/// new contract, or zero on failure, is pushed onto the stack.
+fn complete_create(
+    creator_ctx: &mut Context,
+    address: Option<Address>,
+    revert_data: Vec<u8>,
+    gas_left: u64,
+) -> i8 {
+    creator_ctx.last_call_return_data = revert_data;
+    creator_ctx.return_gas(gas_left);
+
+    let word = match address {
+        Some(address) => address_to_word(&address),
+        None => [0; WORD_SIZE_BYTES as usize],
+    };
+    creator_ctx.stack_push(word);
+    0
+}
+
+/// Pushes the size of the last call's return data onto the stack.
+///
+/// # Safety
+///
+/// This function is unsafe because it dereferences the given pointer. The caller must ensure that
+/// the pointer is valid.
+pub unsafe extern "C" fn return_data_size(ctx: *mut Context) -> i8 {
+    let ctx = unsafe { ctx.as_mut() }.unwrap();
+
+    let mut size: Word = [0; WORD_SIZE_BYTES as usize];
+    size[..8].copy_from_slice(&(ctx.last_call_return_data().len() as u64).to_le_bytes());
+    ctx.stack_push(size);
+    0
+}
+
+/// Copies the last call's return data into memory, expanding it if needed. Unlike call data,
+/// reading past the end of the return data is an error.
+///
+/// # Safety
+///
+/// This function is unsafe because it dereferences the given pointers. The caller must ensure that
+/// all the pointers are valid.
+pub unsafe extern "C" fn return_data_copy(
+    ctx: *mut Context,
+    dest_offset: *const Word,
+    offset: *const Word,
+    len: *const Word,
+) -> i8 {
+    let ctx = unsafe { ctx.as_mut() }.unwrap();
+    let dest_offset = unsafe { &*dest_offset };
+    let offset = unsafe { &*offset };
+    let len = unsafe { &*len };
+
+    let src_range = match (word_to_u32(offset), word_to_u32(len)) {
+        (Some(offset), Some(len)) => offset as usize..offset as usize + len as usize,
+        _ => return ReturnCode::InvalidReturnDataAccess as i8,
+    };
+    if src_range.end > ctx.last_call_return_data.len() {
+        return ReturnCode::InvalidReturnDataAccess as i8;
+    }
+
+    let dest_range = match ctx.memory_range(dest_offset, len) {
+        Ok(range) => range,
+        Err(code) => return code as i8,
+    };
+    if let Err(code) = ctx.use_gas(gas::word_cost(gas::COPY_WORD, dest_range.len())) {
+        return code as i8;
+    }
+    ctx.memory[dest_range].copy_from_slice(&ctx.last_call_return_data[src_range]);
     0
 }
 
 //  Utils
 //
 
-pub extern "C" fn jet_ops_keccak256(buffer: &mut [u8; 32]) -> u8 {
-    // Hash the bytes
+/// Returns the stack word for a boolean flag.
+fn bool_word(flag: bool) -> Word {
+    let mut word = [0; WORD_SIZE_BYTES as usize];
+    word[0] = flag as u8;
+    word
+}
+
+/// Raises the base to the power of the exponent, wrapping around on overflow, and pushes the
+/// result onto the stack. Each byte of the exponent costs extra.
+///
+/// # Safety
+///
+/// This function is unsafe because it dereferences the given pointers. The caller must ensure that
+/// all the pointers are valid.
+pub unsafe extern "C" fn jet_ops_exp(
+    ctx: *mut Context,
+    base: *const Word,
+    exponent: *const Word,
+) -> i8 {
+    let ctx = unsafe { ctx.as_mut() }.unwrap();
+    let base = unsafe { &*base };
+    let exponent = unsafe { &*exponent };
+
+    if let Err(code) = ctx.use_gas(gas::exp_cost(ctx.spec_id(), exponent)) {
+        return code as i8;
+    }
+    ctx.stack_push(word_exp(base, exponent));
+    0
+}
+
+/// Hashes a range of memory, expanding it if needed, and pushes the hash onto the stack.
+///
+/// # Safety
+///
+/// This function is unsafe because it dereferences the given pointers. The caller must ensure that
+/// all the pointers are valid.
+pub unsafe extern "C" fn jet_ops_keccak256(
+    ctx: *mut Context,
+    offset: *const Word,
+    len: *const Word,
+) -> i8 {
     use sha3::{Digest, Keccak256};
+
+    let ctx = unsafe { ctx.as_mut() }.unwrap();
+    let offset = unsafe { &*offset };
+    let len = unsafe { &*len };
+
+    let range = match ctx.memory_range(offset, len) {
+        Ok(range) => range,
+        Err(code) => return code as i8,
+    };
+    if let Err(code) = ctx.use_gas(gas::word_cost(gas::KECCAK256_WORD, range.len())) {
+        return code as i8;
+    }
+
+    // Hash the bytes
     let mut hasher = Keccak256::new();
-    hasher.update(*buffer);
+    hasher.update(&ctx.memory[range]);
     let hash = hasher.finalize();
 
-    // Write the hash back to the buffer
-    for i in 0..32 {
-        buffer[i] = hash[i];
+    // The hash is big-endian but words are little-endian on the stack
+    let mut word: Word = [0; WORD_SIZE_BYTES as usize];
+    for (dest, src) in word.iter_mut().zip(hash.iter().rev()) {
+        *dest = *src;
     }
+    ctx.stack_push(word);
     0
 }
@@ -1,13 +1,29 @@
+use std::{
+    cell::RefCell,
+    collections::{HashMap, HashSet},
+    ffi::c_void,
+    mem::offset_of,
+    ops::Range,
+    rc::Rc,
+};
+
 use inkwell::execution_engine::ExecutionEngine;
 use log::error;
+use sha3::{Digest, Keccak256};
 
-use crate::{*, symbols::FN_CONTRACT_PREFIX};
+use crate::{
+    *,
+    spec::SpecId,
+    state::{Account, CodeRegistry, hash_code, Storage},
+    symbols::{FN_CONTRACT_PREFIX, FN_DEPLOYED_SUFFIX, FN_INIT_CODE_SUFFIX},
+};
 
 pub type Word = [u8; WORD_SIZE_BYTES as usize];
 pub type Address = [u8; ADDRESS_SIZE_BYTES];
 pub type HashHistory = [Word; BLOCK_HASH_HISTORY_SIZE];
 
-pub type ContractFunc = unsafe extern "C" fn(*const Context) -> ReturnCode;
+pub type ContractFunc =
+    unsafe extern "C" fn(*const Context, *const BlockInfo, *const TxInfo) -> ReturnCode;
 
 #[repr(C)]
 pub struct Context {
@@ -17,28 +33,50 @@ pub struct Context {
     return_off: u32,
     return_len: u32,
 
-    sub_call: Option<Box<Context>>,
     stack: [Word; STACK_SIZE_WORDS as usize],
 
-    pub(crate) memory: [u8; (WORD_SIZE_BYTES * MEMORY_INITIAL_SIZE_WORDS) as usize],
     pub(crate) memory_len: u32,
     pub(crate) memory_cap: u32,
+
+    pub(crate) gas_remaining: u64,
+
+    // Fields below are only accessed by the runtime and are not part of the LLVM struct type.
+    pub(crate) memory: Vec<u8>,
+    gas_limit: u64,
+
+    tx_info: TxInfo,
+    is_static: bool,
+    input: Vec<u8>,
+    pub(crate) last_call_return_data: Vec<u8>,
+    result: ReturnCode,
+
+    // Every call made from this context, in the order they were made
+    sub_calls: Vec<Context>,
+    execution: *mut Execution,
 }
 
 impl Context {
-    #[allow(clippy::new_without_default)]
-    pub fn new() -> Self {
-        let init_memory_buf = [0u8; (WORD_SIZE_BYTES * MEMORY_INITIAL_SIZE_WORDS) as usize];
+    pub fn new(tx_info: TxInfo, input: Vec<u8>, gas_limit: u64, execution: *mut Execution) -> Self {
+        let init_memory_buf =
+            Vec::with_capacity((WORD_SIZE_BYTES * MEMORY_INITIAL_SIZE_WORDS) as usize);
         Context {
             stack_ptr: 0,
             jump_ptr: 0,
             return_off: 0,
             return_len: 0,
-            sub_call: None,
             stack: [[0; 32]; STACK_SIZE_WORDS as usize],
-            memory: init_memory_buf,
             memory_len: 0,
-            memory_cap: WORD_SIZE_BYTES * STACK_SIZE_WORDS,
+            memory_cap: WORD_SIZE_BYTES * MEMORY_MAX_SIZE_WORDS,
+            gas_remaining: gas_limit,
+            memory: init_memory_buf,
+            gas_limit,
+            tx_info,
+            is_static: false,
+            input,
+            last_call_return_data: Vec::new(),
+            result: ReturnCode::default(),
+            sub_calls: Vec::new(),
+            execution,
         }
     }
 
@@ -59,9 +97,13 @@ impl Context {
     }
 
     pub fn return_data(&self) -> &[u8] {
+        if self.return_len == 0 {
+            return &[];
+        }
+
+        // Memory is expanded to cover the range when it is returned, so it is always in bounds
         let offset = self.return_off as usize;
         let end = offset + self.return_len as usize;
-        // TODO: Check bounds
         &self.memory[offset..end]
     }
 
@@ -85,12 +127,70 @@ impl Context {
         self.memory_cap
     }
 
-    pub fn sub_ctx(&self) -> Option<&Context> {
-        self.sub_call.as_ref().map(|ctx| ctx.as_ref())
+    /// Sets the maximum size, in bytes, that memory may be expanded to.
+    pub fn set_memory_cap(&mut self, memory_cap: u32) {
+        self.memory_cap = memory_cap;
+    }
+
+    /// Returns the gas the context was given to run with.
+    pub fn gas_limit(&self) -> u64 {
+        self.gas_limit
+    }
+
+    pub fn gas_remaining(&self) -> u64 {
+        self.gas_remaining
     }
 
-    pub fn sub_ctx_mut(&mut self) -> Option<&mut Context> {
-        self.sub_call.as_mut().map(|ctx| ctx.as_mut())
+    /// Returns the gas used by the context, including the gas used by the calls it made. Failures
+    /// other than reverts use up all the gas the context was given.
+    pub fn gas_used(&self) -> u64 {
+        self.gas_limit - self.gas_remaining
+    }
+
+    /// Returns the transaction and message this context runs for. It is passed to the contract
+    /// function along with the block info.
+    pub fn tx_info(&self) -> &TxInfo {
+        &self.tx_info
+    }
+
+    pub fn address(&self) -> &Address {
+        &self.tx_info.address
+    }
+
+    /// Returns the address of the account that made the call.
+    pub fn caller(&self) -> &Address {
+        &self.tx_info.caller
+    }
+
+    /// Returns the value sent with the call.
+    pub fn value(&self) -> &Word {
+        &self.tx_info.value
+    }
+
+    /// Returns true if the context, or any context that called into it, was created by a
+    /// STATICCALL. State can't be modified while this is true.
+    pub fn is_static(&self) -> bool {
+        self.is_static
+    }
+
+    pub fn input(&self) -> &[u8] {
+        &self.input
+    }
+
+    /// Returns the data returned or reverted with by the last call made from this context.
+    pub fn last_call_return_data(&self) -> &[u8] {
+        &self.last_call_return_data
+    }
+
+    /// Returns how the contract run in this context finished.
+    pub fn result(&self) -> ReturnCode {
+        self.result.clone()
+    }
+
+    /// Returns the contexts of every call made from this one, in the order they were made. Calls
+    /// to accounts without code and calls rejected by the depth limit have no context.
+    pub fn sub_calls(&self) -> &[Context] {
+        &self.sub_calls
     }
 
     // Mutators; internal-only
@@ -141,10 +241,702 @@ impl Context {
         true
     }
 
-    /// Creates a new context and sets it as the sub context.
-    pub(crate) fn init_sub_call(&mut self) -> &mut Context {
-        self.sub_call = Some(Box::new(Context::new()));
-        self.sub_call.as_mut().unwrap().as_mut()
+    /// Expands memory to cover the given range and returns it as a range of indexes into memory.
+    /// Fails with InvalidMemoryAccess if the range can't be addressed or exceeds the memory cap,
+    /// and with OutOfGas if the expansion can't be paid for.
+    pub(crate) fn memory_range(
+        &mut self,
+        offset: &Word,
+        len: &Word,
+    ) -> Result<Range<usize>, ReturnCode> {
+        let len = word_to_u32(len).ok_or(ReturnCode::InvalidMemoryAccess)?;
+        self.memory_range_with_len(offset, len)
+    }
+
+    /// Same as memory_range, but for a range with a fixed length.
+    pub(crate) fn memory_range_with_len(
+        &mut self,
+        offset: &Word,
+        len: u32,
+    ) -> Result<Range<usize>, ReturnCode> {
+        // Empty ranges never touch memory, so their offset doesn't matter
+        if len == 0 {
+            return Ok(0..0);
+        }
+
+        let offset = word_to_u32(offset).ok_or(ReturnCode::InvalidMemoryAccess)?;
+        let end = offset
+            .checked_add(len)
+            .ok_or(ReturnCode::InvalidMemoryAccess)?;
+        self.expand_memory(end)?;
+        Ok(offset as usize..end as usize)
+    }
+
+    /// Grows memory in whole words until it is at least the given number of bytes long, charging
+    /// for the words it grew by.
+    pub(crate) fn expand_memory(&mut self, min_len: u32) -> Result<(), ReturnCode> {
+        if min_len <= self.memory_len {
+            return Ok(());
+        }
+
+        let new_len = match min_len.checked_next_multiple_of(WORD_SIZE_BYTES) {
+            Some(new_len) if new_len <= self.memory_cap => new_len,
+            _ => return Err(ReturnCode::InvalidMemoryAccess),
+        };
+        self.use_gas(gas::memory_cost(new_len) - gas::memory_cost(self.memory_len))?;
+        self.memory.resize(new_len as usize, 0);
+        self.memory_len = new_len;
+        Ok(())
+    }
+
+    /// Takes the given amount of gas from the gas remaining. Fails with OutOfGas, without taking
+    /// anything, if there isn't enough left.
+    pub(crate) fn use_gas(&mut self, amount: u64) -> Result<(), ReturnCode> {
+        if amount > self.gas_remaining {
+            return Err(ReturnCode::OutOfGas);
+        }
+        self.gas_remaining -= amount;
+        Ok(())
+    }
+
+    /// Gives back gas that was set aside for a call or creation but not used by it.
+    pub(crate) fn return_gas(&mut self, amount: u64) {
+        self.gas_remaining += amount;
+    }
+
+    /// Charges for accessing the given account. Since Berlin, accessing an account costs more the
+    /// first time in a transaction; the cost of later accesses is part of the static cost of the
+    /// instruction, so only the difference is charged here.
+    pub(crate) fn access_account(&mut self, address: &Address) -> Result<(), ReturnCode> {
+        if !self.spec_id().is_enabled_in(SpecId::Berlin) {
+            return Ok(());
+        }
+        if !self.execution_mut().warm_account(address) {
+            return Ok(());
+        }
+        self.use_gas(gas::COLD_ACCOUNT_ACCESS - gas::WARM_ACCESS)
+    }
+
+    /// Charges for reading the given slot of the current contract's storage, the same way as
+    /// access_account.
+    pub(crate) fn access_slot(&mut self, key: &Word) -> Result<(), ReturnCode> {
+        if !self.spec_id().is_enabled_in(SpecId::Berlin) {
+            return Ok(());
+        }
+        let address = *self.address();
+        if !self.execution_mut().warm_slot(&address, key) {
+            return Ok(());
+        }
+        self.use_gas(gas::COLD_SLOAD - gas::WARM_ACCESS)
+    }
+
+    /// Checks that the data returned by init code can be stored as the code of the new contract.
+    /// Returns false, and fails the context with InvalidCode, if it can't. Code over the size limit
+    /// can't be stored since Spurious Dragon, and neither can code starting with the reserved 0xEF
+    /// byte since London.
+    pub fn check_code_deposit(&mut self) -> bool {
+        let spec_id = self.spec_id();
+        let code = self.return_data();
+        let too_large = gas::max_code_size(spec_id).is_some_and(|max| code.len() > max);
+        let reserved = spec_id.is_enabled_in(SpecId::London) && code.first() == Some(&0xef);
+        if too_large || reserved {
+            self.set_result(ReturnCode::InvalidCode);
+            return false;
+        }
+        true
+    }
+
+    /// Charges for storing the data returned by init code as the code of the new contract. Returns
+    /// false, and fails the context with OutOfGas, if there isn't enough gas left. Before
+    /// Homestead, running out of gas here wasn't a failure; the contract was created without code
+    /// instead, which shows up as the init code returning nothing.
+    pub fn charge_code_deposit(&mut self) -> bool {
+        let cost = gas::CODE_DEPOSIT_BYTE * self.return_len as u64;
+        if self.use_gas(cost).is_ok() {
+            return true;
+        }
+        if !self.spec_id().is_enabled_in(SpecId::Homestead) {
+            self.return_len = 0;
+            return true;
+        }
+        self.set_result(ReturnCode::OutOfGas);
+        false
+    }
+
+    /// Fills the destination with input starting at the given offset. Bytes past the end of the
+    /// input are read as zero.
+    pub(crate) fn read_input(&self, offset: &Word, dest: &mut [u8]) {
+        read_padded(&self.input, offset, dest);
+    }
+
+    /// Returns the fork whose rules the context runs by.
+    pub(crate) fn spec_id(&self) -> SpecId {
+        // The execution outlives every context created for it
+        unsafe { self.execution.as_ref() }.unwrap().spec_id()
+    }
+
+    /// Returns the execution this context belongs to.
+    pub(crate) fn execution_mut(&mut self) -> &mut Execution {
+        // The execution outlives every context created for it
+        unsafe { self.execution.as_mut() }.unwrap()
+    }
+
+    /// Records how the contract run in this context finished. Failures other than reverts use up
+    /// all the gas that was left.
+    pub(crate) fn set_result(&mut self, result: ReturnCode) {
+        if !result.is_success() && result != ReturnCode::Revert {
+            self.gas_remaining = 0;
+        }
+        self.result = result;
+    }
+
+    /// Creates a new context for a call of the given kind to the contract at the given address and
+    /// adds it to the sub calls. The kind decides which account the callee acts as and who it sees
+    /// as its caller.
+    pub(crate) fn init_sub_call(
+        &mut self,
+        kind: CallKind,
+        to: Address,
+        value: Word,
+        input: Vec<u8>,
+        gas_limit: u64,
+    ) -> &mut Context {
+        let address = kind.callee_address(self, to);
+        let (caller, value) = match kind {
+            CallKind::DelegateCall => (self.tx_info.caller, self.tx_info.value),
+            _ => (self.tx_info.address, value),
+        };
+
+        // Only the message changes; the transaction stays the same for every call it makes
+        let tx_info = TxInfo {
+            caller,
+            address,
+            value,
+            ..self.tx_info
+        };
+        let mut sub_ctx = Context::new(tx_info, input, gas_limit, self.execution);
+        sub_ctx.is_static = self.is_static || kind == CallKind::StaticCall;
+        sub_ctx.set_memory_cap(self.memory_cap);
+        self.sub_calls.push(sub_ctx);
+        self.sub_calls.last_mut().unwrap()
+    }
+}
+
+/// The kinds of calls a contract can make to another contract.
+///
+/// - Call runs the callee as itself.
+/// - CallCode runs the callee's code as the caller, with the caller's storage.
+/// - DelegateCall is like CallCode, but also keeps the caller's caller and value.
+/// - StaticCall is like Call, but the callee and everything it calls can't modify state.
+#[derive(Clone, Copy, Debug, PartialEq)]
+#[repr(u8)]
+pub enum CallKind {
+    Call = 0,
+    CallCode = 1,
+    DelegateCall = 2,
+    StaticCall = 3,
+}
+
+impl CallKind {
+    /// Returns the address of the account that a call of this kind to the given address runs as.
+    pub(crate) fn callee_address(&self, caller_ctx: &Context, to: Address) -> Address {
+        match self {
+            CallKind::Call | CallKind::StaticCall => to,
+            CallKind::CallCode | CallKind::DelegateCall => caller_ctx.tx_info.address,
+        }
+    }
+}
+
+/// State shared by every context of a single contract run.
+pub struct Execution {
+    storage: Box<dyn Storage>,
+    spec_id: SpecId,
+
+    // Values of the slots changed in the transaction, as they were before the first change
+    original_slots: HashMap<(Address, Word), Word>,
+    refund: u64,
+
+    transient_storage: HashMap<(Address, Word), Word>,
+    logs: Vec<Log>,
+    deployer: Option<Deployer>,
+    code_registry: Option<Rc<RefCell<CodeRegistry>>>,
+
+    // Accounts and slots accessed so far in the transaction
+    accessed_accounts: HashSet<Address>,
+    accessed_slots: HashSet<(Address, Word)>,
+
+    journal: Vec<JournalEntry>,
+    frames: Vec<Frame>,
+}
+
+/// A contract invocation that is currently executing.
+struct Frame {
+    address: Address,
+
+    // Where the journal stood when the frame was entered
+    checkpoint: usize,
+}
+
+/// Compiles the init code of a contract being created at the given address and returns the
+/// function that runs it. Returns None if the code couldn't be compiled.
+pub type BuildInitCodeFn =
+    unsafe fn(host: *const c_void, address: &Address, code: &[u8]) -> Option<ContractFunc>;
+
+/// Compiles the runtime code of a newly created contract into a function named after the given
+/// address and the hash of the code, see `mangle_deployed_contract_fn`. Returns false if the code
+/// couldn't be compiled.
+pub type BuildContractFn = unsafe fn(host: *const c_void, address: &Address, code: &[u8]) -> bool;
+
+/// Hooks into the host for compiling the code of contracts created during an execution. The
+/// runtime can't compile code by itself, so the host hands over its hooks along with a pointer to
+/// itself, which is passed back to every hook.
+#[derive(Clone, Copy)]
+pub struct Deployer {
+    host: *const c_void,
+    build_init_code: BuildInitCodeFn,
+    build_contract: BuildContractFn,
+}
+
+impl Deployer {
+    /// # Safety
+    ///
+    /// The host pointer must be what the hooks expect, and must stay valid for as long as the
+    /// deployer is in use.
+    pub unsafe fn new(
+        host: *const c_void,
+        build_init_code: BuildInitCodeFn,
+        build_contract: BuildContractFn,
+    ) -> Self {
+        Deployer {
+            host,
+            build_init_code,
+            build_contract,
+        }
+    }
+
+    pub(crate) fn build_init_code(&self, address: &Address, code: &[u8]) -> Option<ContractFunc> {
+        unsafe { (self.build_init_code)(self.host, address, code) }
+    }
+
+    pub(crate) fn build_contract(&self, address: &Address, code: &[u8]) -> bool {
+        unsafe { (self.build_contract)(self.host, address, code) }
+    }
+}
+
+/// An event emitted by one of the LOG instructions.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Log {
+    address: Address,
+    topics: Vec<Word>,
+    data: Vec<u8>,
+}
+
+impl Log {
+    pub fn new(address: Address, topics: Vec<Word>, data: Vec<u8>) -> Self {
+        Log {
+            address,
+            topics,
+            data,
+        }
+    }
+
+    /// Returns the address of the contract that emitted the log.
+    pub fn address(&self) -> &Address {
+        &self.address
+    }
+
+    /// Returns the topics of the log. They use the same little-endian word layout as the stack.
+    pub fn topics(&self) -> &[Word] {
+        &self.topics
+    }
+
+    pub fn data(&self) -> &[u8] {
+        &self.data
+    }
+}
+
+/// A reversible change made during an execution.
+enum JournalEntry {
+    StorageChanged {
+        address: Address,
+        key: Word,
+        previous: Word,
+    },
+    TransientStorageChanged {
+        address: Address,
+        key: Word,
+        previous: Word,
+    },
+    LogAdded,
+    AccountChanged {
+        address: Address,
+        previous: Account,
+    },
+    AccountWarmed {
+        address: Address,
+    },
+    SlotWarmed {
+        address: Address,
+        key: Word,
+    },
+    RefundChanged {
+        previous: u64,
+    },
+}
+
+impl Execution {
+    pub fn new(storage: Box<dyn Storage>, spec_id: SpecId) -> Self {
+        Execution {
+            storage,
+            spec_id,
+            original_slots: HashMap::new(),
+            refund: 0,
+            transient_storage: HashMap::new(),
+            logs: Vec::new(),
+            deployer: None,
+            code_registry: None,
+            accessed_accounts: HashSet::new(),
+            accessed_slots: HashSet::new(),
+            journal: Vec::new(),
+            frames: Vec::new(),
+        }
+    }
+
+    pub fn storage(&self) -> &dyn Storage {
+        self.storage.as_ref()
+    }
+
+    /// Returns the fork whose rules the execution runs by.
+    pub fn spec_id(&self) -> SpecId {
+        self.spec_id
+    }
+
+    pub(crate) fn storage_store(&mut self, address: &Address, key: &Word, value: Word) {
+        let previous = self.storage.load(address, key);
+        self.journal.push(JournalEntry::StorageChanged {
+            address: *address,
+            key: *key,
+            previous,
+        });
+        self.storage.store(address, key, value);
+    }
+
+    /// Returns the value the given slot had when the transaction started. It must be called
+    /// before the slot is first changed.
+    pub(crate) fn original_storage_load(&mut self, address: &Address, key: &Word) -> Word {
+        let storage = &self.storage;
+        *self
+            .original_slots
+            .entry((*address, *key))
+            .or_insert_with(|| storage.load(address, key))
+    }
+
+    /// Returns the gas refunded so far in the transaction.
+    pub fn refund(&self) -> u64 {
+        self.refund
+    }
+
+    /// Adds the given amount, which may be negative, to the refund counter.
+    pub(crate) fn add_refund(&mut self, amount: i64) {
+        if amount == 0 {
+            return;
+        }
+        self.journal.push(JournalEntry::RefundChanged {
+            previous: self.refund,
+        });
+        self.refund = self.refund.saturating_add_signed(amount);
+    }
+
+    pub(crate) fn transient_storage_load(&self, address: &Address, key: &Word) -> Word {
+        self.transient_storage
+            .get(&(*address, *key))
+            .copied()
+            .unwrap_or([0; WORD_SIZE_BYTES as usize])
+    }
+
+    pub(crate) fn transient_storage_store(&mut self, address: &Address, key: &Word, value: Word) {
+        let previous = self.transient_storage_load(address, key);
+        self.journal.push(JournalEntry::TransientStorageChanged {
+            address: *address,
+            key: *key,
+            previous,
+        });
+        self.transient_storage.insert((*address, *key), value);
+    }
+
+    /// Returns every log emitted by frames that haven't been reverted, in the order they were
+    /// emitted.
+    pub fn logs(&self) -> &[Log] {
+        &self.logs
+    }
+
+    pub(crate) fn add_log(&mut self, log: Log) {
+        self.journal.push(JournalEntry::LogAdded);
+        self.logs.push(log);
+    }
+
+    /// Returns the balance of the given account.
+    pub fn balance(&self, address: &Address) -> Word {
+        *self.storage.account(address).balance()
+    }
+
+    /// Returns the nonce of the given account. Accounts start at zero, and contracts go up by one
+    /// for every contract they create.
+    pub fn nonce(&self, address: &Address) -> u64 {
+        self.storage.account(address).nonce()
+    }
+
+    pub(crate) fn set_nonce(&mut self, address: &Address, nonce: u64) {
+        self.update_account(address, |account| account.set_nonce(nonce));
+    }
+
+    pub(crate) fn set_code(&mut self, address: &Address, code: Vec<u8>) {
+        self.update_account(address, |account| account.set_code(code));
+    }
+
+    /// Moves value from one account to another. Returns false, without moving anything, if the
+    /// sender can't afford it or the recipient's balance would overflow.
+    pub(crate) fn transfer(&mut self, from: &Address, to: &Address, value: &Word) -> bool {
+        let from_balance = match word_checked_sub(&self.balance(from), value) {
+            Some(balance) => balance,
+            None => return false,
+        };
+
+        // Sending to yourself leaves the balance as it was
+        if from == to {
+            return true;
+        }
+        let to_balance = match word_checked_add(&self.balance(to), value) {
+            Some(balance) => balance,
+            None => return false,
+        };
+
+        self.update_account(from, |account| account.set_balance(from_balance));
+        self.update_account(to, |account| account.set_balance(to_balance));
+        true
+    }
+
+    /// Changes the given account, keeping what it was before in the journal.
+    fn update_account(&mut self, address: &Address, update: impl FnOnce(&mut Account)) {
+        let previous = self.storage.account(address);
+        let mut account = previous.clone();
+        update(&mut account);
+        self.journal.push(JournalEntry::AccountChanged {
+            address: *address,
+            previous,
+        });
+        self.storage.set_account(address, account);
+    }
+
+    /// Sets the hooks used to compile contracts created during the execution. Without them, every
+    /// contract creation fails.
+    pub fn set_deployer(&mut self, deployer: Deployer) {
+        self.deployer = Some(deployer);
+    }
+
+    pub(crate) fn deployer(&self) -> Option<Deployer> {
+        self.deployer
+    }
+
+    /// Sets the registry holding the code of contracts built by the host. Without it, only code
+    /// deployed during the execution can be inspected.
+    pub fn set_code_registry(&mut self, code_registry: Rc<RefCell<CodeRegistry>>) {
+        self.code_registry = Some(code_registry);
+    }
+
+    /// Returns the code of the given account. Code deployed during the execution is kept in the
+    /// account, while the code of contracts built by the host comes from the code registry.
+    pub fn code(&self, address: &Address) -> Vec<u8> {
+        let account = self.storage.account(address);
+        if !account.code().is_empty() {
+            return account.code().to_vec();
+        }
+        self.code_registry
+            .as_ref()
+            .and_then(|registry| registry.borrow().code(address).map(<[u8]>::to_vec))
+            .unwrap_or_default()
+    }
+
+    /// Returns true if the given account has code, either deployed during the execution or built
+    /// by the host. Compiled code outlives the creation that deployed it, so this decides whether
+    /// it can run.
+    pub(crate) fn has_code(&self, address: &Address) -> bool {
+        if !self.storage.account(address).code().is_empty() {
+            return true;
+        }
+        self.code_registry
+            .as_ref()
+            .is_some_and(|registry| registry.borrow().code_hash(address).is_some())
+    }
+
+    /// Returns the pointer to the compiled function running the code of the given account, if there
+    /// is one. Code deployed during an execution is looked up by its hash, and the code of
+    /// contracts built by the host by the address alone.
+    pub(crate) fn contract_fn(
+        &self,
+        jit_engine: &ExecutionEngine,
+        address: &Address,
+    ) -> Option<usize> {
+        let account = self.storage.account(address);
+        match account.code().is_empty() {
+            true => jet_contract_fn_try_lookup(jit_engine, address),
+            false => {
+                jet_deployed_contract_fn_try_lookup(jit_engine, address, &hash_code(account.code()))
+            }
+        }
+    }
+
+    /// Returns true if the given account has a balance, a nonce or code.
+    pub fn account_exists(&self, address: &Address) -> bool {
+        if !self.storage.account(address).is_empty() {
+            return true;
+        }
+        self.code_registry
+            .as_ref()
+            .is_some_and(|registry| registry.borrow().code_hash(address).is_some())
+    }
+
+    /// Returns the hash of the code of the given account. Accounts without code hash to the hash
+    /// of empty code, unless they don't exist at all, in which case the hash is zero.
+    pub fn code_hash(&self, address: &Address) -> Word {
+        let account = self.storage.account(address);
+        if !account.code().is_empty() {
+            return hash_code(account.code());
+        }
+        let registered = self
+            .code_registry
+            .as_ref()
+            .and_then(|registry| registry.borrow().code_hash(address).copied());
+        match registered {
+            Some(hash) => hash,
+            None if account.is_empty() => [0; WORD_SIZE_BYTES as usize],
+            None => hash_code(&[]),
+        }
+    }
+
+    /// Marks the accounts and slots that start out warm: the sender and recipient of the
+    /// transaction, the coinbase since Shanghai and everything in the access list of the
+    /// transaction. Jet has no precompiled contracts, so unlike other clients it doesn't warm their
+    /// addresses.
+    pub fn warm_up(
+        &mut self,
+        tx_info: &TxInfo,
+        coinbase: &Address,
+        access_list: &[AccessListItem],
+    ) {
+        self.accessed_accounts.insert(tx_info.origin);
+        self.accessed_accounts.insert(tx_info.address);
+        if self.spec_id.is_enabled_in(SpecId::Shanghai) {
+            self.accessed_accounts.insert(*coinbase);
+        }
+        for item in access_list {
+            self.accessed_accounts.insert(item.address);
+            for key in item.storage_keys.iter() {
+                self.accessed_slots.insert((item.address, *key));
+            }
+        }
+    }
+
+    /// Marks the given account as accessed. Returns true if it hadn't been accessed before.
+    pub(crate) fn warm_account(&mut self, address: &Address) -> bool {
+        if !self.accessed_accounts.insert(*address) {
+            return false;
+        }
+        self.journal
+            .push(JournalEntry::AccountWarmed { address: *address });
+        true
+    }
+
+    /// Marks the given slot as accessed. Returns true if it hadn't been accessed before.
+    pub(crate) fn warm_slot(&mut self, address: &Address, key: &Word) -> bool {
+        if !self.accessed_slots.insert((*address, *key)) {
+            return false;
+        }
+        self.journal.push(JournalEntry::SlotWarmed {
+            address: *address,
+            key: *key,
+        });
+        true
+    }
+
+    /// Clears the transient storage. This happens at the end of every top-level run, after which
+    /// the changes can no longer be reverted.
+    pub fn clear_transient_storage(&mut self) {
+        self.transient_storage.clear();
+        self.journal.clear();
+    }
+
+    /// Returns the addresses of the contracts currently executing, from the outermost to the
+    /// innermost. A contract shows up more than once when it is re-entered.
+    pub fn call_stack(&self) -> impl Iterator<Item = &Address> {
+        self.frames.iter().map(|frame| &frame.address)
+    }
+
+    /// Returns the depth of the innermost executing frame. The top-level frame has depth 0.
+    pub fn call_depth(&self) -> usize {
+        self.frames.len().saturating_sub(1)
+    }
+
+    /// Returns true if another frame can be entered without going past the call depth limit.
+    pub(crate) fn can_enter_frame(&self) -> bool {
+        self.frames.len() <= CALL_DEPTH_LIMIT
+    }
+
+    /// Starts executing a frame for the contract at the given address. Returns false, without
+    /// entering the frame, if it would be deeper than the call depth limit.
+    pub fn enter_frame(&mut self, address: Address) -> bool {
+        if !self.can_enter_frame() {
+            return false;
+        }
+        self.frames.push(Frame {
+            address,
+            checkpoint: self.journal.len(),
+        });
+        true
+    }
+
+    /// Finishes executing the innermost frame. If it failed, every change made since it was
+    /// entered is undone.
+    pub fn exit_frame(&mut self, success: bool) {
+        let frame = self.frames.pop().unwrap();
+        if !success {
+            self.revert_to(frame.checkpoint);
+        }
+    }
+
+    /// Undoes every change made since the given checkpoint.
+    fn revert_to(&mut self, checkpoint: usize) {
+        while self.journal.len() > checkpoint {
+            match self.journal.pop().unwrap() {
+                JournalEntry::StorageChanged {
+                    address,
+                    key,
+                    previous,
+                } => self.storage.store(&address, &key, previous),
+                JournalEntry::TransientStorageChanged {
+                    address,
+                    key,
+                    previous,
+                } => {
+                    self.transient_storage.insert((address, key), previous);
+                }
+                JournalEntry::LogAdded => {
+                    self.logs.pop();
+                }
+                JournalEntry::AccountChanged { address, previous } => {
+                    self.storage.set_account(&address, previous);
+                }
+                JournalEntry::AccountWarmed { address } => {
+                    self.accessed_accounts.remove(&address);
+                }
+                JournalEntry::SlotWarmed { address, key } => {
+                    self.accessed_slots.remove(&(address, key));
+                }
+                JournalEntry::RefundChanged { previous } => {
+                    self.refund = previous;
+                }
+            }
+        }
     }
 }
 
@@ -152,20 +944,50 @@ impl Context {
 pub struct ContractRun {
     result: ReturnCode,
     ctx: Context,
+
+    // Boxed, since the context and its sub calls point at it
+    execution: Box<Execution>,
 }
 
 impl ContractRun {
-    pub fn new(result: ReturnCode, ctx: Context) -> Self {
-        ContractRun { result, ctx }
+    pub fn new(result: ReturnCode, mut ctx: Context, execution: Box<Execution>) -> Self {
+        ctx.set_result(result.clone());
+        ContractRun {
+            result,
+            ctx,
+            execution,
+        }
     }
 
     pub fn result(&self) -> ReturnCode {
         self.result.clone()
     }
 
+    /// Returns the context of the top-level frame. The frames of every call made during the run
+    /// can be reached through its sub calls.
     pub fn ctx(&self) -> &Context {
         &self.ctx
     }
+
+    /// Returns the gas used by the run. This doesn't include the intrinsic cost of the
+    /// transaction, which is charged before the run starts.
+    pub fn gas_used(&self) -> u64 {
+        self.ctx.gas_used()
+    }
+
+    /// Returns the gas refunded by the run. It is uncapped; applying the cap of half the gas used
+    /// by the transaction, or one fifth since London (EIP-3529), is up to the caller.
+    pub fn gas_refund(&self) -> u64 {
+        self.execution.refund()
+    }
+
+    pub fn storage(&self) -> &dyn Storage {
+        self.execution.storage()
+    }
+
+    pub fn logs(&self) -> &[Log] {
+        self.execution.logs()
+    }
 }
 
 /// Information about the current block that gets exposed to the EVM.
@@ -179,11 +1001,28 @@ pub struct BlockInfo {
     blob_base_fee: u64,
     chain_id: u64,
     hash: Word,
+
+    // Hashes of the blocks before this one, starting with its parent
     hash_history: HashHistory,
     coinbase: Address,
 }
 
 impl BlockInfo {
+    /// Byte offsets of the fields in declaration order, which the block info type in the generated
+    /// code has to match.
+    pub const FIELD_OFFSETS: [usize; 10] = [
+        offset_of!(BlockInfo, number),
+        offset_of!(BlockInfo, difficulty),
+        offset_of!(BlockInfo, gas_limit),
+        offset_of!(BlockInfo, timestamp),
+        offset_of!(BlockInfo, base_fee),
+        offset_of!(BlockInfo, blob_base_fee),
+        offset_of!(BlockInfo, chain_id),
+        offset_of!(BlockInfo, hash),
+        offset_of!(BlockInfo, hash_history),
+        offset_of!(BlockInfo, coinbase),
+    ];
+
     #[allow(clippy::too_many_arguments)]
     pub fn new(
         number: u64,
@@ -243,6 +1082,7 @@ impl BlockInfo {
         &self.hash
     }
 
+    /// Returns the hashes of the blocks before this one, starting with its parent.
     pub fn hash_history(&self) -> &HashHistory {
         &self.hash_history
     }
@@ -252,6 +1092,102 @@ impl BlockInfo {
     }
 }
 
+/// Information about the transaction being executed and the message a contract runs for, exposed
+/// to the EVM. The origin and gas price are the same for every call made by the transaction, while
+/// the rest changes from call to call.
+#[derive(Clone, Copy, Debug, PartialEq)]
+#[repr(C)]
+pub struct TxInfo {
+    origin: Address,
+    gas_price: Word,
+    caller: Address,
+    address: Address,
+    value: Word,
+}
+
+impl TxInfo {
+    /// Byte offsets of the fields in declaration order, which the transaction info type in the
+    /// generated code has to match.
+    pub const FIELD_OFFSETS: [usize; 5] = [
+        offset_of!(TxInfo, origin),
+        offset_of!(TxInfo, gas_price),
+        offset_of!(TxInfo, caller),
+        offset_of!(TxInfo, address),
+        offset_of!(TxInfo, value),
+    ];
+
+    pub fn new(
+        origin: Address,
+        gas_price: Word,
+        caller: Address,
+        address: Address,
+        value: Word,
+    ) -> Self {
+        TxInfo {
+            origin,
+            gas_price,
+            caller,
+            address,
+            value,
+        }
+    }
+
+    /// Returns the address of the account that sent the transaction.
+    pub fn origin(&self) -> &Address {
+        &self.origin
+    }
+
+    pub fn gas_price(&self) -> &Word {
+        &self.gas_price
+    }
+
+    /// Returns the address of the account that made the call.
+    pub fn caller(&self) -> &Address {
+        &self.caller
+    }
+
+    /// Returns the address of the account the contract runs as.
+    pub fn address(&self) -> &Address {
+        &self.address
+    }
+
+    /// Returns the value sent with the call.
+    pub fn value(&self) -> &Word {
+        &self.value
+    }
+
+    /// Returns the same transaction and message, sent to a different address.
+    pub fn with_address(&self, address: Address) -> Self {
+        TxInfo { address, ..*self }
+    }
+}
+
+/// An entry of an EIP-2930 access list: an account, and slots of its storage, that a transaction
+/// declares it will access. They start out warm, so the transaction pays less to access them.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct AccessListItem {
+    address: Address,
+    storage_keys: Vec<Word>,
+}
+
+impl AccessListItem {
+    pub fn new(address: Address, storage_keys: Vec<Word>) -> Self {
+        AccessListItem {
+            address,
+            storage_keys,
+        }
+    }
+
+    pub fn address(&self) -> &Address {
+        &self.address
+    }
+
+    /// Returns the keys of the slots. They use the same little-endian word layout as the stack.
+    pub fn storage_keys(&self) -> &[Word] {
+        &self.storage_keys
+    }
+}
+
 /// Return codes returned by contract function calls.
 /// - Negative values are Jet-level failures.
 /// - Positive values are successfully captured EVM-returns.
@@ -273,6 +1209,193 @@ pub enum ReturnCode {
     Revert = 64,
     Invalid = 65,
     JumpFailure = 66,
+    InvalidMemoryAccess = 67,
+    InvalidReturnDataAccess = 68,
+    StaticCallViolation = 69,
+    OutOfGas = 70,
+    InvalidCode = 71,
+}
+
+impl ReturnCode {
+    /// Returns true if the code represents a successful EVM execution whose changes are kept.
+    pub fn is_success(&self) -> bool {
+        matches!(
+            self,
+            ReturnCode::ImplicitReturn | ReturnCode::ExplicitReturn | ReturnCode::Stop
+        )
+    }
+}
+
+/// Parses a hex encoded address, with or without a 0x prefix.
+pub fn parse_address(address: &str) -> Option<Address> {
+    let address = address.strip_prefix("0x").unwrap_or(address);
+    hex::decode(address).ok()?.try_into().ok()
+}
+
+/// Converts a stack word into a u64. Returns None if the value doesn't fit.
+pub fn word_to_u64(word: &Word) -> Option<u64> {
+    if word[8..].iter().any(|byte| *byte != 0) {
+        return None;
+    }
+    Some(u64::from_le_bytes(word[..8].try_into().unwrap()))
+}
+
+/// Converts a stack word into a u32. Returns None if the value doesn't fit.
+pub fn word_to_u32(word: &Word) -> Option<u32> {
+    if word[4..].iter().any(|byte| *byte != 0) {
+        return None;
+    }
+    Some(u32::from_le_bytes([word[0], word[1], word[2], word[3]]))
+}
+
+/// Fills the destination with the source starting at the given offset. Bytes past the end of the
+/// source are read as zero.
+pub(crate) fn read_padded(src: &[u8], offset: &Word, dest: &mut [u8]) {
+    let start = match word_to_u32(offset) {
+        Some(offset) => (offset as usize).min(src.len()),
+        None => src.len(),
+    };
+    let available = &src[start..];
+    let copy_len = available.len().min(dest.len());
+    dest[..copy_len].copy_from_slice(&available[..copy_len]);
+    dest[copy_len..].fill(0);
+}
+
+/// Adds two words. Returns None if the sum doesn't fit.
+pub(crate) fn word_checked_add(a: &Word, b: &Word) -> Option<Word> {
+    let mut sum = [0u8; WORD_SIZE_BYTES as usize];
+    let mut carry = 0u16;
+    for ((out, a), b) in sum.iter_mut().zip(a).zip(b) {
+        let byte = *a as u16 + *b as u16 + carry;
+        *out = byte as u8;
+        carry = byte >> 8;
+    }
+    (carry == 0).then_some(sum)
+}
+
+/// Subtracts the second word from the first. Returns None if the second word is larger.
+pub(crate) fn word_checked_sub(a: &Word, b: &Word) -> Option<Word> {
+    let mut diff = [0u8; WORD_SIZE_BYTES as usize];
+    let mut borrow = 0i16;
+    for ((out, a), b) in diff.iter_mut().zip(a).zip(b) {
+        let byte = *a as i16 - *b as i16 - borrow;
+        *out = byte.rem_euclid(256) as u8;
+        borrow = (byte < 0) as i16;
+    }
+    (borrow == 0).then_some(diff)
+}
+
+/// Multiplies two words, wrapping around on overflow.
+pub(crate) fn word_wrapping_mul(a: &Word, b: &Word) -> Word {
+    let mut product = [0u8; WORD_SIZE_BYTES as usize];
+    for (i, a) in a.iter().enumerate() {
+        // Bytes of the partial product past the end of the word are dropped
+        let mut carry = 0u32;
+        for (out, b) in product[i..].iter_mut().zip(b) {
+            let byte = *out as u32 + *a as u32 * *b as u32 + carry;
+            *out = byte as u8;
+            carry = byte >> 8;
+        }
+    }
+    product
+}
+
+/// Raises a word to the power of another by square-and-multiply, wrapping around on overflow.
+pub fn word_exp(base: &Word, exponent: &Word) -> Word {
+    let mut result = [0u8; WORD_SIZE_BYTES as usize];
+    result[0] = 1;
+    let mut square = *base;
+    for byte in exponent[..word_byte_len(exponent)].iter() {
+        for bit in 0..8 {
+            if byte >> bit & 1 == 1 {
+                result = word_wrapping_mul(&result, &square);
+            }
+            square = word_wrapping_mul(&square, &square);
+        }
+    }
+    result
+}
+
+/// Returns the number of bytes needed to hold the value of a word, i.e. its size without leading
+/// zeros.
+pub fn word_byte_len(word: &Word) -> usize {
+    word.iter()
+        .rposition(|byte| *byte != 0)
+        .map_or(0, |i| i + 1)
+}
+
+/// Converts a stack word into an address by taking its low-order bytes.
+pub fn address_from_word(word: &Word) -> Address {
+    let mut address = [0u8; ADDRESS_SIZE_BYTES];
+    for (i, byte) in address.iter_mut().rev().enumerate() {
+        *byte = word[i];
+    }
+    address
+}
+
+/// Converts an address into a stack word, the inverse of address_from_word.
+pub fn address_to_word(address: &Address) -> Word {
+    let mut word = [0u8; WORD_SIZE_BYTES as usize];
+    for (i, byte) in address.iter().rev().enumerate() {
+        word[i] = *byte;
+    }
+    word
+}
+
+/// Hex encodes an address with a 0x prefix.
+pub fn format_address(address: &Address) -> String {
+    format!("0x{}", hex::encode(address))
+}
+
+/// Returns the address of the contract created by CREATE, which is derived from the RLP encoding
+/// of the creator's address and nonce.
+pub fn create_address(creator: &Address, nonce: u64) -> Address {
+    // Leading zero bytes are dropped from the nonce, so zero encodes as an empty string
+    let nonce_bytes = nonce.to_be_bytes();
+    let nonce_bytes = &nonce_bytes[nonce.leading_zeros() as usize / 8..];
+
+    // Both items are short, so the list is too
+    let mut items = Vec::new();
+    rlp_append_bytes(&mut items, creator);
+    rlp_append_bytes(&mut items, nonce_bytes);
+    let mut encoded = vec![0xc0 + items.len() as u8];
+    encoded.extend(items);
+
+    address_from_hash(&Keccak256::digest(&encoded))
+}
+
+/// Returns the address of the contract created by CREATE2, which is derived from the creator's
+/// address, the salt and the hash of the init code.
+pub fn create2_address(creator: &Address, salt: &Word, init_code: &[u8]) -> Address {
+    let mut hasher = Keccak256::new();
+    hasher.update([0xff]);
+    hasher.update(creator);
+
+    // The salt is hashed big-endian, like it would be in memory
+    let mut salt = *salt;
+    salt.reverse();
+    hasher.update(salt);
+
+    hasher.update(Keccak256::digest(init_code));
+    address_from_hash(&hasher.finalize())
+}
+
+/// Appends the RLP encoding of a string of fewer than 56 bytes.
+fn rlp_append_bytes(out: &mut Vec<u8>, bytes: &[u8]) {
+    // A single byte below 0x80 is its own encoding
+    if let [byte] = bytes {
+        if *byte < 0x80 {
+            out.push(*byte);
+            return;
+        }
+    }
+    out.push(0x80 + bytes.len() as u8);
+    out.extend_from_slice(bytes);
+}
+
+/// Takes the low-order bytes of a hash as an address.
+fn address_from_hash(hash: &[u8]) -> Address {
+    hash[hash.len() - ADDRESS_SIZE_BYTES..].try_into().unwrap()
 }
 
 /// Mangles the given address into a contract function name.
@@ -280,13 +1403,54 @@ pub fn mangle_contract_fn(address: &str) -> String {
     format!("{}{}", FN_CONTRACT_PREFIX, address)
 }
 
+/// Mangles the given address and the hash of code deployed to it during an execution into the name
+/// of the function running that code. Compiled code can't be taken out of the JIT, so the hash
+/// keeps an address that is created again, after an earlier creation was undone, from running the
+/// code of the earlier one.
+pub fn mangle_deployed_contract_fn(address: &str, code_hash: &Word) -> String {
+    format!(
+        "{}{}{}.{}",
+        FN_CONTRACT_PREFIX,
+        address,
+        FN_DEPLOYED_SUFFIX,
+        hex::encode(code_hash)
+    )
+}
+
+/// Mangles the given address into the name of a function running init code for it. Init code can
+/// run for the same address more than once, so every build gets an id of its own.
+pub fn mangle_init_code_fn(address: &str, id: usize) -> String {
+    format!(
+        "{}{}{}.{}",
+        FN_CONTRACT_PREFIX, address, FN_INIT_CODE_SUFFIX, id
+    )
+}
+
+/// Finds the pointer to the compiled contract function for the given address, if there is one.
+/// Unlike `jet_contract_fn_lookup`, a missing function isn't treated as an error.
+pub fn jet_contract_fn_try_lookup(
+    jit_engine: &ExecutionEngine,
+    address: &Address,
+) -> Option<usize> {
+    let fn_name = mangle_contract_fn(&format_address(address));
+    jit_engine.get_function_address(fn_name.as_str()).ok()
+}
+
+/// Finds the pointer to the function compiled for code with the given hash deployed to the given
+/// address during an execution, if there is one.
+pub fn jet_deployed_contract_fn_try_lookup(
+    jit_engine: &ExecutionEngine,
+    address: &Address,
+    code_hash: &Word,
+) -> Option<usize> {
+    let fn_name = mangle_deployed_contract_fn(&format_address(address), code_hash);
+    jit_engine.get_function_address(fn_name.as_str()).ok()
+}
+
 /// Finds the pointer to the compiled contract function for the given address.
-pub fn jet_contract_fn_lookup(jit_engine: &ExecutionEngine, addr_slice: &[u8]) -> usize {
+pub fn jet_contract_fn_lookup(jit_engine: &ExecutionEngine, address: &Address) -> usize {
     // Convert the address to a function name
-    let reversed_addr = addr_slice.iter().rev().cloned().collect::<Vec<u8>>();
-    let mut addr_str = "0x".to_owned();
-    addr_str.push_str(&hex::encode(reversed_addr.as_slice()));
-    let fn_name = mangle_contract_fn(addr_str.as_str());
+    let fn_name = mangle_contract_fn(&format_address(address));
 
     // Look up the function pointer
     match jit_engine.get_function_address(fn_name.as_str()) {
@@ -0,0 +1,178 @@
+use crate::{exec::{Word, word_byte_len}, spec::SpecId, WORD_SIZE_BYTES};
+
+// Costs that depend on the operands of an instruction, charged by the runtime on top of the
+// static cost of the instruction, which is charged by the contract function itself. Costs that
+// changed over time are the ones of the latest fork; see the functions below for the others.
+
+// Memory
+pub const MEMORY_WORD: u64 = 3;
+pub const MEMORY_QUADRATIC_DIVISOR: u64 = 512;
+
+// Arithmetic
+pub const EXP_BYTE: u64 = 50;
+
+// Copying, hashing and logging
+pub const COPY_WORD: u64 = 3;
+pub const KECCAK256_WORD: u64 = 6;
+pub const LOG_DATA_BYTE: u64 = 8;
+
+// Accounts and storage. Since Berlin, accessing one for the first time in a transaction makes it
+// warm, and later accesses cost less.
+pub const WARM_ACCESS: u64 = 100;
+pub const COLD_ACCOUNT_ACCESS: u64 = 2600;
+pub const COLD_SLOAD: u64 = 2100;
+
+// Storage
+pub const SSTORE_SET: u64 = 20000;
+pub const SSTORE_RESET: u64 = 5000;
+pub const SSTORE_CLEARS_REFUND: u64 = 15000;
+pub const SSTORE_SENTRY: u64 = 2300;
+pub const ACCESS_LIST_STORAGE_KEY: u64 = 1900;
+
+// Calls
+pub const CALL_VALUE_TRANSFER: u64 = 9000;
+pub const CALL_NEW_ACCOUNT: u64 = 25000;
+pub const CALL_STIPEND: u64 = 2300;
+
+// Contract creation
+pub const INIT_CODE_WORD: u64 = 2;
+pub const CODE_DEPOSIT_BYTE: u64 = 200;
+
+// Contract size limits, which only apply from the forks that introduced them; see the functions
+// below
+pub const MAX_CODE_SIZE: usize = 24576;
+pub const MAX_INITCODE_SIZE: usize = 2 * MAX_CODE_SIZE;
+
+/// Returns the cost of an operation charged per word of the given number of bytes. Partial words
+/// are charged as whole ones.
+pub fn word_cost(cost_per_word: u64, len: usize) -> u64 {
+    let words = (len as u64).div_ceil(WORD_SIZE_BYTES as u64);
+    cost_per_word * words
+}
+
+/// Returns the total cost of memory of the given size in bytes. Memory is paid for as it expands,
+/// by the difference between the cost of its new and old size.
+pub fn memory_cost(len: u32) -> u64 {
+    let words = (len as u64).div_ceil(WORD_SIZE_BYTES as u64);
+    MEMORY_WORD * words + words * words / MEMORY_QUADRATIC_DIVISOR
+}
+
+/// Returns the cost of EXP with the given exponent, which is charged per byte of the exponent,
+/// not counting leading zeros. Spurious Dragon raised the cost per byte from 10 (EIP-160).
+pub fn exp_cost(spec_id: SpecId, exponent: &Word) -> u64 {
+    let cost_per_byte = match spec_id.is_enabled_in(SpecId::SpuriousDragon) {
+        true => EXP_BYTE,
+        false => 10,
+    };
+    cost_per_byte * word_byte_len(exponent) as u64
+}
+
+/// Returns the size limit on the code returned by init code, if the given fork has one. Spurious
+/// Dragon introduced it (EIP-170).
+pub fn max_code_size(spec_id: SpecId) -> Option<usize> {
+    spec_id
+        .is_enabled_in(SpecId::SpuriousDragon)
+        .then_some(MAX_CODE_SIZE)
+}
+
+/// Returns the size limit on init code, if the given fork has one. Shanghai introduced it
+/// (EIP-3860).
+pub fn max_initcode_size(spec_id: SpecId) -> Option<usize> {
+    spec_id
+        .is_enabled_in(SpecId::Shanghai)
+        .then_some(MAX_INITCODE_SIZE)
+}
+
+/// Returns the cost of an SSTORE to a warm slot, and the change it makes to the refund counter.
+/// The original value is the one the slot had when the transaction started, and the current value
+/// is the one it has before the SSTORE.
+///
+/// Before net metering, the cost only depends on the current and new values. With it (EIP-1283 in
+/// Constantinople, then EIP-2200 from Istanbul on, as modified by EIP-2929 and EIP-3529), only the
+/// first change to a slot in a transaction pays the full price. Later changes cost as much as a
+/// read, and refunds are adjusted as the slot is cleared, set again or restored.
+pub fn sstore_cost(spec_id: SpecId, original: &Word, current: &Word, new: &Word) -> (u64, i64) {
+    let is_zero = |word: &Word| word.iter().all(|byte| *byte == 0);
+
+    if !is_net_metered(spec_id) {
+        let cost = match is_zero(current) && !is_zero(new) {
+            true => SSTORE_SET,
+            false => SSTORE_RESET,
+        };
+        let refund = match !is_zero(current) && is_zero(new) {
+            true => SSTORE_CLEARS_REFUND as i64,
+            false => 0,
+        };
+        return (cost, refund);
+    }
+
+    // Since Berlin, the cold part of the reset cost is charged along with the access instead
+    let read = storage_read_cost(spec_id);
+    let reset = match spec_id.is_enabled_in(SpecId::Berlin) {
+        true => SSTORE_RESET - COLD_SLOAD,
+        false => SSTORE_RESET,
+    };
+    let clears_refund = match spec_id.is_enabled_in(SpecId::London) {
+        true => reset + ACCESS_LIST_STORAGE_KEY,
+        false => SSTORE_CLEARS_REFUND,
+    } as i64;
+
+    // Storing the value a slot already has changes nothing
+    if current == new {
+        return (read, 0);
+    }
+
+    // The first change to a slot in the transaction
+    if original == current {
+        if is_zero(original) {
+            return (SSTORE_SET, 0);
+        }
+        let refund = match is_zero(new) {
+            true => clears_refund,
+            false => 0,
+        };
+        return (reset, refund);
+    }
+
+    // A slot that was already changed. A clear that was refunded and is now being undone gives
+    // the refund back, while a new clear earns one.
+    let mut refund = 0;
+    if !is_zero(original) {
+        if is_zero(current) {
+            refund -= clears_refund;
+        } else if is_zero(new) {
+            refund += clears_refund;
+        }
+    }
+
+    // Restoring the original value refunds what the first change cost, less a read
+    if original == new {
+        refund += match is_zero(original) {
+            true => (SSTORE_SET - read) as i64,
+            false => (reset - read) as i64,
+        };
+    }
+    (read, refund)
+}
+
+/// Returns true if SSTORE is net metered in the given fork. Petersburg took net metering out
+/// again right after Constantinople, until Istanbul brought it back.
+fn is_net_metered(spec_id: SpecId) -> bool {
+    spec_id == SpecId::Constantinople || spec_id.is_enabled_in(SpecId::Istanbul)
+}
+
+/// Returns what net metered SSTOREs that don't change a slot for the first time cost, which is
+/// the cost of a warm SLOAD.
+fn storage_read_cost(spec_id: SpecId) -> u64 {
+    match spec_id {
+        _ if spec_id.is_enabled_in(SpecId::Berlin) => WARM_ACCESS,
+        _ if spec_id.is_enabled_in(SpecId::Istanbul) => 800,
+        _ => 200,
+    }
+}
+
+/// Returns the most gas that can be passed on to a call or creation out of the given gas: all of
+/// it but one 64th, which the caller keeps.
+pub fn all_but_one_64th(gas: u64) -> u64 {
+    gas - gas / 64
+}
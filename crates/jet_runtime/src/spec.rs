@@ -0,0 +1,55 @@
+use std::str::FromStr;
+
+/// The hardforks of Ethereum, in the order they were activated. Each one decides which
+/// instructions are available and what they cost. Forks that only moved the difficulty bomb are
+/// left out, since they don't change execution.
+#[derive(serde::Serialize, Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum SpecId {
+    Frontier,
+    Homestead,
+    TangerineWhistle,
+    SpuriousDragon,
+    Byzantium,
+    Constantinople,
+    Petersburg,
+    Istanbul,
+    Berlin,
+    London,
+    Paris,
+    Shanghai,
+    #[default]
+    Cancun,
+    Prague,
+}
+
+impl SpecId {
+    /// Returns true if the given fork is active in this one, i.e. this fork is the given one or
+    /// came after it.
+    pub fn is_enabled_in(self, fork: SpecId) -> bool {
+        self >= fork
+    }
+}
+
+impl FromStr for SpecId {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "frontier" => Ok(Self::Frontier),
+            "homestead" => Ok(Self::Homestead),
+            "tangerine_whistle" => Ok(Self::TangerineWhistle),
+            "spurious_dragon" => Ok(Self::SpuriousDragon),
+            "byzantium" => Ok(Self::Byzantium),
+            "constantinople" => Ok(Self::Constantinople),
+            "petersburg" => Ok(Self::Petersburg),
+            "istanbul" => Ok(Self::Istanbul),
+            "berlin" => Ok(Self::Berlin),
+            "london" => Ok(Self::London),
+            "paris" | "merge" => Ok(Self::Paris),
+            "shanghai" => Ok(Self::Shanghai),
+            "cancun" => Ok(Self::Cancun),
+            "prague" => Ok(Self::Prague),
+            _ => Err(()),
+        }
+    }
+}
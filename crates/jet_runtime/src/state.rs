@@ -0,0 +1,171 @@
+use std::collections::HashMap;
+
+use sha3::{Digest, Keccak256};
+
+use crate::{
+    exec::{Address, Word},
+    WORD_SIZE_BYTES,
+};
+
+/// Persistent state supplied by the host: the accounts and the storage of every contract.
+///
+/// Slots are keyed by the address of the contract that owns them and a 256-bit key. Keys and
+/// values use the same little-endian word layout as the stack.
+pub trait Storage {
+    /// Returns the value of the given slot, or the zero word if it has never been written.
+    fn load(&self, address: &Address, key: &Word) -> Word;
+
+    /// Sets the value of the given slot.
+    fn store(&mut self, address: &Address, key: &Word, value: Word);
+
+    /// Returns the account at the given address, or an empty account if there is none.
+    fn account(&self, address: &Address) -> Account;
+
+    /// Replaces the account at the given address.
+    fn set_account(&mut self, address: &Address, account: Account);
+}
+
+/// The balance, nonce and code of an address.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Account {
+    balance: Word,
+    nonce: u64,
+    code: Vec<u8>,
+}
+
+impl Account {
+    pub fn new(balance: Word, nonce: u64, code: Vec<u8>) -> Self {
+        Account {
+            balance,
+            nonce,
+            code,
+        }
+    }
+
+    /// Returns the balance in wei. It uses the same little-endian word layout as the stack.
+    pub fn balance(&self) -> &Word {
+        &self.balance
+    }
+
+    pub fn nonce(&self) -> u64 {
+        self.nonce
+    }
+
+    pub fn code(&self) -> &[u8] {
+        &self.code
+    }
+
+    pub fn set_balance(&mut self, balance: Word) {
+        self.balance = balance;
+    }
+
+    pub fn set_nonce(&mut self, nonce: u64) {
+        self.nonce = nonce;
+    }
+
+    pub fn set_code(&mut self, code: Vec<u8>) {
+        self.code = code;
+    }
+
+    /// Returns true if the account has no balance, nonce or code, which is the same as not
+    /// existing at all.
+    pub fn is_empty(&self) -> bool {
+        self.balance == [0; WORD_SIZE_BYTES as usize] && self.nonce == 0 && self.code.is_empty()
+    }
+}
+
+/// Storage that keeps every slot and account in memory.
+#[derive(Clone, Debug, Default)]
+pub struct InMemoryStorage {
+    slots: HashMap<(Address, Word), Word>,
+    accounts: HashMap<Address, Account>,
+}
+
+impl InMemoryStorage {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns all slots holding a non-zero value.
+    pub fn slots(&self) -> &HashMap<(Address, Word), Word> {
+        &self.slots
+    }
+
+    /// Returns all accounts that aren't empty.
+    pub fn accounts(&self) -> &HashMap<Address, Account> {
+        &self.accounts
+    }
+}
+
+impl Storage for InMemoryStorage {
+    fn load(&self, address: &Address, key: &Word) -> Word {
+        self.slots
+            .get(&(*address, *key))
+            .copied()
+            .unwrap_or([0; WORD_SIZE_BYTES as usize])
+    }
+
+    fn store(&mut self, address: &Address, key: &Word, value: Word) {
+        // Zero is the default value of every slot, so there is no need to keep it around
+        if value == [0; WORD_SIZE_BYTES as usize] {
+            self.slots.remove(&(*address, *key));
+            return;
+        }
+        self.slots.insert((*address, *key), value);
+    }
+
+    fn account(&self, address: &Address) -> Account {
+        self.accounts.get(address).cloned().unwrap_or_default()
+    }
+
+    fn set_account(&mut self, address: &Address, account: Account) {
+        // Like zero slots, empty accounts are the default and aren't kept around
+        if account.is_empty() {
+            self.accounts.remove(address);
+            return;
+        }
+        self.accounts.insert(*address, account);
+    }
+}
+
+/// The code of every contract built by the host, so that it can be inspected at runtime. Each
+/// address maps to the hash of its code, and each piece of code is kept once per hash.
+#[derive(Clone, Debug, Default)]
+pub struct CodeRegistry {
+    hashes: HashMap<Address, Word>,
+    code: HashMap<Word, Vec<u8>>,
+}
+
+impl CodeRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Records the code of the contract at the given address, replacing any code it had before.
+    pub fn insert(&mut self, address: Address, code: &[u8]) {
+        let hash = hash_code(code);
+        self.code.entry(hash).or_insert_with(|| code.to_vec());
+        self.hashes.insert(address, hash);
+    }
+
+    /// Returns the code of the contract at the given address, if it has any.
+    pub fn code(&self, address: &Address) -> Option<&[u8]> {
+        self.code_by_hash(self.code_hash(address)?)
+    }
+
+    /// Returns the hash of the code of the contract at the given address, if it has any.
+    pub fn code_hash(&self, address: &Address) -> Option<&Word> {
+        self.hashes.get(address)
+    }
+
+    pub fn code_by_hash(&self, hash: &Word) -> Option<&[u8]> {
+        self.code.get(hash).map(Vec::as_slice)
+    }
+}
+
+/// Returns the keccak256 hash of the given code. Unlike stack words, the hash is big-endian.
+pub fn hash_code(code: &[u8]) -> Word {
+    let mut hash = [0; WORD_SIZE_BYTES as usize];
+    hash.copy_from_slice(&Keccak256::digest(code));
+    hash
+}
@@ -10,8 +10,30 @@ pub const FN_STACK_SWAP: &str = "jet.stack.swap";
 pub const FN_MEM_STORE_WORD: &str = "jet.mem.store.word";
 pub const FN_MEM_STORE_BYTE: &str = "jet.mem.store.byte";
 pub const FN_MEM_LOAD: &str = "jet.mem.load";
+pub const FN_MEM_EXPAND: &str = "jet.mem.expand";
+pub const FN_MEM_COPY: &str = "jet.mem.copy";
+pub const FN_CALLDATA_LOAD: &str = "jet.calldata.load";
+pub const FN_CALLDATA_SIZE: &str = "jet.calldata.size";
+pub const FN_CALLDATA_COPY: &str = "jet.calldata.copy";
+pub const FN_STORAGE_LOAD: &str = "jet.storage.load";
+pub const FN_STORAGE_STORE: &str = "jet.storage.store";
+pub const FN_TRANSIENT_STORAGE_LOAD: &str = "jet.transient_storage.load";
+pub const FN_TRANSIENT_STORAGE_STORE: &str = "jet.transient_storage.store";
+pub const FN_BALANCE: &str = "jet.account.balance";
+pub const FN_SELF_BALANCE: &str = "jet.account.self_balance";
+pub const FN_CODE_COPY: &str = "jet.code.copy";
+pub const FN_EXT_CODE_SIZE: &str = "jet.code.ext_size";
+pub const FN_EXT_CODE_COPY: &str = "jet.code.ext_copy";
+pub const FN_EXT_CODE_HASH: &str = "jet.code.ext_hash";
+pub const FN_LOG: &str = "jet.log";
 pub const FN_CONTRACT_CALL: &str = "jet.contract.call";
-pub const FN_CONTRACT_CALL_RETURN_DATA_COPY: &str = "jet.contracts.call_return_data_copy";
+pub const FN_CONTRACT_CREATE: &str = "jet.contract.create";
+pub const FN_RETURN_DATA_SIZE: &str = "jet.return_data.size";
+pub const FN_RETURN_DATA_COPY: &str = "jet.return_data.copy";
+pub const FN_EXP: &str = "jet.ops.exp";
 pub const FN_KECCAK256: &str = "jet.ops.keccak256";
 
 pub const FN_CONTRACT_PREFIX: &str = "jet.contracts.";
+pub const FN_INIT_CODE_SUFFIX: &str = ".init";
+pub const FN_DEPLOYED_SUFFIX: &str = ".deployed";
+pub const CODE_SUFFIX: &str = ".code";
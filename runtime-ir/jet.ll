@@ -33,11 +33,10 @@ target triple = "x86_64-apple-macosx14.0.0"
   i32, ; jump_ptr
   i32, ; return offset
   i32, ; return length
-  ptr,; sub call ctx
   [1024 x %jet.types.word], ; stack
-  [1024 x i8], ; memory
   i32, ; mem length
-  i32 ; mem capacity
+  i32, ; mem capacity
+  i64 ; gas remaining
 }>
 
 
@@ -52,22 +51,46 @@ declare i1 @jet.stack.swap (ptr, i8)
 
 declare i8 @jet.mem.store.word (ptr, ptr, ptr)
 declare i8 @jet.mem.store.byte (ptr, ptr, ptr)
-declare ptr @jet.mem.load (ptr, ptr)
+declare i8 @jet.mem.load (ptr, ptr)
+declare i8 @jet.mem.expand (ptr, ptr, ptr)
+declare i8 @jet.mem.copy (ptr, ptr, ptr, ptr)
 
-declare i8 @jet.contract.call(ptr, ptr, ptr, ptr, ptr)
-declare i8 @jet.contracts.call_return_data_copy(ptr, ptr, i32, i32, i32)
+declare i8 @jet.calldata.load (ptr, ptr)
+declare i8 @jet.calldata.size (ptr)
+declare i8 @jet.calldata.copy (ptr, ptr, ptr, ptr)
 
-declare i8 @jet.ops.keccak256(ptr)
+declare i8 @jet.code.copy(ptr, ptr, i32, ptr, ptr, ptr)
+declare i8 @jet.code.ext_size(ptr, ptr)
+declare i8 @jet.code.ext_copy(ptr, ptr, ptr, ptr, ptr)
+declare i8 @jet.code.ext_hash(ptr, ptr)
+
+declare i8 @jet.storage.load (ptr, ptr)
+declare i8 @jet.storage.store (ptr, ptr, ptr)
+declare i8 @jet.transient_storage.load (ptr, ptr)
+declare i8 @jet.transient_storage.store (ptr, ptr, ptr)
+
+declare i8 @jet.account.balance(ptr, ptr)
+declare i8 @jet.account.self_balance(ptr)
+
+declare i8 @jet.log(ptr, ptr, ptr, i8, ptr, ptr, ptr, ptr)
+
+declare i8 @jet.contract.call(ptr, ptr, ptr, i8, ptr, ptr, ptr, ptr, ptr, ptr, ptr)
+declare i8 @jet.contract.create(ptr, ptr, ptr, ptr, ptr, ptr, ptr)
+declare i8 @jet.return_data.size(ptr)
+declare i8 @jet.return_data.copy(ptr, ptr, ptr, ptr)
+
+declare i8 @jet.ops.exp(ptr, ptr, ptr)
+declare i8 @jet.ops.keccak256(ptr, ptr, ptr)
 
 ;
 ; IR-based runtime function
 ;
-define i1 @jet.stack.push.i256 (%jet.types.exec_ctx*, i256) {
+define internal i1 @jet.stack.push.i256 (%jet.types.exec_ctx*, i256) {
 entry:
   ; Load stack pointer
   %stack.ptr.addr = getelementptr inbounds %jet.types.exec_ctx, ptr %0, i32 0, i32 0
   %stack.ptr = load i32, ptr %stack.ptr.addr
-  %stack.top.addr = getelementptr inbounds %jet.types.exec_ctx, ptr %0, i32 0, i32 5, i32 %stack.ptr
+  %stack.top.addr = getelementptr inbounds %jet.types.exec_ctx, ptr %0, i32 0, i32 4, i32 %stack.ptr
 
   ; Store word
   store i256 %1, ptr %stack.top.addr